@@ -0,0 +1,16 @@
+use lib::configuracion::esquema::{Clave, Esquema, TipoValor};
+use messaging_client::cliente::opciones::OpcionesConexion;
+
+/// Las claves de configuración del sistema de cámaras
+pub fn esquema() -> Esquema {
+    Esquema::new("cameras", "Sistema de cámaras de vigilancia")
+        .con_clave(
+            Clave::new(
+                "camaras",
+                TipoValor::Texto,
+                "Archivo csv donde se guardan las cámaras",
+            )
+            .con_defecto("camaras.csv"),
+        )
+        .con_claves(OpcionesConexion::claves())
+}
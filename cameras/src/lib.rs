@@ -1,3 +1,4 @@
+pub mod esquema;
 pub mod estado;
 pub mod interfaz;
 pub mod sistema;
@@ -1,6 +1,6 @@
 use std::{error::Error, process::exit};
 
-use cameras::{estado::Estado, interfaz::interfaz, sistema::Sistema};
+use cameras::{esquema::esquema, estado::Estado, interfaz::interfaz, sistema::Sistema};
 
 use lib::configuracion::Configuracion;
 
@@ -15,7 +15,7 @@ fn intentar_iniciar_sistema() -> Result<(), Box<dyn Error>> {
     let estado: Estado = Estado::new();
     let (enviar_respuesta, recibir_comandos) = interfaz();
 
-    let configuracion: Configuracion = Configuracion::desde_argv()?;
+    let configuracion: Configuracion = esquema().desde_argv_o_terminar();
     let mut sistema: Sistema =
         Sistema::new(estado, configuracion, enviar_respuesta, recibir_comandos);
 
@@ -1,7 +1,7 @@
 use std::{
     borrow::Cow,
     fs,
-    io::{self, Error, ErrorKind},
+    io::{self, Error},
     path::Path,
     sync::mpsc::{Receiver, Sender},
     thread,
@@ -18,7 +18,10 @@ use lib::{
         serializar_vec, Serializable,
     },
 };
-use messaging_client::cliente::{suscripcion::Suscripcion, Cliente};
+use messaging_client::{
+    cliente::{opciones::OpcionesConexion, suscripcion::Suscripcion, Cliente},
+    servicio::{error::ErrorServicio, peticion::Peticion, Servicio},
+};
 
 use crate::{
     estado::Estado,
@@ -75,7 +78,16 @@ impl Sistema {
             cliente.suscribirse("incidentes.*.creado", None)?;
         let sub_incidentes_finalizados: Suscripcion =
             cliente.suscribirse("incidentes.*.finalizado", None)?;
-        let sub_comandos_remotos: Suscripcion = cliente.suscribirse("comandos.camaras", None)?;
+
+        // Los comandos remotos se atienden como un servicio, así quien envía el
+        // comando con un reply_to recibe el resultado
+        let mut servicio_comandos: Servicio = Servicio::new(
+            &mut cliente,
+            "camaras",
+            env!("CARGO_PKG_VERSION"),
+            "Comandos remotos del sistema central de cámaras",
+        )?;
+        servicio_comandos.agregar_endpoint(&mut cliente, "comandos", "comandos.camaras", None)?;
 
         let sub_incidentes: Suscripcion = cliente.suscribirse("incidentes", None)?;
 
@@ -86,7 +98,7 @@ impl Sistema {
                 &cliente,
                 &sub_nuevos_incidentes,
                 &sub_incidentes_finalizados,
-                &sub_comandos_remotos,
+                &mut servicio_comandos,
                 &sub_incidentes,
             )?;
         }
@@ -94,22 +106,13 @@ impl Sistema {
 
     /// Conectar el cliente
     fn conectar(&self) -> io::Result<Cliente> {
-        let direccion: String = self
-            .configuracion
-            .obtener::<String>("direccion")
-            .unwrap_or("127.0.0.1".to_string());
+        let opciones: OpcionesConexion = OpcionesConexion::desde_configuracion(&self.configuracion);
+        println!(
+            "Conectando al servidor de NATS en {}",
+            opciones.servidores.join(", ")
+        );
 
-        let puerto: u16 = self.configuracion.obtener::<u16>("puerto").unwrap_or(4222);
-        println!("Conectando al servidor de NATS en {}:{}", direccion, puerto);
-
-        let user: Option<String> = self.configuracion.obtener::<String>("user");
-        let pass: Option<String> = self.configuracion.obtener::<String>("pass");
-
-        if user.is_some() || pass.is_some() {
-            Cliente::conectar_con_user_y_pass(&format!("{}:{}", direccion, puerto), user, pass)
-        } else {
-            Cliente::conectar(&format!("{}:{}", direccion, puerto))
-        }
+        Cliente::conectar_con_opciones(opciones)
     }
 
     /// Publica al servidor el estado de todas las camaras en el topico "camaras". Toma un
@@ -166,7 +169,7 @@ impl Sistema {
         cliente: &Cliente,
         sub_nuevos_incidentes: &Suscripcion,
         sub_incidentes_finalizados: &Suscripcion,
-        sub_comandos: &Suscripcion,
+        servicio_comandos: &mut Servicio,
         sub_incidentes: &Suscripcion,
     ) -> io::Result<()> {
         self.leer_incidentes(
@@ -176,7 +179,7 @@ impl Sistema {
             sub_incidentes,
         )?;
         self.leer_comandos(cliente)?;
-        self.leer_comandos_remotos(cliente, sub_comandos)?;
+        self.leer_comandos_remotos(cliente, servicio_comandos)?;
 
         thread::sleep(Duration::from_millis(5));
 
@@ -238,14 +241,16 @@ impl Sistema {
     /// Lee comandos desde la interfaz y los procesa
     fn leer_comandos(&mut self, cliente: &Cliente) -> io::Result<()> {
         while let Ok(comando) = self.recibir_comandos.try_recv() {
-            self.matchear_comandos(cliente, comando)?;
+            let respuesta: Respuesta = self.matchear_comandos(cliente, comando)?;
+            self.responder(respuesta)?;
         }
 
         Ok(())
     }
 
-    fn matchear_comandos(&mut self, cliente: &Cliente, comando: Comando) -> io::Result<()> {
-        match comando {
+    /// Ejecuta un comando y devuelve la respuesta, sin enviarla a ningún lado
+    fn matchear_comandos(&mut self, cliente: &Cliente, comando: Comando) -> io::Result<Respuesta> {
+        let respuesta: Respuesta = match comando {
             Comando::Conectar(id, latitud, longitud, rango) => {
                 self.comando_conectar_camara(cliente, id, latitud, longitud, rango)?
             }
@@ -254,18 +259,22 @@ impl Sistema {
                 self.comando_conectar_camara(cliente, id, latitud, longitud, rango)?
             }
             Comando::Desconectar(id) => self.comando_desconectar_camara(cliente, id)?,
-            Comando::ListarCamaras => self.comando_listar_camaras()?,
+            Comando::ListarCamaras => self.comando_listar_camaras(),
             Comando::ModificarRango(id, rango) => {
                 self.comando_modificar_rango(cliente, id, rango)?
             }
             Comando::ModificarUbicacion(id, latitud, longitud) => {
                 self.comando_modificar_ubicacion(cliente, id, latitud, longitud)?
             }
-            Comando::Camara(id) => self.comando_mostrar_camara(id)?,
-            Comando::Ayuda => self.comando_ayuda()?,
-            Comando::Actualizar => self.publicar_y_guardar_estado_general(cliente)?,
-        }
-        Ok(())
+            Comando::Camara(id) => self.comando_mostrar_camara(id),
+            Comando::Ayuda => Respuesta::Ayuda,
+            Comando::Actualizar => {
+                self.publicar_y_guardar_estado_general(cliente)?;
+                Respuesta::Ok
+            }
+        };
+
+        Ok(respuesta)
     }
 
     fn buscar_id_camara(&self) -> u64 {
@@ -278,22 +287,47 @@ impl Sistema {
         max_id + 1
     }
 
+    /// Atiende las peticiones del endpoint `comandos.camaras`. El resultado de cada
+    /// comando se devuelve por el reply_to de la petición (si lo tiene)
     fn leer_comandos_remotos(
         &mut self,
         cliente: &Cliente,
-        sub_comandos_remotos: &Suscripcion,
+        servicio_comandos: &mut Servicio,
     ) -> io::Result<()> {
-        while let Some(mensaje) = sub_comandos_remotos.intentar_leer()? {
-            let mensaje_texto: Cow<'_, str> = String::from_utf8_lossy(&mensaje.payload);
+        while let Some(peticion) = servicio_comandos.proxima_peticion(cliente)? {
+            let mensaje_texto: Cow<'_, str> = String::from_utf8_lossy(&peticion.payload);
 
-            if let Some(comando) = interpretar_comando(&mensaje_texto) {
-                self.matchear_comandos(cliente, comando)?;
-            }
+            let respuesta: Respuesta = match interpretar_comando(&mensaje_texto) {
+                Some(comando) => self.matchear_comandos(cliente, comando)?,
+                None => Respuesta::Error("Comando inválido".to_string()),
+            };
+
+            self.responder_remoto(cliente, servicio_comandos, &peticion, respuesta)?;
         }
 
         Ok(())
     }
 
+    fn responder_remoto(
+        &self,
+        cliente: &Cliente,
+        servicio_comandos: &mut Servicio,
+        peticion: &Peticion,
+        respuesta: Respuesta,
+    ) -> io::Result<()> {
+        match respuesta {
+            Respuesta::Error(error) => servicio_comandos.responder_error(
+                cliente,
+                peticion,
+                &ErrorServicio::new(400, &error),
+                b"",
+            ),
+            respuesta => {
+                servicio_comandos.responder(cliente, peticion, respuesta.como_string().as_bytes())
+            }
+        }
+    }
+
     fn comando_conectar_camara(
         &mut self,
         cliente: &Cliente,
@@ -301,36 +335,36 @@ impl Sistema {
         latitud: f64,
         longitud: f64,
         rango: f64,
-    ) -> io::Result<()> {
+    ) -> io::Result<Respuesta> {
         if self.estado.camara(id).is_some() {
-            return self.responder(Respuesta::Error(
+            return Ok(Respuesta::Error(
                 "Ya existe una cámara con ese ID".to_string(),
             ));
         }
         let camara: Camara = Camara::new(id, latitud, longitud, rango);
         self.estado.conectar_camara(camara);
         self.publicar_y_guardar_estado_general(cliente)?;
-        self.responder_ok()
+        Ok(Respuesta::Ok)
     }
 
-    fn comando_desconectar_camara(&mut self, cliente: &Cliente, id: u64) -> io::Result<()> {
+    fn comando_desconectar_camara(&mut self, cliente: &Cliente, id: u64) -> io::Result<Respuesta> {
         if self.estado.desconectar_camara(id).is_some() {
             self.publicar_y_guardar_estado_general(cliente)?;
-            self.responder_ok()
+            Ok(Respuesta::Ok)
         } else {
-            self.responder(Respuesta::Error(
+            Ok(Respuesta::Error(
                 "No existe una cámara con ese ID".to_string(),
             ))
         }
     }
 
-    fn comando_listar_camaras(&mut self) -> io::Result<()> {
+    fn comando_listar_camaras(&mut self) -> Respuesta {
         let camaras: Vec<Camara> = self.estado.camaras().into_iter().cloned().collect();
 
         if camaras.is_empty() {
-            self.responder(Respuesta::Error("No hay cámaras conectadas".to_string()))
+            Respuesta::Error("No hay cámaras conectadas".to_string())
         } else {
-            self.responder(Respuesta::Camaras(camaras))
+            Respuesta::Camaras(camaras)
         }
     }
 
@@ -339,16 +373,16 @@ impl Sistema {
         cliente: &Cliente,
         id: u64,
         rango: f64,
-    ) -> io::Result<()> {
+    ) -> io::Result<Respuesta> {
         if self.estado.camara(id).is_none() {
-            return self.responder(Respuesta::Error(
+            return Ok(Respuesta::Error(
                 "No existe una cámara con ese ID".to_string(),
             ));
         }
 
         self.estado.modificar_rango_camara(id, rango);
         self.publicar_y_guardar_estado_general(cliente)?;
-        self.responder_ok()
+        Ok(Respuesta::Ok)
     }
 
     fn comando_modificar_ubicacion(
@@ -357,9 +391,9 @@ impl Sistema {
         id: u64,
         latitud: f64,
         longitud: f64,
-    ) -> io::Result<()> {
+    ) -> io::Result<Respuesta> {
         if self.estado.camara(id).is_none() {
-            return self.responder(Respuesta::Error(
+            return Ok(Respuesta::Error(
                 "No existe una cámara con ese ID".to_string(),
             ));
         }
@@ -367,30 +401,19 @@ impl Sistema {
         self.estado
             .modificar_ubicacion_camara(id, latitud, longitud);
         self.publicar_y_guardar_estado_general(cliente)?;
-        self.responder_ok()
+        Ok(Respuesta::Ok)
     }
 
-    fn comando_mostrar_camara(&mut self, id: u64) -> io::Result<()> {
+    fn comando_mostrar_camara(&mut self, id: u64) -> Respuesta {
         if let Some(camara) = self.estado.camara(id) {
-            self.responder(Respuesta::Camara(camara.clone()))
+            Respuesta::Camara(camara.clone())
         } else {
-            self.responder(Respuesta::Error(
-                "No existe una cámara con ese ID".to_string(),
-            ))
+            Respuesta::Error("No existe una cámara con ese ID".to_string())
         }
     }
 
-    fn comando_ayuda(&mut self) -> io::Result<()> {
-        self.responder(Respuesta::Ayuda)
-    }
-
-    fn responder_ok(&self) -> io::Result<()> {
-        self.responder(Respuesta::Ok)
-    }
-
+    /// Envía la respuesta de un comando a la interfaz de la terminal
     fn responder(&self, respuesta: Respuesta) -> io::Result<()> {
-        self.enviar_respuesta
-            .send(respuesta)
-            .map_err(|e| Error::new(ErrorKind::Other, e))
+        self.enviar_respuesta.send(respuesta).map_err(Error::other)
     }
 }
@@ -0,0 +1,345 @@
+use std::{io, time::Duration};
+
+use crate::benchmark::OpcionesBenchmark;
+
+/// Tiempo que se espera la respuesta de una petición si no se indica `--timeout`
+pub const TIEMPO_LIMITE_POR_DEFECTO: Duration = Duration::from_secs(5);
+
+pub const AYUDA: &str = "Uso: cli [clave=valor ...] <comando> [argumentos]
+
+Comandos:
+  pub <topico> <datos> [--header Clave:Valor] [--count N]
+      Publica los datos en el tópico (N veces). {{contador}} se reemplaza
+      por el número de publicación
+  sub <topico> [--queue grupo]
+      Se suscribe al tópico y muestra los mensajes recibidos. Los payloads de
+      cámaras, incidentes y drones se muestran con formato
+  req <topico> <datos> [--header Clave:Valor] [--timeout 5s]
+      Envía una petición y muestra la respuesta
+  reply <topico> <plantilla> [--queue grupo]
+      Responde cada petición con la plantilla. {{peticion}}, {{topico}} y
+      {{contador}} se reemplazan por el payload, el tópico y el número de petición
+  bench <topico> [--pub 1] [--sub 1] [--msgs 100000] [--size 128] [--queue grupo] [--csv archivo]
+      Prueba de rendimiento: publica los mensajes repartidos entre los publicadores
+      y muestra mensajes/s, bytes/s y percentiles de latencia. Con --csv agrega
+      una fila con los resultados al archivo
+  ayuda
+      Muestra este mensaje
+
+Configuración (antes del comando, igual que el resto de los programas):
+  direccion=127.0.0.1 puerto=4222 servidores=nats://a:4222,nats://b:4222
+  user=usuario pass=contraseña config=archivo.txt";
+
+/// Comando a ejecutar, con sus argumentos ya parseados
+#[derive(Debug, PartialEq)]
+pub enum Comando {
+    Publicar {
+        topico: String,
+        datos: String,
+        headers: Vec<(String, String)>,
+        cantidad: usize,
+    },
+    Suscribir {
+        topico: String,
+        queue_group: Option<String>,
+    },
+    Peticion {
+        topico: String,
+        datos: String,
+        headers: Vec<(String, String)>,
+        tiempo_limite: Duration,
+    },
+    Responder {
+        topico: String,
+        plantilla: String,
+        queue_group: Option<String>,
+    },
+    Benchmark(OpcionesBenchmark),
+    Ayuda,
+}
+
+/// Argumentos de la linea de comandos. Los parámetros `clave=valor` que están
+/// antes del comando son la configuración (dirección, credenciales, etc.)
+#[derive(Debug, PartialEq)]
+pub struct Argumentos {
+    pub parametros_configuracion: Vec<String>,
+    pub comando: Comando,
+}
+
+impl Argumentos {
+    pub fn parsear(args: &[&str]) -> io::Result<Self> {
+        let mut parametros_configuracion: Vec<String> = Vec::new();
+        let mut restantes = args.iter();
+
+        let nombre_comando: &str = loop {
+            match restantes.next() {
+                Some(arg) if arg.contains('=') => parametros_configuracion.push(arg.to_string()),
+                Some(arg) => break arg,
+                None => {
+                    return Ok(Argumentos {
+                        parametros_configuracion,
+                        comando: Comando::Ayuda,
+                    })
+                }
+            }
+        };
+
+        let mut posicionales: Vec<String> = Vec::new();
+        let mut opciones: Vec<(String, String)> = Vec::new();
+
+        while let Some(arg) = restantes.next() {
+            if let Some(opcion) = arg.strip_prefix("--") {
+                let valor: &str = restantes
+                    .next()
+                    .ok_or_else(|| error(&format!("Falta el valor de --{}", opcion)))?;
+                opciones.push((opcion.to_string(), valor.to_string()));
+            } else {
+                posicionales.push(arg.to_string());
+            }
+        }
+
+        let comando: Comando = match nombre_comando {
+            "pub" => {
+                let [topico, datos] =
+                    posicionales_exactos::<2>(posicionales, "pub <topico> <datos>")?;
+                validar_opciones(&opciones, &["header", "count"])?;
+
+                Comando::Publicar {
+                    topico,
+                    datos,
+                    headers: headers(&opciones)?,
+                    cantidad: opcion_numerica(&opciones, "count")?.unwrap_or(1),
+                }
+            }
+            "sub" => {
+                let [topico] = posicionales_exactos::<1>(posicionales, "sub <topico>")?;
+                validar_opciones(&opciones, &["queue"])?;
+
+                Comando::Suscribir {
+                    topico,
+                    queue_group: opcion(&opciones, "queue").map(|s| s.to_string()),
+                }
+            }
+            "req" => {
+                let [topico, datos] =
+                    posicionales_exactos::<2>(posicionales, "req <topico> <datos>")?;
+                validar_opciones(&opciones, &["header", "timeout"])?;
+
+                Comando::Peticion {
+                    topico,
+                    datos,
+                    headers: headers(&opciones)?,
+                    tiempo_limite: match opcion(&opciones, "timeout") {
+                        Some(tiempo_limite) => parsear_duracion(tiempo_limite)?,
+                        None => TIEMPO_LIMITE_POR_DEFECTO,
+                    },
+                }
+            }
+            "reply" => {
+                let [topico, plantilla] =
+                    posicionales_exactos::<2>(posicionales, "reply <topico> <plantilla>")?;
+                validar_opciones(&opciones, &["queue"])?;
+
+                Comando::Responder {
+                    topico,
+                    plantilla,
+                    queue_group: opcion(&opciones, "queue").map(|s| s.to_string()),
+                }
+            }
+            "bench" => {
+                let [topico] = posicionales_exactos::<1>(posicionales, "bench <topico>")?;
+                validar_opciones(&opciones, &["pub", "sub", "msgs", "size", "queue", "csv"])?;
+
+                let mut benchmark: OpcionesBenchmark = OpcionesBenchmark::new(&topico);
+                if let Some(publicadores) = opcion_numerica(&opciones, "pub")? {
+                    benchmark.publicadores = publicadores;
+                }
+                if let Some(suscriptores) = opcion_numerica(&opciones, "sub")? {
+                    benchmark.suscriptores = suscriptores;
+                }
+                if let Some(mensajes) = opcion_numerica(&opciones, "msgs")? {
+                    benchmark.mensajes = mensajes;
+                }
+                if let Some(tamanio) = opcion_numerica(&opciones, "size")? {
+                    benchmark.tamanio = tamanio;
+                }
+                benchmark.queue_group = opcion(&opciones, "queue").map(|s| s.to_string());
+                benchmark.csv = opcion(&opciones, "csv").map(|s| s.to_string());
+
+                Comando::Benchmark(benchmark)
+            }
+            "ayuda" | "help" => Comando::Ayuda,
+            otro => return Err(error(&format!("Comando desconocido: {}", otro))),
+        };
+
+        Ok(Argumentos {
+            parametros_configuracion,
+            comando,
+        })
+    }
+}
+
+/// Parsea una duración: `500ms`, `2s` o un número de segundos
+pub fn parsear_duracion(texto: &str) -> io::Result<Duration> {
+    let invalida = || error(&format!("Duración inválida: {}", texto));
+
+    if let Some(milisegundos) = texto.strip_suffix("ms") {
+        return Ok(Duration::from_millis(
+            milisegundos.parse().map_err(|_| invalida())?,
+        ));
+    }
+
+    let segundos: f64 = texto
+        .strip_suffix('s')
+        .unwrap_or(texto)
+        .parse()
+        .map_err(|_| invalida())?;
+
+    // También rechaza los negativos, los infinitos y los que no entran en un `Duration`
+    Duration::try_from_secs_f64(segundos).map_err(|_| invalida())
+}
+
+fn posicionales_exactos<const N: usize>(
+    posicionales: Vec<String>,
+    uso: &str,
+) -> io::Result<[String; N]> {
+    posicionales
+        .try_into()
+        .map_err(|_| error(&format!("Uso: {}", uso)))
+}
+
+fn validar_opciones(opciones: &[(String, String)], validas: &[&str]) -> io::Result<()> {
+    for (opcion, _) in opciones {
+        if !validas.contains(&opcion.as_str()) {
+            return Err(error(&format!("Opción desconocida: --{}", opcion)));
+        }
+    }
+
+    Ok(())
+}
+
+fn opcion<'a>(opciones: &'a [(String, String)], nombre: &str) -> Option<&'a str> {
+    opciones
+        .iter()
+        .rev()
+        .find(|(opcion, _)| opcion.eq(nombre))
+        .map(|(_, valor)| valor.as_str())
+}
+
+fn opcion_numerica(opciones: &[(String, String)], nombre: &str) -> io::Result<Option<usize>> {
+    match opcion(opciones, nombre) {
+        Some(valor) => valor
+            .parse()
+            .map(Some)
+            .map_err(|_| error(&format!("Valor inválido para --{}: {}", nombre, valor))),
+        None => Ok(None),
+    }
+}
+
+/// Todas las opciones `--header Clave:Valor`
+fn headers(opciones: &[(String, String)]) -> io::Result<Vec<(String, String)>> {
+    opciones
+        .iter()
+        .filter(|(opcion, _)| opcion.eq("header"))
+        .map(|(_, header)| match header.split_once(':') {
+            Some((clave, valor)) if !clave.trim().is_empty() => {
+                Ok((clave.trim().to_string(), valor.trim().to_string()))
+            }
+            _ => Err(error(&format!("Header inválido (Clave:Valor): {}", header))),
+        })
+        .collect()
+}
+
+fn error(mensaje: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, mensaje)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::benchmark::OpcionesBenchmark;
+
+    use super::{parsear_duracion, Argumentos, Comando, TIEMPO_LIMITE_POR_DEFECTO};
+
+    #[test]
+    fn configuracion_antes_del_comando() {
+        let argumentos =
+            Argumentos::parsear(&["puerto=4223", "pub", "camaras", "a=b", "--count", "3"]).unwrap();
+
+        assert_eq!(argumentos.parametros_configuracion, vec!["puerto=4223"]);
+        assert_eq!(
+            argumentos.comando,
+            Comando::Publicar {
+                topico: "camaras".to_string(),
+                datos: "a=b".to_string(),
+                headers: vec![],
+                cantidad: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn peticion_con_headers() {
+        let argumentos = Argumentos::parsear(&[
+            "req",
+            "comandos.camaras",
+            "actualizar",
+            "--header",
+            "Origen: cli",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            argumentos.comando,
+            Comando::Peticion {
+                topico: "comandos.camaras".to_string(),
+                datos: "actualizar".to_string(),
+                headers: vec![("Origen".to_string(), "cli".to_string())],
+                tiempo_limite: TIEMPO_LIMITE_POR_DEFECTO,
+            }
+        );
+    }
+
+    #[test]
+    fn errores() {
+        assert!(Argumentos::parsear(&["pub", "camaras"]).is_err());
+        assert!(Argumentos::parsear(&["sub", "camaras", "--count", "1"]).is_err());
+        assert!(Argumentos::parsear(&["sub", "camaras", "--queue"]).is_err());
+        assert!(Argumentos::parsear(&["borrar"]).is_err());
+        assert_eq!(Argumentos::parsear(&[]).unwrap().comando, Comando::Ayuda);
+    }
+
+    #[test]
+    fn benchmark() {
+        let argumentos = Argumentos::parsear(&[
+            "bench", "prueba", "--pub", "2", "--sub", "3", "--size", "1024", "--queue", "q",
+        ])
+        .unwrap();
+
+        let mut esperado = OpcionesBenchmark::new("prueba");
+        esperado.publicadores = 2;
+        esperado.suscriptores = 3;
+        esperado.tamanio = 1024;
+        esperado.queue_group = Some("q".to_string());
+
+        assert_eq!(argumentos.comando, Comando::Benchmark(esperado));
+        assert!(Argumentos::parsear(&["bench", "prueba", "--msgs", "muchos"]).is_err());
+    }
+
+    #[test]
+    fn duraciones() {
+        assert_eq!(
+            parsear_duracion("500ms").unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(parsear_duracion("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(
+            parsear_duracion("1.5").unwrap(),
+            Duration::from_millis(1500)
+        );
+        assert!(parsear_duracion("-1").is_err());
+        assert!(parsear_duracion("1e30").is_err());
+        assert!(parsear_duracion("rapido").is_err());
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lib::csv::csv_encodear_linea;
+use messaging_client::cliente::{
+    opciones::OpcionesConexion, publicacion::Publicacion, suscripcion::Suscripcion, Cliente,
+};
+
+/// Cantidad de dígitos de la marca de tiempo (nanosegundos desde epoch) que
+/// va al principio de cada payload. Es el tamaño mínimo de un mensaje
+pub const TAMANIO_MARCA_DE_TIEMPO: usize = 20;
+
+/// Si los suscriptores pasan este tiempo sin recibir mensajes, se da por
+/// terminada la prueba (los mensajes que falten se cuentan como perdidos)
+const TIEMPO_LIMITE_SIN_MENSAJES: Duration = Duration::from_secs(5);
+
+/// Espera después de suscribirse para que la suscripción llegue a todos los hilos del servidor
+const ESPERA_PROPAGACION_SUSCRIPCIONES: Duration = Duration::from_millis(500);
+
+const COLUMNAS_CSV: [&str; 15] = [
+    "publicadores",
+    "suscriptores",
+    "queue_group",
+    "mensajes",
+    "tamanio",
+    "mensajes_por_segundo_publicados",
+    "bytes_por_segundo_publicados",
+    "mensajes_por_segundo_recibidos",
+    "bytes_por_segundo_recibidos",
+    "recibidos",
+    "perdidos",
+    "latencia_p50_us",
+    "latencia_p90_us",
+    "latencia_p99_us",
+    "latencia_maxima_us",
+];
+
+/// Parámetros de una prueba de rendimiento
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpcionesBenchmark {
+    pub topico: String,
+    pub publicadores: usize,
+    pub suscriptores: usize,
+    /// Mensajes en total, repartidos entre los publicadores
+    pub mensajes: usize,
+    pub tamanio: usize,
+    pub queue_group: Option<String>,
+    /// Archivo al que se agrega una fila con los resultados
+    pub csv: Option<String>,
+}
+
+impl OpcionesBenchmark {
+    pub fn new(topico: &str) -> Self {
+        Self {
+            topico: topico.to_string(),
+            publicadores: 1,
+            suscriptores: 1,
+            mensajes: 100_000,
+            tamanio: 128,
+            queue_group: None,
+            csv: None,
+        }
+    }
+
+    /// Mensajes que se espera recibir entre todos los suscriptores. Con queue
+    /// group cada mensaje le llega a uno solo
+    fn mensajes_esperados(&self) -> usize {
+        if self.queue_group.is_some() {
+            self.mensajes
+        } else {
+            self.mensajes * self.suscriptores
+        }
+    }
+}
+
+/// Lo que midió un publicador o un suscriptor
+struct Medicion {
+    mensajes: usize,
+    bytes: usize,
+    inicio: Option<Instant>,
+    fin: Option<Instant>,
+    latencias: Vec<Duration>,
+}
+
+impl Medicion {
+    fn new() -> Self {
+        Self {
+            mensajes: 0,
+            bytes: 0,
+            inicio: None,
+            fin: None,
+            latencias: Vec::new(),
+        }
+    }
+}
+
+/// Resultados agregados de una prueba
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultadoBenchmark {
+    pub opciones: OpcionesBenchmark,
+    pub publicados: usize,
+    pub bytes_publicados: usize,
+    pub duracion_publicacion: Duration,
+    pub recibidos: usize,
+    pub bytes_recibidos: usize,
+    pub duracion_recepcion: Duration,
+    /// Latencias ordenadas de menor a mayor
+    pub latencias: Vec<Duration>,
+}
+
+/// Corre la prueba: conecta los suscriptores, espera a que estén listos,
+/// publica los mensajes desde todos los publicadores y espera que lleguen
+pub fn ejecutar(
+    opciones_conexion: &OpcionesConexion,
+    opciones: &OpcionesBenchmark,
+) -> io::Result<ResultadoBenchmark> {
+    if opciones.publicadores == 0 || opciones.mensajes == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Tiene que haber al menos un publicador y un mensaje",
+        ));
+    }
+
+    let recibidos_totales: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let (tx_listo, rx_listo) = channel::<io::Result<()>>();
+
+    let mut hilos_suscriptores: Vec<JoinHandle<io::Result<Medicion>>> = Vec::new();
+    for _ in 0..opciones.suscriptores {
+        let opciones_conexion: OpcionesConexion = opciones_conexion.clone();
+        let opciones: OpcionesBenchmark = opciones.clone();
+        let recibidos_totales: Arc<AtomicUsize> = recibidos_totales.clone();
+        let tx_listo = tx_listo.clone();
+
+        hilos_suscriptores.push(thread::spawn(move || {
+            suscriptor(&opciones_conexion, &opciones, &recibidos_totales, || {
+                let _ = tx_listo.send(Ok(()));
+            })
+            .inspect_err(|e| {
+                let _ = tx_listo.send(Err(io::Error::new(e.kind(), e.to_string())));
+            })
+        }));
+    }
+    drop(tx_listo);
+
+    for _ in 0..opciones.suscriptores {
+        match rx_listo.recv() {
+            Ok(resultado) => resultado?,
+            Err(_) => {
+                return Err(io::Error::other(
+                    "Un suscriptor terminó antes de estar listo",
+                ))
+            }
+        }
+    }
+    thread::sleep(ESPERA_PROPAGACION_SUSCRIPCIONES);
+
+    let mut hilos_publicadores: Vec<JoinHandle<io::Result<Medicion>>> = Vec::new();
+    for i in 0..opciones.publicadores {
+        let opciones_conexion: OpcionesConexion = opciones_conexion.clone();
+        let opciones: OpcionesBenchmark = opciones.clone();
+
+        // Se reparten los mensajes: los primeros publicadores mandan uno más si no es exacto
+        let cantidad: usize = opciones.mensajes / opciones.publicadores
+            + usize::from(i < opciones.mensajes % opciones.publicadores);
+
+        hilos_publicadores.push(thread::spawn(move || {
+            publicador(&opciones_conexion, &opciones, cantidad)
+        }));
+    }
+
+    let publicaciones: Vec<Medicion> = esperar(hilos_publicadores)?;
+    let recepciones: Vec<Medicion> = esperar(hilos_suscriptores)?;
+
+    Ok(ResultadoBenchmark::new(
+        opciones.clone(),
+        &publicaciones,
+        recepciones,
+    ))
+}
+
+fn esperar(hilos: Vec<JoinHandle<io::Result<Medicion>>>) -> io::Result<Vec<Medicion>> {
+    let mut mediciones: Vec<Medicion> = Vec::new();
+
+    for hilo in hilos {
+        match hilo.join() {
+            Ok(medicion) => mediciones.push(medicion?),
+            Err(_) => return Err(io::Error::other("Error en un hilo de la prueba")),
+        }
+    }
+
+    Ok(mediciones)
+}
+
+fn publicador(
+    opciones_conexion: &OpcionesConexion,
+    opciones: &OpcionesBenchmark,
+    cantidad: usize,
+) -> io::Result<Medicion> {
+    let mut cliente: Cliente = Cliente::conectar_con_opciones(opciones_conexion.clone())?;
+    let mut medicion: Medicion = Medicion::new();
+    let mut payload: Vec<u8> = vec![b'x'; opciones.tamanio.max(TAMANIO_MARCA_DE_TIEMPO)];
+
+    medicion.inicio = Some(Instant::now());
+
+    for _ in 0..cantidad {
+        escribir_marca_de_tiempo(&mut payload, SystemTime::now());
+        cliente.publicar(&opciones.topico, &payload, None)?;
+
+        medicion.mensajes += 1;
+        medicion.bytes += payload.len();
+    }
+
+    // El tiempo de publicación incluye hasta que se terminó de escribir todo al servidor
+    cliente.desconectar()?;
+    medicion.fin = Some(Instant::now());
+
+    Ok(medicion)
+}
+
+fn suscriptor(
+    opciones_conexion: &OpcionesConexion,
+    opciones: &OpcionesBenchmark,
+    recibidos_totales: &AtomicUsize,
+    listo: impl FnOnce(),
+) -> io::Result<Medicion> {
+    let mut cliente: Cliente = Cliente::conectar_con_opciones(opciones_conexion.clone())?;
+    let suscripcion: Suscripcion =
+        cliente.suscribirse(&opciones.topico, opciones.queue_group.as_deref())?;
+
+    // Ida y vuelta por un inbox propio: cuando vuelve, el servidor ya procesó el SUB
+    let inbox: String = cliente.nuevo_inbox();
+    let suscripcion_inbox: Suscripcion = cliente.suscribirse(&inbox, None)?;
+    cliente.publicar(&inbox, b"", None)?;
+    if suscripcion_inbox
+        .leer_con_limite_de_tiempo(TIEMPO_LIMITE_SIN_MENSAJES)?
+        .is_none()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "El servidor no confirmó la suscripción",
+        ));
+    }
+    listo();
+
+    let mut medicion: Medicion = Medicion::new();
+    let esperados: usize = opciones.mensajes_esperados();
+
+    while recibidos_totales.load(Ordering::Relaxed) < esperados {
+        let publicacion: Publicacion =
+            match suscripcion.leer_con_limite_de_tiempo(TIEMPO_LIMITE_SIN_MENSAJES)? {
+                Some(publicacion) => publicacion,
+                None => break,
+            };
+
+        let ahora: Instant = Instant::now();
+        if medicion.inicio.is_none() {
+            medicion.inicio = Some(ahora);
+        }
+        medicion.fin = Some(ahora);
+        medicion.mensajes += 1;
+        medicion.bytes += publicacion.payload.len();
+
+        if let Some(enviado) = leer_marca_de_tiempo(&publicacion.payload) {
+            if let Ok(latencia) = SystemTime::now().duration_since(enviado) {
+                medicion.latencias.push(latencia);
+            }
+        }
+
+        recibidos_totales.fetch_add(1, Ordering::Relaxed);
+    }
+
+    Ok(medicion)
+}
+
+/// Escribe en los primeros bytes del payload los nanosegundos desde epoch
+fn escribir_marca_de_tiempo(payload: &mut [u8], momento: SystemTime) {
+    let nanos: u128 = momento
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let marca: String = format!("{:0ancho$}", nanos, ancho = TAMANIO_MARCA_DE_TIEMPO);
+
+    payload[..TAMANIO_MARCA_DE_TIEMPO]
+        .copy_from_slice(&marca.as_bytes()[..TAMANIO_MARCA_DE_TIEMPO]);
+}
+
+fn leer_marca_de_tiempo(payload: &[u8]) -> Option<SystemTime> {
+    let marca: &str = std::str::from_utf8(payload.get(..TAMANIO_MARCA_DE_TIEMPO)?).ok()?;
+    let nanos: u64 = marca.parse().ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+/// Desde el primer inicio hasta el último fin de todas las mediciones
+fn duracion_total(mediciones: &[Medicion]) -> Duration {
+    let inicio: Option<Instant> = mediciones.iter().filter_map(|m| m.inicio).min();
+    let fin: Option<Instant> = mediciones.iter().filter_map(|m| m.fin).max();
+
+    match (inicio, fin) {
+        (Some(inicio), Some(fin)) => fin.duration_since(inicio),
+        _ => Duration::ZERO,
+    }
+}
+
+/// Percentil (0 a 100) de una lista ordenada, por el método del rango más cercano
+pub fn percentil(ordenadas: &[Duration], p: f64) -> Duration {
+    if ordenadas.is_empty() {
+        return Duration::ZERO;
+    }
+
+    let rango: usize = ((p / 100.0) * ordenadas.len() as f64).ceil() as usize;
+    ordenadas[rango.clamp(1, ordenadas.len()) - 1]
+}
+
+fn por_segundo(cantidad: usize, duracion: Duration) -> f64 {
+    if duracion.is_zero() {
+        return 0.0;
+    }
+
+    cantidad as f64 / duracion.as_secs_f64()
+}
+
+impl ResultadoBenchmark {
+    fn new(
+        opciones: OpcionesBenchmark,
+        publicaciones: &[Medicion],
+        recepciones: Vec<Medicion>,
+    ) -> Self {
+        let duracion_recepcion: Duration = duracion_total(&recepciones);
+        let recibidos: usize = recepciones.iter().map(|m| m.mensajes).sum();
+        let bytes_recibidos: usize = recepciones.iter().map(|m| m.bytes).sum();
+        let mut latencias: Vec<Duration> =
+            recepciones.into_iter().flat_map(|m| m.latencias).collect();
+        latencias.sort();
+
+        Self {
+            publicados: publicaciones.iter().map(|m| m.mensajes).sum(),
+            bytes_publicados: publicaciones.iter().map(|m| m.bytes).sum(),
+            duracion_publicacion: duracion_total(publicaciones),
+            recibidos,
+            bytes_recibidos,
+            duracion_recepcion,
+            latencias,
+            opciones,
+        }
+    }
+
+    fn tamanio_mensaje(&self) -> usize {
+        self.opciones.tamanio.max(TAMANIO_MARCA_DE_TIEMPO)
+    }
+
+    pub fn mensajes_por_segundo_publicados(&self) -> f64 {
+        por_segundo(self.publicados, self.duracion_publicacion)
+    }
+
+    pub fn mensajes_por_segundo_recibidos(&self) -> f64 {
+        por_segundo(self.recibidos, self.duracion_recepcion)
+    }
+
+    pub fn bytes_por_segundo_publicados(&self) -> f64 {
+        por_segundo(self.bytes_publicados, self.duracion_publicacion)
+    }
+
+    pub fn bytes_por_segundo_recibidos(&self) -> f64 {
+        por_segundo(self.bytes_recibidos, self.duracion_recepcion)
+    }
+
+    pub fn perdidos(&self) -> usize {
+        self.opciones
+            .mensajes_esperados()
+            .saturating_sub(self.recibidos)
+    }
+
+    /// Texto para mostrar por consola
+    pub fn reporte(&self) -> String {
+        let mut texto: String = format!(
+            "Tópico \"{}\": {} publicadores, {} suscriptores{}, {} mensajes de {} bytes\n",
+            self.opciones.topico,
+            self.opciones.publicadores,
+            self.opciones.suscriptores,
+            match &self.opciones.queue_group {
+                Some(queue_group) => format!(" (queue group \"{}\")", queue_group),
+                None => "".to_string(),
+            },
+            self.opciones.mensajes,
+            self.tamanio_mensaje()
+        );
+
+        texto.push_str(&format!(
+            "Publicación: {} mensajes en {:.3}s - {:.0} msgs/s, {:.0} bytes/s\n",
+            self.publicados,
+            self.duracion_publicacion.as_secs_f64(),
+            self.mensajes_por_segundo_publicados(),
+            self.bytes_por_segundo_publicados()
+        ));
+
+        if self.opciones.suscriptores > 0 {
+            texto.push_str(&format!(
+                "Recepción: {} mensajes en {:.3}s - {:.0} msgs/s, {:.0} bytes/s ({} perdidos)\n",
+                self.recibidos,
+                self.duracion_recepcion.as_secs_f64(),
+                self.mensajes_por_segundo_recibidos(),
+                self.bytes_por_segundo_recibidos(),
+                self.perdidos()
+            ));
+            texto.push_str(&format!(
+                "Latencia: p50 {:?}, p90 {:?}, p99 {:?}, máxima {:?}",
+                percentil(&self.latencias, 50.0),
+                percentil(&self.latencias, 90.0),
+                percentil(&self.latencias, 99.0),
+                percentil(&self.latencias, 100.0)
+            ));
+        }
+
+        texto
+    }
+
+    /// Fila del reporte CSV, en el orden de `COLUMNAS_CSV`
+    pub fn fila_csv(&self) -> String {
+        let microsegundos = |p: f64| percentil(&self.latencias, p).as_micros().to_string();
+
+        csv_encodear_linea(&[
+            self.opciones.publicadores.to_string(),
+            self.opciones.suscriptores.to_string(),
+            self.opciones.queue_group.clone().unwrap_or_default(),
+            self.opciones.mensajes.to_string(),
+            self.tamanio_mensaje().to_string(),
+            format!("{:.0}", self.mensajes_por_segundo_publicados()),
+            format!("{:.0}", self.bytes_por_segundo_publicados()),
+            format!("{:.0}", self.mensajes_por_segundo_recibidos()),
+            format!("{:.0}", self.bytes_por_segundo_recibidos()),
+            self.recibidos.to_string(),
+            self.perdidos().to_string(),
+            microsegundos(50.0),
+            microsegundos(90.0),
+            microsegundos(99.0),
+            microsegundos(100.0),
+        ])
+    }
+
+    /// Agrega los resultados al final del archivo CSV. Si el archivo no existe,
+    /// se crea con la fila de encabezados
+    pub fn guardar_csv(&self, ruta: &str) -> io::Result<()> {
+        let nuevo: bool = !Path::new(ruta).exists();
+        let mut archivo = OpenOptions::new().create(true).append(true).open(ruta)?;
+
+        if nuevo {
+            writeln!(archivo, "{}", COLUMNAS_CSV.join(","))?;
+        }
+
+        writeln!(archivo, "{}", self.fila_csv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{
+        escribir_marca_de_tiempo, leer_marca_de_tiempo, percentil, TAMANIO_MARCA_DE_TIEMPO,
+    };
+
+    #[test]
+    fn marca_de_tiempo_en_payload() {
+        let momento = UNIX_EPOCH + Duration::from_nanos(1_718_000_000_123_456_789);
+        let mut payload = vec![b'x'; TAMANIO_MARCA_DE_TIEMPO + 4];
+
+        escribir_marca_de_tiempo(&mut payload, momento);
+
+        assert_eq!(&payload[TAMANIO_MARCA_DE_TIEMPO..], b"xxxx");
+        assert_eq!(leer_marca_de_tiempo(&payload), Some(momento));
+        assert_eq!(leer_marca_de_tiempo(b"hola"), None);
+        assert!(leer_marca_de_tiempo(&payload).unwrap() < SystemTime::now());
+    }
+
+    #[test]
+    fn percentiles() {
+        let latencias: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+        assert_eq!(percentil(&latencias, 50.0), Duration::from_millis(50));
+        assert_eq!(percentil(&latencias, 99.0), Duration::from_millis(99));
+        assert_eq!(percentil(&latencias, 100.0), Duration::from_millis(100));
+        assert_eq!(percentil(&latencias, 0.0), Duration::from_millis(1));
+        assert_eq!(percentil(&[], 50.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{io, time::Duration};
+
+use lib::{headers::Headers, serializables::Serializable};
+use messaging_client::{
+    cliente::{
+        opciones::OpcionesConexion, publicacion::Publicacion, suscripcion::Suscripcion, Cliente,
+    },
+    servicio::error::ErrorServicio,
+};
+
+use crate::{
+    argumentos::Comando,
+    benchmark::{self, OpcionesBenchmark, ResultadoBenchmark},
+    formato::{completar_plantilla, formatear_payload, formatear_publicacion},
+};
+
+/// Ejecuta el comando. Salvo el benchmark (que abre sus propias conexiones)
+/// todos usan un único cliente
+pub fn ejecutar(opciones_conexion: OpcionesConexion, comando: Comando) -> io::Result<()> {
+    match comando {
+        Comando::Benchmark(opciones) => return benchmark(&opciones_conexion, &opciones),
+        Comando::Ayuda => return Ok(()),
+        _ => {}
+    }
+
+    let mut cliente: Cliente = Cliente::conectar_con_opciones(opciones_conexion)?;
+
+    match comando {
+        Comando::Publicar {
+            topico,
+            datos,
+            headers,
+            cantidad,
+        } => publicar(&mut cliente, &topico, &datos, &headers, cantidad),
+        Comando::Suscribir {
+            topico,
+            queue_group,
+        } => suscribir(&mut cliente, &topico, queue_group.as_deref()),
+        Comando::Peticion {
+            topico,
+            datos,
+            headers,
+            tiempo_limite,
+        } => peticion(&mut cliente, &topico, &datos, &headers, tiempo_limite),
+        Comando::Responder {
+            topico,
+            plantilla,
+            queue_group,
+        } => responder(&mut cliente, &topico, &plantilla, queue_group.as_deref()),
+        Comando::Benchmark(_) | Comando::Ayuda => Ok(()),
+    }
+}
+
+fn benchmark(opciones_conexion: &OpcionesConexion, opciones: &OpcionesBenchmark) -> io::Result<()> {
+    println!(
+        "Iniciando prueba de rendimiento en \"{}\"...",
+        opciones.topico
+    );
+
+    let resultado: ResultadoBenchmark = benchmark::ejecutar(opciones_conexion, opciones)?;
+    println!("{}", resultado.reporte());
+
+    if let Some(ruta) = &opciones.csv {
+        resultado.guardar_csv(ruta)?;
+        println!("Resultados agregados a {}", ruta);
+    }
+
+    Ok(())
+}
+
+fn publicar(
+    cliente: &mut Cliente,
+    topico: &str,
+    datos: &str,
+    headers: &[(String, String)],
+    cantidad: usize,
+) -> io::Result<()> {
+    let headers: Option<Vec<u8>> = serializar_headers(headers);
+
+    for contador in 1..=cantidad {
+        let payload: String = datos.replace("{{contador}}", &contador.to_string());
+
+        match &headers {
+            Some(headers) => {
+                cliente.publicar_con_header(topico, payload.as_bytes(), headers, None)?
+            }
+            None => cliente.publicar(topico, payload.as_bytes(), None)?,
+        }
+    }
+
+    // Esperar a que se envíen todas las publicaciones antes de terminar
+    cliente.desconectar()?;
+    println!("Publicados {} mensajes en \"{}\"", cantidad, topico);
+
+    Ok(())
+}
+
+fn suscribir(cliente: &mut Cliente, topico: &str, queue_group: Option<&str>) -> io::Result<()> {
+    let suscripcion: Suscripcion = cliente.suscribirse(topico, queue_group)?;
+    println!("Escuchando en \"{}\"", topico);
+
+    let mut contador: usize = 0;
+
+    loop {
+        let publicacion: Publicacion = suscripcion.leer()?;
+        contador += 1;
+
+        println!("{}\n", formatear_publicacion(contador, &publicacion));
+    }
+}
+
+fn peticion(
+    cliente: &mut Cliente,
+    topico: &str,
+    datos: &str,
+    headers: &[(String, String)],
+    tiempo_limite: Duration,
+) -> io::Result<()> {
+    let respuesta: Option<Publicacion> = match serializar_headers(headers) {
+        Some(headers) => cliente.peticion_con_tiempo_limite_con_header(
+            topico,
+            datos.as_bytes(),
+            &headers,
+            tiempo_limite,
+        )?,
+        None => cliente.peticion_con_tiempo_limite(topico, datos.as_bytes(), tiempo_limite)?,
+    };
+
+    let respuesta: Publicacion = match respuesta {
+        Some(respuesta) => respuesta,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("No se recibió respuesta en {:?}", tiempo_limite),
+            ))
+        }
+    };
+
+    if let Some(error) = ErrorServicio::desde_publicacion(&respuesta) {
+        println!("Error {}: {}", error.codigo, error.descripcion);
+    }
+
+    println!(
+        "{}",
+        formatear_payload(&respuesta.topico, &respuesta.payload)
+    );
+
+    Ok(())
+}
+
+fn responder(
+    cliente: &mut Cliente,
+    topico: &str,
+    plantilla: &str,
+    queue_group: Option<&str>,
+) -> io::Result<()> {
+    let suscripcion: Suscripcion = cliente.suscribirse(topico, queue_group)?;
+    println!("Respondiendo peticiones en \"{}\"", topico);
+
+    let mut contador: usize = 0;
+
+    loop {
+        let peticion: Publicacion = suscripcion.leer()?;
+        contador += 1;
+
+        println!("{}\n", formatear_publicacion(contador, &peticion));
+
+        match &peticion.replay_to {
+            Some(replay_to) => {
+                let respuesta: String = completar_plantilla(plantilla, &peticion, contador);
+                cliente.publicar(replay_to, respuesta.as_bytes(), None)?;
+            }
+            None => eprintln!("La petición #{} no tiene tópico de respuesta", contador),
+        }
+    }
+}
+
+fn serializar_headers(headers: &[(String, String)]) -> Option<Vec<u8>> {
+    if headers.is_empty() {
+        return None;
+    }
+
+    let mut resultado: Headers = Headers::new();
+    for (clave, valor) in headers {
+        resultado.insertar(clave, valor);
+    }
+
+    Some(resultado.serializar())
+}
@@ -0,0 +1,165 @@
+use drone::dron::Dron;
+use lib::{camara::Camara, headers::Headers, incidente::Incidente, serializables::Serializable};
+use messaging_client::cliente::publicacion::Publicacion;
+
+/// Muestra una publicación recibida: el tópico, el tópico de respuesta, los
+/// headers y el payload
+pub fn formatear_publicacion(numero: usize, publicacion: &Publicacion) -> String {
+    let mut texto: String = format!("[#{}] Recibido en \"{}\"", numero, publicacion.topico);
+
+    if let Some(replay_to) = &publicacion.replay_to {
+        texto.push_str(&format!(" (responder a \"{}\")", replay_to));
+    }
+
+    texto.push('\n');
+
+    if let Some(header) = &publicacion.header {
+        match Headers::deserializar(header) {
+            Ok(headers) => {
+                for (clave, valor) in headers.iter() {
+                    texto.push_str(&format!("{}: {}\n", clave, valor));
+                }
+            }
+            Err(_) => texto.push_str(&format!("{}\n", String::from_utf8_lossy(header).trim())),
+        }
+    }
+
+    texto.push_str(&formatear_payload(
+        &publicacion.topico,
+        &publicacion.payload,
+    ));
+    texto
+}
+
+/// Si el tópico es uno de los que usa el sistema, muestra el payload como
+/// cámaras, incidentes o drones. Si no, lo muestra como texto
+pub fn formatear_payload(topico: &str, payload: &[u8]) -> String {
+    let tokens: Vec<&str> = topico.split('.').collect();
+
+    let formateado: Option<String> = match tokens.as_slice() {
+        ["camaras"] => Vec::<Camara>::deserializar(payload).ok().map(|camaras| {
+            camaras
+                .iter()
+                .map(formatear_camara)
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+        ["incidentes"] => Vec::<Incidente>::deserializar(payload)
+            .ok()
+            .map(|incidentes| {
+                incidentes
+                    .iter()
+                    .map(formatear_incidente)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }),
+        ["incidentes", _, "creado" | "finalizado"] => Incidente::deserializar(payload)
+            .ok()
+            .map(|incidente| formatear_incidente(&incidente)),
+        ["dron", _, "informacion"] => Dron::deserializar(payload)
+            .ok()
+            .map(|dron| formatear_dron(&dron)),
+        _ => None,
+    };
+
+    formateado.unwrap_or_else(|| String::from_utf8_lossy(payload).to_string())
+}
+
+fn formatear_camara(camara: &Camara) -> String {
+    let mut primarios: Vec<&u64> = camara.incidentes_primarios.iter().collect();
+    let mut secundarios: Vec<&u64> = camara.incidentes_secundarios.iter().collect();
+    primarios.sort();
+    secundarios.sort();
+
+    format!(
+        "Cámara {} ({}) en ({}, {}) rango {} - incidentes primarios: {:?}, secundarios: {:?}",
+        camara.id,
+        if camara.activa() {
+            "activa"
+        } else {
+            "en ahorro"
+        },
+        camara.latitud,
+        camara.longitud,
+        camara.rango,
+        primarios,
+        secundarios
+    )
+}
+
+fn formatear_incidente(incidente: &Incidente) -> String {
+    format!(
+        "Incidente {} \"{}\" en ({}, {}) - inicio: {}",
+        incidente.id, incidente.detalle, incidente.latitud, incidente.longitud, incidente.inicio
+    )
+}
+
+fn formatear_dron(dron: &Dron) -> String {
+    format!(
+        "Dron {} ({}) en ({}, {}) - batería: {}/{}, central: ({}, {}) rango {}",
+        dron.id,
+        dron.estado.estado_a_str(),
+        dron.desplazamiento.latitud,
+        dron.desplazamiento.longitud,
+        dron.bateria.duracion_actual,
+        dron.bateria.duracion_total,
+        dron.central.latitud,
+        dron.central.longitud,
+        dron.central.rango
+    )
+}
+
+/// Reemplaza en la plantilla de `reply` los valores de la petición recibida
+pub fn completar_plantilla(plantilla: &str, peticion: &Publicacion, contador: usize) -> String {
+    plantilla
+        .replace("{{peticion}}", &String::from_utf8_lossy(&peticion.payload))
+        .replace("{{topico}}", &peticion.topico)
+        .replace("{{contador}}", &contador.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::{camara::Camara, incidente::Incidente, serializables::Serializable};
+    use messaging_client::cliente::publicacion::Publicacion;
+
+    use super::{completar_plantilla, formatear_payload};
+
+    #[test]
+    fn payloads_del_sistema() {
+        let incidente = Incidente::new(3, "Robo".to_string(), -34.6, -58.4, 100);
+        assert_eq!(
+            formatear_payload("incidentes.3.creado", &incidente.serializar()),
+            "Incidente 3 \"Robo\" en (-34.6, -58.4) - inicio: 100"
+        );
+
+        let camaras = vec![Camara::new(1, 1.0, 2.0, 50.0)];
+        assert_eq!(
+            formatear_payload("camaras", &camaras.serializar()),
+            "Cámara 1 (en ahorro) en (1, 2) rango 50 - incidentes primarios: [], secundarios: []"
+        );
+    }
+
+    #[test]
+    fn payload_de_texto() {
+        assert_eq!(
+            formatear_payload("comandos.camaras", b"actualizar"),
+            "actualizar"
+        );
+        assert_eq!(formatear_payload("incidentes", b"\xff"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn plantilla() {
+        let peticion = Publicacion {
+            topico: "saludar".to_string(),
+            replay_to: Some("_INBOX.1".to_string()),
+            payload: b"mundo".to_vec(),
+            header: None,
+        };
+
+        assert_eq!(
+            completar_plantilla("hola {{peticion}} ({{topico}} #{{contador}})", &peticion, 2),
+            "hola mundo (saludar #2)"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod argumentos;
+pub mod benchmark;
+pub mod comandos;
+pub mod formato;
@@ -0,0 +1,39 @@
+use std::{env::args, io, process};
+
+use cli::{
+    argumentos::{Argumentos, Comando, AYUDA},
+    comandos,
+};
+use lib::configuracion::Configuracion;
+use messaging_client::cliente::opciones::OpcionesConexion;
+
+fn main() {
+    if let Err(e) = ejecutar() {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn ejecutar() -> io::Result<()> {
+    let args: Vec<String> = args().skip(1).collect();
+    let argumentos: Argumentos =
+        Argumentos::parsear(&args.iter().map(|s| s.as_str()).collect::<Vec<&str>>())?;
+
+    if argumentos.comando == Comando::Ayuda {
+        println!("{}", AYUDA);
+        return Ok(());
+    }
+
+    let configuracion: Configuracion = Configuracion::desde_parametros_y_leer(
+        &argumentos
+            .parametros_configuracion
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<&str>>(),
+    )?;
+
+    comandos::ejecutar(
+        OpcionesConexion::desde_configuracion(&configuracion),
+        argumentos.comando,
+    )
+}
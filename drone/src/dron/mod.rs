@@ -26,7 +26,7 @@ use {
     central::Central,
     desplazamiento::Desplazamiento,
     estado::Estado,
-    messaging_client::cliente::{suscripcion::Suscripcion, Cliente},
+    messaging_client::cliente::{opciones::OpcionesConexion, suscripcion::Suscripcion, Cliente},
 };
 
 pub const NO_ATENDIENDO: u64 = u64::MAX;
@@ -160,24 +160,18 @@ impl Dron {
 
     /// Conectar el cliente al servidor.
     fn conectar(&self, configuracion: &Configuracion) -> io::Result<Cliente> {
-        let direccion: String = configuracion
-            .obtener::<String>("direccion")
-            .unwrap_or("127.0.0.1".to_string());
+        let mut opciones: OpcionesConexion = OpcionesConexion::desde_configuracion(configuracion);
+        // El servidor informa con este nombre cuando el dron se desconecta
+        if opciones.nombre.is_none() {
+            opciones.nombre = Some(format!("dron-{}", self.id));
+        }
 
-        let puerto: u16 = configuracion.obtener::<u16>("puerto").unwrap_or(4222);
         println!(
-            "Conectando al servidor de NATS en {}:{}\n",
-            direccion, puerto
+            "Conectando al servidor de NATS en {}\n",
+            opciones.servidores.join(", ")
         );
 
-        let user: Option<String> = configuracion.obtener::<String>("user");
-        let pass: Option<String> = configuracion.obtener::<String>("pass");
-
-        if user.is_some() || pass.is_some() {
-            Cliente::conectar_con_user_y_pass(&format!("{}:{}", direccion, puerto), user, pass)
-        } else {
-            Cliente::conectar(&format!("{}:{}", direccion, puerto))
-        }
+        Cliente::conectar_con_opciones(opciones)
     }
 
     /// El bucle de eventos del dron.
@@ -228,7 +222,9 @@ impl Dron {
             let topico_string: String = format!("dron.{}.id.incidente.atendido", self.id);
             let body: String = self.id_incidente_a_atender.to_string();
 
-            cliente.publicar(topico_string.as_str(), body.as_bytes(), None)?;
+            // Si el ciclo se reintenta se vuelve a publicar lo mismo, con el
+            // mismo id: el servidor puede descartar las repeticiones
+            cliente.publicar_deduplicado(topico_string.as_str(), body.as_bytes())?;
         }
         Ok(())
     }
@@ -0,0 +1,66 @@
+use lib::configuracion::esquema::{Clave, Esquema, TipoValor};
+use messaging_client::cliente::opciones::OpcionesConexion;
+
+/// Las claves de configuración de un dron. Los valores del dron se pueden
+/// omitir si se indica el archivo de `drones`, de donde se toman según el id
+pub fn esquema() -> Esquema {
+    Esquema::new("drone", "Dron de atención de incidentes")
+        .con_clave(Clave::new("id", TipoValor::Natural, "Id del dron").requerida())
+        .con_clave(Clave::new(
+            "drones",
+            TipoValor::Texto,
+            "Archivo csv con los drones, del que se toma el dron con el id",
+        ))
+        .con_clave(
+            Clave::new("latitud", TipoValor::Decimal, "Latitud de la central")
+                .con_rango(-90., 90.)
+                .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new("longitud", TipoValor::Decimal, "Longitud de la central")
+                .con_rango(-180., 180.)
+                .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new("rango", TipoValor::Decimal, "Radio de operación")
+                .con_minimo(0.)
+                .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new("velocidad", TipoValor::Natural, "Velocidad del dron")
+                .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new(
+                "duracion_bateria",
+                TipoValor::Natural,
+                "Segundos que dura la batería completa",
+            )
+            .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new(
+                "duracion_bateria_minima",
+                TipoValor::Natural,
+                "Segundos de batería con los que vuelve a recargar",
+            )
+            .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new(
+                "tiempo_recarga",
+                TipoValor::Natural,
+                "Segundos que tarda en recargar la batería",
+            )
+            .requerida_salvo("drones"),
+        )
+        .con_clave(
+            Clave::new(
+                "tiempo_atender_incidente",
+                TipoValor::Natural,
+                "Segundos que tarda en atender un incidente",
+            )
+            .requerida_salvo("drones"),
+        )
+        .con_claves(OpcionesConexion::claves())
+}
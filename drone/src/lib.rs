@@ -1 +1,2 @@
 pub mod dron;
+pub mod esquema;
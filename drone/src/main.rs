@@ -5,8 +5,11 @@ use std::{
     process,
 };
 
-use drone::dron::Dron;
-use lib::configuracion::Configuracion;
+use drone::{dron::Dron, esquema::esquema};
+use lib::configuracion::{
+    esquema::{ErrorClave, ErrorConfiguracion},
+    Configuracion,
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
     if let Err(e) = intentar_iniciar_dron() {
@@ -18,27 +21,28 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 fn intentar_iniciar_dron() -> Result<(), Box<dyn Error>> {
-    let mut configuracion: Configuracion = Configuracion::desde_argv()?;
-
-    // Si no hay id, no se puede tomar el dron del archivo ni crear uno por comando,
-    // asi que devuelvo un error
-    if let Some(id_dron) = configuracion.obtener::<u64>("id") {
-        // Si mando un archivo de drones, obtengo el dron del archivo según su id y creo el dron
-        if let Some(ruta_archivo_drones) = configuracion.obtener::<String>("drones") {
-            configuracion = config_dron_desde_archivo(id_dron, ruta_archivo_drones)?;
-        // Si no mandé archivo de drones, creo el dron por consola si la cantidad de argumentos es correcta
-        } else if configuracion.longitud() < 9 {
-            return Err(Box::new(io::Error::new(io::ErrorKind::Other, "Cantidad de argumentos incorrecta para crear el dron. \nEspecifique los siguientes valores: id, latitud, longitud, rango, velocidad, duracion_bateria, duracion_bateria_minima, tiempo_recarga y tiempo_atender_incidente")));
+    // El esquema exige el id y, si no se manda el archivo de drones, todos
+    // los valores del dron
+    let mut configuracion: Configuracion = esquema().desde_argv_o_terminar();
+
+    // Si mando un archivo de drones, tomo los valores del dron del archivo
+    // según su id, y los valido como si se hubieran mandado por consola
+    if let (Some(id_dron), Some(ruta_archivo_drones)) = (
+        configuracion.obtener::<u64>("id"),
+        configuracion.obtener::<String>("drones"),
+    ) {
+        configuracion.extender(config_dron_desde_archivo(id_dron, ruta_archivo_drones)?);
+
+        let errores: Vec<ErrorClave> = esquema().validar(&configuracion);
+        if !errores.is_empty() {
+            return Err(Box::new(ErrorConfiguracion::Claves(errores)));
         }
+    }
 
-        let mut dron: Dron = Dron::new(&configuracion);
-        dron.iniciar(&configuracion)?;
+    let mut dron: Dron = Dron::new(&configuracion);
+    dron.iniciar(&configuracion)?;
 
-        Ok(())
-    } else {
-        println!("No se envió id de dron.");
-        process::exit(1);
-    }
+    Ok(())
 }
 
 /// Esta función toma el id de un dron que se quiere crear y la ruta al archivo
@@ -71,10 +75,7 @@ fn config_dron_desde_archivo(
     }
 
     if !existe_dron {
-        return Err(Box::new(io::Error::new(
-            io::ErrorKind::Other,
-            "No existe el dron con ese id",
-        )));
+        return Err(Box::new(io::Error::other("No existe el dron con ese id")));
     }
 
     let parametros: &[String; 9] = &[
@@ -0,0 +1,120 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lib::parseador::Parseador;
+
+/// Tamaño de cada lectura del socket (igual que `Conexion::leer_bytes` en el servidor)
+const TAMANIO_LECTURA: usize = 1024;
+
+fn flujo_pubs(cantidad: usize, tamanio_payload: usize) -> Vec<u8> {
+    let payload: Vec<u8> = vec![b'x'; tamanio_payload];
+    let mut flujo: Vec<u8> = Vec::new();
+
+    for i in 0..cantidad {
+        flujo.extend_from_slice(
+            format!("PUB camaras.{} {}\r\n", i % 10, tamanio_payload).as_bytes(),
+        );
+        flujo.extend_from_slice(&payload);
+        flujo.extend_from_slice(b"\r\n");
+    }
+
+    flujo
+}
+
+fn flujo_hmsgs(cantidad: usize, tamanio_payload: usize) -> Vec<u8> {
+    let headers: &[u8] = b"NATS/1.0\r\nNats-Msg-Id: 1234\r\n\r\n";
+    let payload: Vec<u8> = vec![b'x'; tamanio_payload];
+    let mut flujo: Vec<u8> = Vec::new();
+
+    for i in 0..cantidad {
+        flujo.extend_from_slice(
+            format!(
+                "HMSG incidentes.{}.creado {} _INBOX.abc {} {}\r\n",
+                i,
+                i % 5,
+                headers.len(),
+                headers.len() + payload.len()
+            )
+            .as_bytes(),
+        );
+        flujo.extend_from_slice(headers);
+        flujo.extend_from_slice(&payload);
+        flujo.extend_from_slice(b"\r\n");
+    }
+
+    flujo
+}
+
+/// Le pasa el flujo al parser de a `TAMANIO_LECTURA` bytes (como llegaría del
+/// socket) y consume todos los mensajes
+fn parsear(flujo: &[u8]) -> usize {
+    let mut parseador: Parseador = Parseador::new();
+    let mut mensajes: usize = 0;
+
+    for lectura in flujo.chunks(TAMANIO_LECTURA) {
+        parseador.agregar_bytes(lectura);
+
+        while let Some(mensaje) = parseador.proximo_mensaje() {
+            black_box(mensaje);
+            mensajes += 1;
+        }
+    }
+
+    mensajes
+}
+
+/// Lee el flujo con `leer_de`, como el servidor lee del socket (sin la copia
+/// de `agregar_bytes`), y consume todos los mensajes
+fn leer_y_parsear(flujo: &[u8]) -> usize {
+    let mut parseador: Parseador = Parseador::new();
+    let mut lector: &[u8] = flujo;
+    let mut mensajes: usize = 0;
+
+    while parseador.leer_de(&mut lector).unwrap_or(0) > 0 {
+        while let Some(mensaje) = parseador.proximo_mensaje() {
+            black_box(mensaje);
+            mensajes += 1;
+        }
+    }
+
+    mensajes
+}
+
+fn mensajes_chicos(c: &mut Criterion) {
+    let mut grupo = c.benchmark_group("mensajes_chicos");
+
+    for tamanio in [16, 256] {
+        let flujo: Vec<u8> = flujo_pubs(10_000, tamanio);
+        grupo.throughput(Throughput::Bytes(flujo.len() as u64));
+        grupo.bench_with_input(BenchmarkId::new("pub", tamanio), &flujo, |b, flujo| {
+            b.iter(|| parsear(flujo))
+        });
+    }
+
+    let flujo: Vec<u8> = flujo_hmsgs(10_000, 64);
+    grupo.throughput(Throughput::Bytes(flujo.len() as u64));
+    grupo.bench_with_input(BenchmarkId::new("hmsg", 64), &flujo, |b, flujo| {
+        b.iter(|| parsear(flujo))
+    });
+
+    grupo.finish();
+}
+
+fn payloads_grandes(c: &mut Criterion) {
+    let mut grupo = c.benchmark_group("payloads_grandes");
+    grupo.sample_size(10);
+
+    for tamanio in [64 * 1024, 1024 * 1024] {
+        let flujo: Vec<u8> = flujo_pubs(1, tamanio);
+        grupo.throughput(Throughput::Bytes(flujo.len() as u64));
+        grupo.bench_with_input(BenchmarkId::new("pub", tamanio), &flujo, |b, flujo| {
+            b.iter(|| parsear(flujo))
+        });
+        grupo.bench_with_input(BenchmarkId::new("leer_de", tamanio), &flujo, |b, flujo| {
+            b.iter(|| leer_y_parsear(flujo))
+        });
+    }
+
+    grupo.finish();
+}
+
+criterion_group!(benches, mensajes_chicos, payloads_grandes);
+criterion_main!(benches);
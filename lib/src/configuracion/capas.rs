@@ -0,0 +1,310 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{Configuracion, Valor};
+
+/// Prefijo de las variables de entorno que configuran a los programas:
+/// `TP_PUERTO=4222` es `puerto=4222`
+pub const PREFIJO_ENTORNO: &str = "TP_";
+
+/// De dónde salió un valor de la configuración. Los orígenes están en orden
+/// de prioridad: valores por defecto < archivos < entorno < parámetros <
+/// programa. Lo que setea el programa (por ejemplo un servidor embebido) le
+/// gana a todo lo demás, para que recargar los archivos no lo pise
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origen {
+    Defecto,
+    /// El archivo de `config` (o uno incluido desde él)
+    Archivo(String),
+    /// La variable de entorno
+    Entorno(String),
+    /// Un parámetro `clave=valor` de la línea de comandos
+    Parametro,
+    /// Lo setea el programa (o lo parsea de un texto)
+    Programa,
+}
+
+impl Origen {
+    fn prioridad(&self) -> u8 {
+        match self {
+            Origen::Defecto => 0,
+            Origen::Archivo(_) => 1,
+            Origen::Entorno(_) => 2,
+            Origen::Parametro => 3,
+            Origen::Programa => 4,
+        }
+    }
+}
+
+impl Display for Origen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origen::Defecto => write!(f, "por defecto"),
+            Origen::Archivo(ruta) => write!(f, "archivo {}", ruta),
+            Origen::Entorno(variable) => write!(f, "entorno {}", variable),
+            Origen::Parametro => write!(f, "parámetro"),
+            Origen::Programa => write!(f, "programa"),
+        }
+    }
+}
+
+impl Configuracion {
+    /// Arma la configuración con todos los orígenes, de menor a mayor
+    /// prioridad: los archivos de `config` (separados por comas, el último
+    /// gana), las variables de entorno con el prefijo `TP_` y los parámetros.
+    /// `config` también se puede pasar por entorno (`TP_CONFIG`)
+    pub fn desde_capas(parametros: &[&str], entorno: &[(String, String)]) -> io::Result<Self> {
+        let parametros: Configuracion = Configuracion::desde_parametros(parametros);
+        let entorno: Configuracion = Configuracion::desde_entorno(entorno);
+
+        let mut config: Configuracion = Configuracion::new();
+
+        let archivos: Option<String> = parametros
+            .obtener::<String>("config")
+            .or(entorno.obtener::<String>("config"));
+
+        for archivo in archivos.iter().flat_map(|a| a.split(',')) {
+            let archivo: &str = archivo.trim();
+            if !archivo.is_empty() {
+                config.fusionar(Configuracion::leer(archivo)?);
+            }
+        }
+
+        config.fusionar(entorno);
+        config.fusionar(parametros);
+
+        Ok(config)
+    }
+
+    /// Las variables que empiezan con `TP_`, con la clave de `clave_entorno`
+    pub fn desde_entorno(variables: &[(String, String)]) -> Self {
+        let mut config: Configuracion = Configuracion::new();
+
+        for (variable, valor) in variables {
+            if let Some(clave) = clave_entorno(variable) {
+                config.setear_con_origen(&clave, valor, Origen::Entorno(variable.clone()));
+            }
+        }
+
+        config
+    }
+
+    /// Agrega los valores de otra configuración, reemplazando solo los que
+    /// vienen de un origen con la misma o menor prioridad. Así al recargar el
+    /// archivo no se pisan los valores del entorno ni de los parámetros
+    pub fn fusionar(&mut self, otra: Configuracion) {
+        for (clave, valor) in otra.valores {
+            let reemplazar: bool = self
+                .valores
+                .get(&clave)
+                .is_none_or(|actual| actual.origen.prioridad() <= valor.origen.prioridad());
+
+            if reemplazar {
+                self.valores.insert(clave, valor);
+            }
+        }
+    }
+
+    /// Los valores que no salieron de un archivo. Al recargar los archivos se
+    /// parte de acá, así las claves que se borraron del archivo dejan de estar
+    pub fn sin_archivos(&self) -> Configuracion {
+        let mut config: Configuracion = Configuracion::new();
+        config.valores = self
+            .valores
+            .iter()
+            .filter(|(_, valor)| !matches!(valor.origen, Origen::Archivo(_)))
+            .map(|(clave, valor)| (clave.clone(), valor.clone()))
+            .collect();
+        config
+    }
+
+    /// La configuración efectiva, una clave por línea (ordenadas) con el
+    /// origen de cada valor: `puerto=4222  # entorno TP_PUERTO`
+    pub fn efectiva(&self) -> String {
+        let mut valores: Vec<(&String, &Valor)> = self.valores.iter().collect();
+        valores.sort_by(|a, b| a.0.cmp(b.0));
+
+        valores
+            .iter()
+            .map(|(clave, valor)| format!("{}={}  # {}\n", clave, valor.texto, valor.origen))
+            .collect()
+    }
+}
+
+/// La clave de una variable que empieza con `TP_`: sin el prefijo y en
+/// minúsculas. Como los nombres de las variables no pueden tener puntos, `__`
+/// se lee como `.`, y lo que sigue conserva las mayúsculas (son tópicos o
+/// usuarios): `TP_MAPEO__Dron__A=b` es `mapeo.Dron.A=b`
+pub fn clave_entorno(variable: &str) -> Option<String> {
+    let clave: &str = variable.strip_prefix(PREFIJO_ENTORNO)?;
+
+    let clave: String = match clave.split_once("__") {
+        Some((prefijo, resto)) => {
+            format!("{}.{}", prefijo.to_lowercase(), resto.replace("__", "."))
+        }
+        None => clave.to_lowercase(),
+    };
+
+    (!clave.is_empty()).then_some(clave)
+}
+
+/// Lee el archivo resolviendo los `include=` (relativos al archivo) y las
+/// variables `${VARIABLE}`. Los valores que siguen a un `include` reemplazan
+/// a los del archivo incluido. `visitados` son los archivos que se están
+/// leyendo, para no incluir uno dentro de sí mismo
+pub(super) fn leer_con_inclusiones(
+    ruta: &Path,
+    visitados: &mut Vec<PathBuf>,
+) -> io::Result<Configuracion> {
+    let canonica: PathBuf = ruta.canonicalize().unwrap_or(ruta.to_path_buf());
+    if visitados.contains(&canonica) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} se incluye a sí mismo", ruta.display()),
+        ));
+    }
+
+    let contenido: String = fs::read_to_string(ruta).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("No se pudo leer {}: {}", ruta.display(), e),
+        )
+    })?;
+
+    visitados.push(canonica);
+
+    let origen = Origen::Archivo(ruta.display().to_string());
+    let mut config: Configuracion = Configuracion::new();
+
+    for linea in contenido.lines() {
+        let Some((clave, valor)) = Configuracion::parsear_linea(linea) else {
+            continue;
+        };
+        let valor: String =
+            interpolar(&valor, |variable| std::env::var(variable).ok()).map_err(|variable| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "La variable {} de {} no está definida",
+                        variable,
+                        ruta.display()
+                    ),
+                )
+            })?;
+
+        if clave == "include" {
+            let incluido: PathBuf = ruta.parent().unwrap_or(Path::new("")).join(valor);
+            config.extender(leer_con_inclusiones(&incluido, visitados)?);
+        } else {
+            config.setear_con_origen(clave, valor, origen.clone());
+        }
+    }
+
+    visitados.pop();
+    Ok(config)
+}
+
+/// Reemplaza cada `${VARIABLE}` por su valor. Si alguna no está definida,
+/// devuelve su nombre
+fn interpolar(texto: &str, variable: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut resultado: String = String::new();
+    let mut resto: &str = texto;
+
+    while let Some(inicio) = resto.find("${") {
+        let Some(largo) = resto[inicio + 2..].find('}') else {
+            break;
+        };
+
+        let nombre: &str = &resto[inicio + 2..inicio + 2 + largo];
+        resultado.push_str(&resto[..inicio]);
+        resultado.push_str(&variable(nombre).ok_or(nombre.to_string())?);
+        resto = &resto[inicio + 3 + largo..];
+    }
+
+    resultado.push_str(resto);
+    Ok(resultado)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpolar, Origen};
+    use crate::configuracion::Configuracion;
+
+    fn entorno(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+        variables
+            .iter()
+            .map(|(variable, valor)| (variable.to_string(), valor.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn probar_prioridad_de_las_capas() {
+        let directorio = std::env::temp_dir().join("rust.configuracion.capas");
+        std::fs::create_dir_all(&directorio).unwrap();
+        let base = directorio.join("base.txt");
+        let principal = directorio.join("principal.txt");
+        std::fs::write(&base, "puerto=1\nhilos=1\ncuentas=base.csv").unwrap();
+        std::fs::write(
+            &principal,
+            "hilos=2\ninclude=base.txt\ncuentas=${CARGO_PKG_NAME}/cuentas.csv\ndireccion=0.0.0.0\nmax_payload=1",
+        )
+        .unwrap();
+
+        let config = format!("config={}", principal.display());
+        let configuracion = Configuracion::desde_capas(
+            &[&config, "max_payload=3"],
+            &entorno(&[("TP_PUERTO", "2"), ("TP_MAX_PAYLOAD", "2"), ("OTRA", "x")]),
+        )
+        .unwrap();
+
+        // El archivo incluido reemplaza lo anterior y lo que sigue lo reemplaza a él
+        assert_eq!(configuracion.obtener::<u16>("hilos"), Some(1));
+        assert_eq!(
+            configuracion.obtener::<String>("cuentas"),
+            Some("lib/cuentas.csv".to_string())
+        );
+        assert_eq!(
+            configuracion.origen("hilos"),
+            Some(&Origen::Archivo(base.display().to_string()))
+        );
+
+        assert_eq!(configuracion.obtener::<u16>("puerto"), Some(2));
+        assert_eq!(
+            configuracion.origen("puerto"),
+            Some(&Origen::Entorno("TP_PUERTO".to_string()))
+        );
+        assert_eq!(configuracion.obtener::<u16>("max_payload"), Some(3));
+        assert_eq!(configuracion.obtener::<String>("otra"), None);
+        assert!(configuracion
+            .efectiva()
+            .contains("puerto=2  # entorno TP_PUERTO\n"));
+
+        // Al recargar el archivo no se pisan el entorno ni los parámetros
+        let mut recargada = configuracion.sin_archivos();
+        assert_eq!(recargada.obtener::<u16>("hilos"), None);
+        recargada.fusionar(Configuracion::leer(&principal.display().to_string()).unwrap());
+        assert_eq!(recargada.efectiva(), configuracion.efectiva());
+
+        std::fs::write(&base, "include=principal.txt").unwrap();
+        assert!(Configuracion::leer(&principal.display().to_string()).is_err());
+
+        std::fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn probar_interpolacion() {
+        let variable = |nombre: &str| (nombre == "A").then(|| "1".to_string());
+
+        assert_eq!(interpolar("x${A}y${A}", variable), Ok("x1y1".to_string()));
+        assert_eq!(interpolar("$A ${A", variable), Ok("$A ${A".to_string()));
+        assert_eq!(interpolar("${B}", variable), Err("B".to_string()));
+        assert_eq!(
+            Configuracion::desde_entorno(&entorno(&[("TP_MAPEO__Dron__A", "b")]))
+                .obtener::<String>("mapeo.Dron.A"),
+            Some("b".to_string())
+        );
+    }
+}
@@ -0,0 +1,609 @@
+use std::{
+    env::{args, vars},
+    error::Error,
+    fmt::Display,
+    io,
+    process::exit,
+};
+
+use super::{
+    capas::{clave_entorno, Origen, PREFIJO_ENTORNO},
+    Configuracion, Valor,
+};
+
+/// Parámetros que piden la ayuda en vez de iniciar el programa
+const PARAMETROS_AYUDA: [&str; 2] = ["--help", "-h"];
+
+/// Parámetro que muestra la configuración efectiva (con el origen de cada
+/// valor) en vez de iniciar el programa
+const PARAMETRO_CONFIGURACION: &str = "--mostrar-configuracion";
+
+/// Tipo de los valores de una clave
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TipoValor {
+    Texto,
+    /// `true` o `false`
+    Booleano,
+    /// Entero sin signo
+    Natural,
+    Decimal,
+    /// Uno de los valores de la lista
+    Opciones(&'static [&'static str]),
+}
+
+impl TipoValor {
+    fn valido(&self, valor: &str) -> bool {
+        match self {
+            TipoValor::Texto => true,
+            TipoValor::Booleano => valor.parse::<bool>().is_ok(),
+            TipoValor::Natural => valor.parse::<u64>().is_ok(),
+            TipoValor::Decimal => valor.parse::<f64>().is_ok_and(f64::is_finite),
+            TipoValor::Opciones(opciones) => opciones.contains(&valor),
+        }
+    }
+}
+
+impl Display for TipoValor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TipoValor::Texto => write!(f, "texto"),
+            TipoValor::Booleano => write!(f, "true|false"),
+            TipoValor::Natural => write!(f, "natural"),
+            TipoValor::Decimal => write!(f, "decimal"),
+            TipoValor::Opciones(opciones) => write!(f, "{}", opciones.join("|")),
+        }
+    }
+}
+
+/// Una clave de configuración que acepta un programa
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clave {
+    nombre: &'static str,
+    tipo: TipoValor,
+    descripcion: &'static str,
+    defecto: Option<String>,
+    minimo: Option<f64>,
+    maximo: Option<f64>,
+    requerida: bool,
+    /// Con esta otra clave la requerida se puede omitir
+    salvo: Option<&'static str>,
+    /// Si `nombre` es el prefijo de una familia de claves (`mapeo.<origen>`)
+    prefijo: bool,
+}
+
+impl Clave {
+    pub fn new(nombre: &'static str, tipo: TipoValor, descripcion: &'static str) -> Self {
+        Self {
+            nombre,
+            tipo,
+            descripcion,
+            defecto: None,
+            minimo: None,
+            maximo: None,
+            requerida: false,
+            salvo: None,
+            prefijo: false,
+        }
+    }
+
+    /// Todas las claves que empiezan con `prefijo` (por ejemplo `mapeo.`)
+    pub fn prefijo(prefijo: &'static str, tipo: TipoValor, descripcion: &'static str) -> Self {
+        Self {
+            prefijo: true,
+            ..Self::new(prefijo, tipo, descripcion)
+        }
+    }
+
+    /// Valor que toma la clave si no se configura
+    pub fn con_defecto<T: ToString>(mut self, defecto: T) -> Self {
+        self.defecto = Some(defecto.to_string());
+        self
+    }
+
+    /// Mínimo y máximo (inclusive) de una clave numérica
+    pub fn con_rango(mut self, minimo: f64, maximo: f64) -> Self {
+        self.minimo = Some(minimo);
+        self.maximo = Some(maximo);
+        self
+    }
+
+    pub fn con_minimo(mut self, minimo: f64) -> Self {
+        self.minimo = Some(minimo);
+        self
+    }
+
+    pub fn requerida(mut self) -> Self {
+        self.requerida = true;
+        self
+    }
+
+    /// Requerida, salvo que se configure la otra clave
+    pub fn requerida_salvo(mut self, otra: &'static str) -> Self {
+        self.requerida = true;
+        self.salvo = Some(otra);
+        self
+    }
+
+    pub fn nombre(&self) -> &'static str {
+        self.nombre
+    }
+
+    fn corresponde(&self, clave: &str) -> bool {
+        if self.prefijo {
+            clave.len() > self.nombre.len() && clave.starts_with(self.nombre)
+        } else {
+            clave == self.nombre
+        }
+    }
+
+    fn validar(&self, clave: &str, valor: &str) -> Option<ErrorClave> {
+        let en_rango = |numero: f64| {
+            self.minimo.is_none_or(|minimo| numero >= minimo)
+                && self.maximo.is_none_or(|maximo| numero <= maximo)
+        };
+
+        let valido: bool = self.tipo.valido(valor)
+            && match self.tipo {
+                TipoValor::Natural | TipoValor::Decimal => valor.parse().is_ok_and(en_rango),
+                _ => true,
+            };
+
+        if valido {
+            return None;
+        }
+
+        Some(ErrorClave::Invalida {
+            clave: clave.to_string(),
+            valor: valor.to_string(),
+            esperado: self.esperado(),
+        })
+    }
+
+    /// El tipo y el rango, como se muestran en la ayuda y en los errores
+    fn esperado(&self) -> String {
+        match (self.minimo, self.maximo) {
+            (Some(minimo), Some(maximo)) => format!("{} entre {} y {}", self.tipo, minimo, maximo),
+            (Some(minimo), None) => format!("{} desde {}", self.tipo, minimo),
+            (None, Some(maximo)) => format!("{} hasta {}", self.tipo, maximo),
+            (None, None) => self.tipo.to_string(),
+        }
+    }
+
+    fn uso(&self) -> String {
+        let nombre: String = if self.prefijo {
+            format!("{}<...>", self.nombre)
+        } else {
+            self.nombre.to_string()
+        };
+        format!("{}=<{}>", nombre, self.esperado())
+    }
+}
+
+/// Un problema con una de las claves de la configuración
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorClave {
+    /// La clave no es del programa. Si se parece a una que sí, se sugiere
+    Desconocida {
+        clave: String,
+        sugerencia: Option<&'static str>,
+    },
+    /// El valor no es del tipo de la clave o está fuera de su rango
+    Invalida {
+        clave: String,
+        valor: String,
+        esperado: String,
+    },
+    /// No se configuró una clave requerida
+    Faltante(&'static str),
+    /// Un parámetro de la línea de comandos sin `=`
+    SinValor(String),
+}
+
+impl Display for ErrorClave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorClave::Desconocida {
+                clave,
+                sugerencia: Some(sugerencia),
+            } => write!(f, "Clave desconocida: {} (¿{}?)", clave, sugerencia),
+            ErrorClave::Desconocida { clave, .. } => write!(f, "Clave desconocida: {}", clave),
+            ErrorClave::Invalida {
+                clave,
+                valor,
+                esperado,
+            } => write!(
+                f,
+                "Valor inválido para {}: '{}' (se esperaba {})",
+                clave, valor, esperado
+            ),
+            ErrorClave::Faltante(clave) => write!(f, "Falta la clave {}", clave),
+            ErrorClave::SinValor(parametro) => write!(
+                f,
+                "Parámetro sin valor: '{}' (se esperaba clave=valor)",
+                parametro
+            ),
+        }
+    }
+}
+
+/// Por qué no se pudo cargar la configuración de un programa
+#[derive(Debug)]
+pub enum ErrorConfiguracion {
+    /// Se pidió la ayuda con `--help`: no es un error, el programa muestra
+    /// el texto y termina
+    Ayuda(String),
+    /// Se pidió la configuración efectiva con `--mostrar-configuracion`. Como
+    /// la ayuda, el programa la muestra y termina
+    Efectiva(String),
+    /// No se pudo leer el archivo de `config`
+    Lectura(io::Error),
+    /// Todas las claves con problemas, para corregirlas de una vez
+    Claves(Vec<ErrorClave>),
+}
+
+impl Display for ErrorConfiguracion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorConfiguracion::Ayuda(texto) | ErrorConfiguracion::Efectiva(texto) => {
+                write!(f, "{}", texto)
+            }
+            ErrorConfiguracion::Lectura(error) => {
+                write!(f, "No se pudo leer la configuración: {}", error)
+            }
+            ErrorConfiguracion::Claves(errores) => {
+                write!(f, "Configuración inválida:")?;
+                for error in errores {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ErrorConfiguracion {}
+
+/// Las claves que acepta un programa, con sus tipos, valores por defecto y
+/// descripciones. Valida la configuración y genera el texto de `--help`:
+///
+/// ```
+/// use lib::configuracion::esquema::{Clave, Esquema, TipoValor};
+///
+/// let esquema = Esquema::new("servidor", "Servidor de ejemplo")
+///     .con_clave(Clave::new("puerto", TipoValor::Natural, "Puerto").con_defecto(4222));
+///
+/// let configuracion = esquema.cargar(&[]).unwrap();
+/// assert_eq!(configuracion.obtener::<u16>("puerto"), Some(4222));
+/// assert!(esquema.cargar(&["puerto=x", "purto=1"]).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Esquema {
+    programa: &'static str,
+    descripcion: &'static str,
+    claves: Vec<Clave>,
+}
+
+impl Esquema {
+    /// Todos los esquemas aceptan `config`, el archivo de configuración
+    pub fn new(programa: &'static str, descripcion: &'static str) -> Self {
+        Self {
+            programa,
+            descripcion,
+            claves: vec![Clave::new(
+                "config",
+                TipoValor::Texto,
+                "Archivo con una clave=valor por línea",
+            )],
+        }
+    }
+
+    pub fn con_clave(mut self, clave: Clave) -> Self {
+        self.claves.push(clave);
+        self
+    }
+
+    /// Agrega claves compartidas entre programas (las del cliente, por ejemplo)
+    pub fn con_claves(mut self, claves: impl IntoIterator<Item = Clave>) -> Self {
+        self.claves.extend(claves);
+        self
+    }
+
+    pub fn claves(&self) -> &[Clave] {
+        &self.claves
+    }
+
+    /// Todos los problemas de la configuración: claves desconocidas, valores
+    /// inválidos y claves requeridas que faltan
+    pub fn validar(&self, configuracion: &Configuracion) -> Vec<ErrorClave> {
+        let mut errores: Vec<ErrorClave> = Vec::new();
+
+        let mut valores: Vec<(&String, &Valor)> = configuracion.valores.iter().collect();
+        valores.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (clave, valor) in valores {
+            match self.claves.iter().find(|c| c.corresponde(clave)) {
+                Some(definicion) => errores.extend(definicion.validar(clave, &valor.texto)),
+                None => errores.push(ErrorClave::Desconocida {
+                    clave: clave.clone(),
+                    sugerencia: self.sugerencia(clave),
+                }),
+            }
+        }
+
+        for clave in self.claves.iter().filter(|c| c.requerida) {
+            let salvada: bool = clave
+                .salvo
+                .is_some_and(|otra| configuracion.valores.contains_key(otra));
+
+            if !salvada && !configuracion.valores.contains_key(clave.nombre) {
+                errores.push(ErrorClave::Faltante(clave.nombre));
+            }
+        }
+
+        errores
+    }
+
+    /// Completa las claves que no se configuraron con su valor por defecto
+    pub fn aplicar_defectos(&self, configuracion: &mut Configuracion) {
+        for clave in &self.claves {
+            match &clave.defecto {
+                Some(defecto) if !configuracion.valores.contains_key(clave.nombre) => {
+                    configuracion.setear_con_origen(clave.nombre, defecto, Origen::Defecto);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Lee la configuración de los parámetros (y de los archivos de `config`),
+    /// la valida y completa los valores por defecto
+    pub fn cargar(&self, parametros: &[&str]) -> Result<Configuracion, ErrorConfiguracion> {
+        self.cargar_con_entorno(parametros, &[])
+    }
+
+    /// Como `cargar`, con las variables de entorno (`TP_*`) entre los archivos
+    /// y los parámetros. Las variables de claves que no son del programa se
+    /// ignoran, porque el entorno puede ser el mismo para todos los programas
+    pub fn cargar_con_entorno(
+        &self,
+        parametros: &[&str],
+        entorno: &[(String, String)],
+    ) -> Result<Configuracion, ErrorConfiguracion> {
+        if parametros.iter().any(|p| PARAMETROS_AYUDA.contains(p)) {
+            return Err(ErrorConfiguracion::Ayuda(self.ayuda()));
+        }
+
+        let mostrar: bool = parametros.contains(&PARAMETRO_CONFIGURACION);
+        let parametros: Vec<&str> = parametros
+            .iter()
+            .copied()
+            .filter(|parametro| *parametro != PARAMETRO_CONFIGURACION)
+            .collect();
+
+        let mut errores: Vec<ErrorClave> = parametros
+            .iter()
+            .filter(|parametro| !parametro.contains('='))
+            .map(|parametro| ErrorClave::SinValor(parametro.to_string()))
+            .collect();
+
+        let entorno: Vec<(String, String)> = entorno
+            .iter()
+            .filter(|(variable, _)| {
+                clave_entorno(variable)
+                    .is_some_and(|clave| self.claves.iter().any(|c| c.corresponde(&clave)))
+            })
+            .cloned()
+            .collect();
+
+        let mut configuracion: Configuracion = Configuracion::desde_capas(&parametros, &entorno)
+            .map_err(ErrorConfiguracion::Lectura)?;
+
+        errores.extend(self.validar(&configuracion));
+        if !errores.is_empty() {
+            return Err(ErrorConfiguracion::Claves(errores));
+        }
+
+        self.aplicar_defectos(&mut configuracion);
+
+        if mostrar {
+            return Err(ErrorConfiguracion::Efectiva(configuracion.efectiva()));
+        }
+        Ok(configuracion)
+    }
+
+    /// Como `cargar`, con los argumentos de la línea de comandos y las
+    /// variables de entorno
+    pub fn desde_argv(&self) -> Result<Configuracion, ErrorConfiguracion> {
+        let args: Vec<String> = args().collect();
+        let parametros: Vec<&str> = args.iter().skip(1).map(|s| s.as_str()).collect();
+        let entorno: Vec<(String, String)> = vars().collect();
+
+        self.cargar_con_entorno(&parametros, &entorno)
+    }
+
+    /// Como `desde_argv`, pero si se pidió la ayuda la muestra y termina el
+    /// programa, y si la configuración es inválida muestra todos los errores
+    /// y termina con código 2
+    pub fn desde_argv_o_terminar(&self) -> Configuracion {
+        match self.desde_argv() {
+            Ok(configuracion) => configuracion,
+            Err(ErrorConfiguracion::Ayuda(texto) | ErrorConfiguracion::Efectiva(texto)) => {
+                print!("{}", texto);
+                exit(0);
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                eprintln!("Use --help para ver las claves de {}", self.programa);
+                exit(2);
+            }
+        }
+    }
+
+    /// El texto de `--help`: todas las claves con su tipo, su valor por
+    /// defecto y su descripción
+    pub fn ayuda(&self) -> String {
+        let usos: Vec<String> = self.claves.iter().map(Clave::uso).collect();
+        let ancho: usize = usos
+            .iter()
+            .map(|uso| uso.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut ayuda: String = format!(
+            "{}\n\nUso: {} [clave=valor ...] [{}]\n\nCada clave también se puede configurar con la variable de entorno {}<CLAVE>.\n\nClaves:\n",
+            self.descripcion, self.programa, PARAMETRO_CONFIGURACION, PREFIJO_ENTORNO
+        );
+
+        for (clave, uso) in self.claves.iter().zip(usos) {
+            let relleno: usize = ancho - uso.chars().count();
+            ayuda.push_str(&format!(
+                "  {}{}  {}",
+                uso,
+                " ".repeat(relleno),
+                clave.descripcion
+            ));
+
+            if let Some(defecto) = &clave.defecto {
+                ayuda.push_str(&format!(" (por defecto: {})", defecto));
+            }
+            if let Some(otra) = clave.salvo {
+                ayuda.push_str(&format!(" (requerida sin {})", otra));
+            } else if clave.requerida {
+                ayuda.push_str(" (requerida)");
+            }
+            ayuda.push('\n');
+        }
+
+        ayuda
+    }
+
+    /// La clave del esquema más parecida a una desconocida, si difiere en a
+    /// lo sumo dos letras
+    fn sugerencia(&self, clave: &str) -> Option<&'static str> {
+        self.claves
+            .iter()
+            .filter(|c| !c.prefijo)
+            .map(|c| (distancia(c.nombre, clave), c.nombre))
+            .filter(|(distancia, _)| *distancia <= 2)
+            .min()
+            .map(|(_, nombre)| nombre)
+    }
+}
+
+/// Distancia de edición (Levenshtein) entre dos textos
+fn distancia(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut anterior: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, caracter_a) in a.chars().enumerate() {
+        let mut actual: Vec<usize> = vec![i + 1];
+        for (j, caracter_b) in b.iter().enumerate() {
+            let sustitucion: usize = anterior[j] + usize::from(caracter_a != *caracter_b);
+            actual.push(sustitucion.min(anterior[j + 1] + 1).min(actual[j] + 1));
+        }
+        anterior = actual;
+    }
+
+    anterior[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clave, ErrorClave, ErrorConfiguracion, Esquema, TipoValor};
+
+    fn esquema() -> Esquema {
+        Esquema::new("prueba", "Programa de prueba")
+            .con_clave(
+                Clave::new("puerto", TipoValor::Natural, "Puerto")
+                    .con_defecto(4222)
+                    .con_rango(0., 65535.),
+            )
+            .con_clave(Clave::new("id", TipoValor::Natural, "Id").requerida_salvo("archivo"))
+            .con_clave(Clave::new("archivo", TipoValor::Texto, "Archivo"))
+            .con_clave(Clave::new(
+                "politica",
+                TipoValor::Opciones(&["demorar", "error"]),
+                "Política",
+            ))
+            .con_clave(Clave::prefijo("mapeo.", TipoValor::Texto, "Mapeos"))
+    }
+
+    #[test]
+    fn probar_errores_agrupados() {
+        let errores = match esquema().cargar(&["purto=1", "puerto=70000", "politica=x", "suelto"]) {
+            Err(ErrorConfiguracion::Claves(errores)) => errores,
+            otro => panic!("{:?}", otro),
+        };
+
+        assert_eq!(
+            errores,
+            vec![
+                ErrorClave::SinValor("suelto".to_string()),
+                ErrorClave::Invalida {
+                    clave: "politica".to_string(),
+                    valor: "x".to_string(),
+                    esperado: "demorar|error".to_string(),
+                },
+                ErrorClave::Invalida {
+                    clave: "puerto".to_string(),
+                    valor: "70000".to_string(),
+                    esperado: "natural entre 0 y 65535".to_string(),
+                },
+                ErrorClave::Desconocida {
+                    clave: "purto".to_string(),
+                    sugerencia: Some("puerto"),
+                },
+                ErrorClave::Faltante("id"),
+            ]
+        );
+    }
+
+    #[test]
+    fn probar_defectos_y_prefijos() {
+        let configuracion = esquema().cargar(&["id=3", "mapeo.a=b"]).unwrap();
+        assert_eq!(configuracion.obtener::<u16>("puerto"), Some(4222));
+        assert_eq!(
+            configuracion.obtener::<String>("mapeo.a"),
+            Some("b".to_string())
+        );
+
+        assert!(esquema().cargar(&["archivo=drones.csv"]).is_ok());
+        assert!(matches!(
+            esquema().cargar(&["mapeo.=b", "id=1"]),
+            Err(ErrorConfiguracion::Claves(_))
+        ));
+    }
+
+    #[test]
+    fn probar_ayuda() {
+        let ayuda = match esquema().cargar(&["puerto=x", "--help"]) {
+            Err(ErrorConfiguracion::Ayuda(ayuda)) => ayuda,
+            otro => panic!("{:?}", otro),
+        };
+
+        assert!(ayuda.starts_with("Programa de prueba\n\nUso: prueba"));
+        assert!(ayuda.contains("  puerto=<natural entre 0 y 65535>  Puerto (por defecto: 4222)\n"));
+        assert!(ayuda.contains("Id (requerida sin archivo)"));
+        assert!(ayuda.contains("mapeo.<...>=<texto>"));
+    }
+
+    #[test]
+    fn probar_entorno_de_otros_programas() {
+        let entorno: Vec<(String, String)> =
+            [("TP_ID", "1"), ("TP_OTRA", "x"), ("TP_MAPEO__Dron", "y")]
+                .iter()
+                .map(|(variable, valor)| (variable.to_string(), valor.to_string()))
+                .collect();
+
+        let configuracion = esquema().cargar_con_entorno(&[], &entorno).unwrap();
+        assert_eq!(configuracion.obtener::<u64>("id"), Some(1));
+        assert_eq!(
+            configuracion.obtener::<String>("mapeo.Dron"),
+            Some("y".to_string())
+        );
+        assert_eq!(configuracion.obtener::<String>("otra"), None);
+
+        // Como parámetro sigue siendo un error
+        assert!(esquema().cargar_con_entorno(&["otra=x"], &entorno).is_err());
+    }
+}
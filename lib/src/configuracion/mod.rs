@@ -1,14 +1,27 @@
+pub mod capas;
+pub mod esquema;
+
 use std::{
     collections::HashMap,
-    env::args,
-    fs, io,
+    env::{args, vars},
+    io,
+    path::Path,
     str::{FromStr, Lines},
 };
 
+use capas::Origen;
+
 /// Conjunto de clave-valor que forman la configuración
 #[derive(Debug, Clone)]
 pub struct Configuracion {
-    valores: HashMap<String, String>,
+    valores: HashMap<String, Valor>,
+}
+
+/// Un valor de la configuración y de dónde salió
+#[derive(Debug, Clone, PartialEq)]
+struct Valor {
+    texto: String,
+    origen: Origen,
 }
 
 impl Default for Configuracion {
@@ -40,22 +53,57 @@ impl Configuracion {
         self.valores.len()
     }
 
-    /// Lee un archivo de configuración y lo convierte en un struct Configuracion
+    /// Lee un archivo de configuración y lo convierte en un struct Configuracion.
+    /// Las líneas `include=otro.txt` agregan los valores de otro archivo (con
+    /// la ruta relativa a este) y `${VARIABLE}` se reemplaza por el valor de la
+    /// variable de entorno
     pub fn leer(ruta: &str) -> io::Result<Self> {
-        // Leer archivo de configuración
-        let contenido: String = fs::read_to_string(ruta)?;
-
-        Ok(Self::parsear(&contenido))
+        capas::leer_con_inclusiones(Path::new(ruta), &mut Vec::new())
     }
 
     /// Obtiene un valor de configuración
     pub fn obtener<T: FromStr>(&self, clave: &str) -> Option<T> {
-        self.valores.get(clave).and_then(|v| v.parse().ok())
+        self.valores.get(clave).and_then(|v| v.texto.parse().ok())
     }
 
-    /// Setea un valor de configuración
+    /// Setea un valor de configuración. Como lo setea el programa, ningún otro
+    /// origen lo reemplaza al fusionar
     pub fn setear<T: ToString>(&mut self, clave: &str, valor: T) {
-        self.valores.insert(clave.to_string(), valor.to_string());
+        self.setear_con_origen(clave, valor, Origen::Programa);
+    }
+
+    pub fn setear_con_origen<T: ToString>(&mut self, clave: &str, valor: T, origen: Origen) {
+        let valor = Valor {
+            texto: valor.to_string(),
+            origen,
+        };
+        self.valores.insert(clave.to_string(), valor);
+    }
+
+    /// De dónde salió el valor de la clave
+    pub fn origen(&self, clave: &str) -> Option<&Origen> {
+        self.valores.get(clave).map(|v| &v.origen)
+    }
+
+    /// Las claves que empiezan con el prefijo (sin el prefijo) y sus valores,
+    /// ordenadas por clave
+    pub fn con_prefijo(&self, prefijo: &str) -> Vec<(String, String)> {
+        let mut valores: Vec<(String, String)> = self
+            .valores
+            .iter()
+            .filter_map(|(clave, valor)| {
+                clave
+                    .strip_prefix(prefijo)
+                    .map(|clave| (clave.to_string(), valor.texto.clone()))
+            })
+            .collect();
+        valores.sort();
+        valores
+    }
+
+    /// Agrega los valores de otra configuración, reemplazando los que ya existen
+    pub fn extender(&mut self, otra: Configuracion) {
+        self.valores.extend(otra.valores);
     }
 
     /// Parsea un archivo de configuración en formato `clave=valor` y lo convierte en un struct Configuracion
@@ -65,22 +113,26 @@ impl Configuracion {
         let lineas: Lines<'_> = texto.lines();
 
         for linea in lineas {
-            let mut partes: std::str::Split<'_, char> = linea.split('=');
-            let clave: Option<&str> = partes.next();
-            let valor: Option<&str> = partes.next();
-
-            if let (Some(clave), Some(valor)) = (clave, valor) {
-                if clave.trim_start().starts_with('#') {
-                    continue;
-                }
-
-                config.setear(clave.trim(), Self::parsear_valor(valor));
+            if let Some((clave, valor)) = Self::parsear_linea(linea) {
+                config.setear(clave, valor);
             }
         }
 
         config
     }
 
+    /// La clave y el valor de una línea `clave=valor`, salvo que sea un
+    /// comentario (`#`). El valor puede tener `=`
+    fn parsear_linea(linea: &str) -> Option<(&str, String)> {
+        let (clave, valor) = linea.split_once('=')?;
+
+        if clave.trim_start().starts_with('#') {
+            return None;
+        }
+
+        Some((clave.trim(), Self::parsear_valor(valor)))
+    }
+
     /// Recibe un valor por ejemplo `5` o por ejemplo `"hola.txt"` y si es necesario le saca las comillas: `"5"` -> `5`
     pub fn parsear_valor(valor: &str) -> String {
         let valor_trim: &str = valor.trim();
@@ -108,12 +160,8 @@ impl Configuracion {
         let mut config: Configuracion = Configuracion::new();
 
         for parametro in parametros {
-            let mut partes: std::str::Split<'_, char> = parametro.split('=');
-            let clave: Option<&str> = partes.next();
-            let valor: Option<&str> = partes.next();
-
-            if let (Some(clave), Some(valor)) = (clave, valor) {
-                config.setear(clave, Self::parsear_valor(valor));
+            if let Some((clave, valor)) = parametro.split_once('=') {
+                config.setear_con_origen(clave, Self::parsear_valor(valor), Origen::Parametro);
             }
         }
 
@@ -121,28 +169,22 @@ impl Configuracion {
     }
 
     /// Hace lo mismo que `desde_parametros` pero si se encuentra un parametro `config`
-    /// se lee el archivo de configuración que se encuentra en ese parametro y se
-    /// mezclan los valores de ambos origenes
+    /// se leen los archivos de configuración (separados por comas) que se encuentran
+    /// en ese parametro. Los parámetros tienen prioridad sobre los archivos
     pub fn desde_parametros_y_leer(parametros: &[&str]) -> io::Result<Self> {
-        let mut config: Configuracion = Configuracion::desde_parametros(parametros);
-
-        if let Some(archivo) = config.obtener::<String>("config") {
-            let archivo_config: Configuracion = Configuracion::leer(&archivo)?;
-            config.valores.extend(archivo_config.valores);
-        }
-
-        Ok(config)
+        Configuracion::desde_capas(parametros, &[])
     }
 
     /// Lee los argumentos de la linea de comandos y los convierte en un struct Configuracion.
     ///
-    /// Funciona igual que `desde_parametros_y_leer` pero toma los argumentos de la linea de comandos
-    /// automáticamente.
+    /// Funciona igual que `desde_capas` pero toma los argumentos de la linea de comandos
+    /// y las variables de entorno automáticamente.
     pub fn desde_argv() -> io::Result<Self> {
         let args: Vec<String> = args().collect();
         let parametros: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        let entorno: Vec<(String, String)> = vars().collect();
 
-        Configuracion::desde_parametros_y_leer(&parametros[1..])
+        Configuracion::desde_capas(&parametros[1..], &entorno)
     }
 }
 
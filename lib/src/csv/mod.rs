@@ -1,12 +1,149 @@
-/// Toma una linea formateada como la de un archivo csv y la
-/// convierte en un vector de strings donde cada elemento
-/// es un parámetro
+/// Toma una linea formateada como la de un archivo csv (RFC 4180) y la
+/// convierte en un vector de strings donde cada elemento es un parámetro.
+/// Los campos entre comillas pueden tener comas, saltos de línea y comillas
+/// (escritas dos veces: `""`)
 pub fn csv_parsear_linea(linea: &str) -> Vec<String> {
-    linea.split(',').map(|s| s.to_string()).collect()
+    let mut campos: Vec<String> = Vec::new();
+    let mut campo: String = String::new();
+    let mut entre_comillas: bool = false;
+    let mut caracteres = linea.chars().peekable();
+
+    while let Some(caracter) = caracteres.next() {
+        match caracter {
+            '"' if entre_comillas => {
+                if caracteres.peek() == Some(&'"') {
+                    caracteres.next();
+                    campo.push('"');
+                } else {
+                    entre_comillas = false;
+                }
+            }
+            // Las comillas solo abren un campo al principio; en el medio de
+            // un campo sin comillas se toman como un caracter más
+            '"' if campo.is_empty() => entre_comillas = true,
+            ',' if !entre_comillas => campos.push(std::mem::take(&mut campo)),
+            caracter => campo.push(caracter),
+        }
+    }
+
+    campos.push(campo);
+    campos
 }
 
-/// Formatea la linea como a la de un archivo csv, separando
-/// cada parámetro por coma
+/// Formatea la linea como a la de un archivo csv, separando cada parámetro
+/// por coma. Los parámetros con comas, comillas o saltos de línea se
+/// escriben entre comillas
 pub fn csv_encodear_linea(linea: &[String]) -> String {
-    linea.join(",")
+    linea
+        .iter()
+        .map(|campo| csv_encodear_campo(campo))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn csv_encodear_campo(campo: &str) -> String {
+    if campo.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", campo.replace('"', "\"\""))
+    } else {
+        campo.to_string()
+    }
+}
+
+/// Separa el contenido de un archivo csv en registros, sin el fin de línea
+/// (`\n` o `\r\n`). Los saltos de línea entre comillas son parte del
+/// registro, así un campo puede tener varias líneas
+pub fn csv_separar_registros(texto: &str) -> Vec<&str> {
+    let mut registros: Vec<&str> = Vec::new();
+    let mut inicio: usize = 0;
+    let mut entre_comillas: bool = false;
+    // Si el caracter anterior cerró las comillas: otra comilla justo después
+    // es una comilla escapada (`""`) y se sigue entre comillas
+    let mut cerro_comillas: bool = false;
+    let mut anterior: Option<u8> = None;
+
+    for (i, byte) in texto.bytes().enumerate() {
+        let cerro: bool = cerro_comillas;
+        cerro_comillas = false;
+
+        match byte {
+            b'"' if entre_comillas => {
+                entre_comillas = false;
+                cerro_comillas = true;
+            }
+            // Como en `csv_parsear_linea`, las comillas solo abren un campo
+            // al principio; en el medio de un campo son un caracter más
+            b'"' if cerro || matches!(anterior, None | Some(b',') | Some(b'\n')) => {
+                entre_comillas = true;
+            }
+            b'\n' if !entre_comillas => {
+                registros.push(sin_retorno(&texto[inicio..i]));
+                inicio = i + 1;
+            }
+            _ => {}
+        }
+
+        anterior = Some(byte);
+    }
+
+    if inicio < texto.len() {
+        registros.push(sin_retorno(&texto[inicio..]));
+    }
+
+    registros
+}
+
+fn sin_retorno(registro: &str) -> &str {
+    registro.strip_suffix('\r').unwrap_or(registro)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_encodear_linea, csv_parsear_linea, csv_separar_registros};
+
+    fn campos(campos: &[&str]) -> Vec<String> {
+        campos.iter().map(|campo| campo.to_string()).collect()
+    }
+
+    #[test]
+    fn probar_campos_con_comillas() {
+        assert_eq!(
+            csv_parsear_linea("1,Robo,-34.6"),
+            campos(&["1", "Robo", "-34.6"])
+        );
+        assert_eq!(csv_parsear_linea(""), campos(&[""]));
+        assert_eq!(csv_parsear_linea("a,,"), campos(&["a", "", ""]));
+        assert_eq!(
+            csv_parsear_linea("1,\"Robo, con \"\"arma\"\"\",2"),
+            campos(&["1", "Robo, con \"arma\"", "2"])
+        );
+        assert_eq!(
+            csv_parsear_linea("\"dos\r\nlíneas\",\"\""),
+            campos(&["dos\r\nlíneas", ""])
+        );
+        // Comillas en el medio de un campo sin comillas
+        assert_eq!(csv_parsear_linea("a\"b,c"), campos(&["a\"b", "c"]));
+
+        for linea in [
+            campos(&["1", "Choque, 2 autos", "3"]),
+            campos(&["\"", "\"\"", ",", "\n", "a\r\nb", "", "ñandú"]),
+        ] {
+            assert_eq!(csv_parsear_linea(&csv_encodear_linea(&linea)), linea);
+        }
+        assert_eq!(csv_encodear_linea(&campos(&["1", "a b", "2"])), "1,a b,2");
+    }
+
+    #[test]
+    fn probar_registros() {
+        let texto = "1,a\r\n2,\"b\nc\"\n\n3,\"d\"\"\r\ne\"\r\n4,f";
+        assert_eq!(
+            csv_separar_registros(texto),
+            vec!["1,a", "2,\"b\nc\"", "", "3,\"d\"\"\r\ne\"", "4,f"]
+        );
+        assert_eq!(
+            csv_separar_registros("1,5\" de lluvia,x\n2,y"),
+            vec!["1,5\" de lluvia,x", "2,y"]
+        );
+        assert!(csv_separar_registros("").is_empty());
+        assert_eq!(csv_separar_registros("1\n"), vec!["1"]);
+    }
 }
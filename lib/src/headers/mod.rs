@@ -0,0 +1,150 @@
+use crate::serializables::{error::DeserializationError, Serializable};
+
+/// Primera linea de todo bloque de headers (`HPUB` / `HMSG`)
+pub const VERSION_HEADERS: &str = "NATS/1.0";
+
+/// Header con el id de un mensaje. El servidor descarta las publicaciones que
+/// repiten un id dentro de la ventana de deduplicación de su tópico
+pub const HEADER_ID_MENSAJE: &str = "Nats-Msg-Id";
+
+/// Header con el tópico al que el servidor envía la traza de una publicación:
+/// por dónde pasó y a qué suscripciones se entregó
+pub const HEADER_DESTINO_TRAZA: &str = "Nats-Trace-Dest";
+
+/// Conjunto de headers de un mensaje, con el formato que usa NATS:
+///
+/// ```text
+/// NATS/1.0\r\n
+/// Clave1: Valor1\r\n
+/// Clave2: Valor2\r\n
+/// \r\n
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headers {
+    valores: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self {
+            valores: Vec::new(),
+        }
+    }
+
+    /// Inserta un header. Si ya existía un header con esa clave, se reemplaza su valor
+    pub fn insertar(&mut self, clave: &str, valor: &str) {
+        if let Some((_, v)) = self
+            .valores
+            .iter_mut()
+            .find(|(c, _)| c.eq_ignore_ascii_case(clave))
+        {
+            *v = valor.to_string();
+            return;
+        }
+
+        self.valores.push((clave.to_string(), valor.to_string()));
+    }
+
+    /// Obtiene el valor de un header (sin distinguir mayúsculas y minúsculas en la clave)
+    pub fn obtener(&self, clave: &str) -> Option<&str> {
+        self.valores
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(clave))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Recorre los headers en el orden en que se insertaron
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.valores.iter().map(|(c, v)| (c.as_str(), v.as_str()))
+    }
+
+    pub fn es_vacio(&self) -> bool {
+        self.valores.is_empty()
+    }
+}
+
+impl Serializable for Headers {
+    fn serializar(&self) -> Vec<u8> {
+        let mut texto: String = format!("{}\r\n", VERSION_HEADERS);
+
+        for (clave, valor) in &self.valores {
+            texto.push_str(&format!("{}: {}\r\n", clave, valor));
+        }
+
+        texto.push_str("\r\n");
+        texto.into_bytes()
+    }
+
+    /// Toma el bloque de headers de un mensaje y lo convierte en un struct Headers.
+    /// La primera linea tiene que ser la versión (`NATS/1.0`, opcionalmente seguida de un estado)
+    fn deserializar(datos: &[u8]) -> Result<Self, DeserializationError>
+    where
+        Self: Sized,
+    {
+        let texto: &str =
+            std::str::from_utf8(datos).map_err(|_| DeserializationError::InvalidData)?;
+        let mut lineas = texto.split("\r\n");
+
+        match lineas.next() {
+            Some(version) if version.starts_with(VERSION_HEADERS) => {}
+            _ => return Err(DeserializationError::InvalidData),
+        }
+
+        let mut headers: Headers = Headers::new();
+
+        for linea in lineas {
+            if linea.is_empty() {
+                continue;
+            }
+
+            let (clave, valor) = linea
+                .split_once(':')
+                .ok_or(DeserializationError::InvalidData)?;
+
+            headers
+                .valores
+                .push((clave.trim().to_string(), valor.trim().to_string()));
+        }
+
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializar_y_deserializar() {
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Service-Error", "Comando inválido");
+        headers.insertar("Nats-Service-Error-Code", "400");
+
+        let bytes = headers.serializar();
+        assert_eq!(
+            String::from_utf8_lossy(&bytes),
+            "NATS/1.0\r\nNats-Service-Error: Comando inválido\r\nNats-Service-Error-Code: 400\r\n\r\n"
+        );
+
+        let deserializado = Headers::deserializar(&bytes).unwrap();
+        assert_eq!(deserializado, headers);
+        assert_eq!(
+            deserializado.obtener("nats-service-error-code"),
+            Some("400")
+        );
+    }
+
+    #[test]
+    fn insertar_reemplaza() {
+        let mut headers = Headers::new();
+        headers.insertar("a", "1");
+        headers.insertar("A", "2");
+
+        assert_eq!(headers.obtener("a"), Some("2"));
+    }
+
+    #[test]
+    fn sin_version() {
+        assert!(Headers::deserializar(b"a: 1\r\n\r\n").is_err());
+    }
+}
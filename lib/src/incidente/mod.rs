@@ -92,3 +92,25 @@ impl Serializable for Incidente {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::serializables::{deserializar_vec, serializar_vec, Serializable};
+
+    use super::Incidente;
+
+    #[test]
+    fn probar_detalle_con_comas_y_saltos_de_linea() {
+        let incidentes = vec![
+            Incidente::new(1, "Robo, con \"arma\"".to_string(), -34.6, -58.4, 10),
+            Incidente::new(2, "Choque\r\nen la esquina".to_string(), -34.5, -58.3, 20),
+            Incidente::new(3, "Incendio".to_string(), -34.4, -58.2, 30),
+        ];
+
+        let incidente = Incidente::deserializar(&incidentes[0].serializar()).unwrap();
+        assert_eq!(incidente, incidentes[0]);
+
+        let datos = serializar_vec(&incidentes);
+        assert_eq!(deserializar_vec::<Incidente>(&datos).unwrap(), incidentes);
+    }
+}
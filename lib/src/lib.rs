@@ -2,7 +2,10 @@ pub mod camara;
 pub mod configuracion;
 pub mod coordenadas;
 pub mod csv;
+pub mod headers;
 pub mod incidente;
 pub mod parseador;
 pub mod serializables;
 pub mod stream;
+pub mod topicos;
+pub mod websocket;
@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+/// Errores del protocolo que el servidor le informa al cliente con `-ERR`.
+/// Se muestran con el mismo texto que usa NATS, así cualquier cliente los reconoce
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorProtocolo {
+    /// No se reconoce el comando o sus argumentos no son válidos
+    OperacionDesconocida,
+    /// Credenciales incorrectas, o un comando antes del CONNECT
+    ViolacionDeAutorizacion,
+    /// El patrón de un SUB no es válido
+    TopicoInvalido,
+    /// El tópico de un PUB no es válido (solo en modo pedante)
+    TopicoDePublicacionInvalido,
+    /// La línea de un comando es más larga que `max_control_line`
+    ControlLineExcedida,
+    /// El payload de un PUB o HPUB es más grande que `max_payload`
+    PayloadExcedido,
+    /// Se alcanzó `max_conexiones` en el servidor
+    MaximoConexiones,
+    /// La conexión ya tiene `max_suscripciones` suscripciones
+    MaximoSuscripciones,
+    /// La conexión acumuló más de `max_pending` bytes sin leer
+    ConsumidorLento,
+    /// El cliente no respondió `max_pings_out` PINGs seguidos
+    ConexionInactiva,
+    /// El usuario no tiene permiso para suscribirse al tópico
+    PermisoDeSuscripcion(String),
+    /// El usuario no tiene permiso para publicar en el tópico
+    PermisoDePublicacion(String),
+    /// La publicación supera los mensajes o bytes por segundo permitidos
+    LimiteDeTasa,
+}
+
+impl ErrorProtocolo {
+    pub fn mensaje(&self) -> String {
+        let mensaje: &str = match self {
+            ErrorProtocolo::OperacionDesconocida => "'Unknown Protocol Operation'",
+            ErrorProtocolo::ViolacionDeAutorizacion => "'Authorization Violation'",
+            ErrorProtocolo::TopicoInvalido => "'Invalid Subject'",
+            ErrorProtocolo::TopicoDePublicacionInvalido => "'Invalid Publish Subject'",
+            ErrorProtocolo::ControlLineExcedida => "'Maximum Control Line Exceeded'",
+            ErrorProtocolo::PayloadExcedido => "'Maximum Payload Violation'",
+            ErrorProtocolo::MaximoConexiones => "'Maximum Connections Exceeded'",
+            ErrorProtocolo::MaximoSuscripciones => "'Maximum Subscriptions Exceeded'",
+            ErrorProtocolo::ConsumidorLento => "'Slow Consumer'",
+            ErrorProtocolo::ConexionInactiva => "'Stale Connection'",
+            ErrorProtocolo::LimiteDeTasa => "'Rate Limit Exceeded'",
+            ErrorProtocolo::PermisoDeSuscripcion(topico) => {
+                return format!("'Permissions Violation for Subscription to {}'", topico);
+            }
+            ErrorProtocolo::PermisoDePublicacion(topico) => {
+                return format!("'Permissions Violation for Publish to {}'", topico);
+            }
+        };
+
+        mensaje.to_string()
+    }
+
+    /// Si después de este error el servidor cierra la conexión
+    pub fn es_fatal(&self) -> bool {
+        !matches!(
+            self,
+            ErrorProtocolo::MaximoSuscripciones
+                | ErrorProtocolo::PermisoDeSuscripcion(_)
+                | ErrorProtocolo::PermisoDePublicacion(_)
+                | ErrorProtocolo::TopicoInvalido
+                | ErrorProtocolo::TopicoDePublicacionInvalido
+                | ErrorProtocolo::LimiteDeTasa
+        )
+    }
+}
+
+impl Display for ErrorProtocolo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mensaje())
+    }
+}
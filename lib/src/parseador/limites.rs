@@ -0,0 +1,34 @@
+/// Tamaño máximo del payload (headers incluidos) que se acepta por defecto
+pub const MAX_PAYLOAD_POR_DEFECTO: usize = 1024 * 1024;
+/// Largo máximo de la línea de un comando que se acepta por defecto
+pub const MAX_CONTROL_LINE_POR_DEFECTO: usize = 4096;
+
+/// Límites que aplica el parser a los bytes que recibe, para no acumular
+/// bytes sin límite cuando el otro extremo manda algo como `PUB x 99999999999`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limites {
+    /// Tamaño máximo de los headers y el payload de un PUB, HPUB, MSG o HMSG
+    pub max_payload: usize,
+    /// Largo máximo de la línea de un comando (sin contar el payload)
+    pub max_control_line: usize,
+}
+
+impl Limites {
+    pub fn new(max_payload: usize, max_control_line: usize) -> Self {
+        Self {
+            max_payload,
+            max_control_line,
+        }
+    }
+
+    /// Sin límites, se acepta cualquier tamaño
+    pub fn sin_limites() -> Self {
+        Self::new(usize::MAX, usize::MAX)
+    }
+}
+
+impl Default for Limites {
+    fn default() -> Self {
+        Self::new(MAX_PAYLOAD_POR_DEFECTO, MAX_CONTROL_LINE_POR_DEFECTO)
+    }
+}
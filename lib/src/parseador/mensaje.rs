@@ -1,17 +1,23 @@
-use super::{parametros_conectar::ParametrosConectar, parametros_info::ParametrosInfo};
+use bytes::Bytes;
+
+use super::{
+    error_protocolo::ErrorProtocolo, parametros_conectar::ParametrosConectar,
+    parametros_info::ParametrosInfo,
+};
 
 #[derive(Debug)]
 pub enum Mensaje {
     // 'topico', 'replay_to' payload
-    Publicar(String, Option<String>, Vec<u8>),
+    Publicar(String, Option<String>, Bytes),
     // 'topico', 'replay_to' headers, payload
-    PublicarConHeader(String, Option<String>, Vec<u8>, Vec<u8>),
+    PublicarConHeader(String, Option<String>, Bytes, Bytes),
     // 'topico', 'queue group', 'id
     Suscribir(String, Option<String>, String),
     // 'id_suscripcion', 'maximos_mensajes'
     Desuscribir(String, Option<u64>),
-    // Mensaje de error (Cuando no se pudo parsear el mensaje)
-    Error(String),
+    // Error después del cual no se puede seguir parseando (Ejemplo: no se
+    // reconoce el comando o se superó un límite)
+    ErrorFatal(ErrorProtocolo),
     // Mensaje para generar la conexión
     Conectar(ParametrosConectar),
     // Mensaje para preservar la conexión
@@ -21,9 +27,13 @@ pub enum Mensaje {
     //
     Info(ParametrosInfo),
     // MSG <subject> <sid> [reply-to] payload
-    Publicacion(String, String, Option<String>, Vec<u8>),
+    Publicacion(String, String, Option<String>, Bytes),
     // HMSG <subject> <sid> [reply-to] headers payload
-    PublicacionConHeader(String, String, Option<String>, Vec<u8>, Vec<u8>),
+    PublicacionConHeader(String, String, Option<String>, Bytes, Bytes),
+    // +OK (solo con `Parseador::con_respuestas`)
+    RespuestaOk(),
+    // -ERR <error> (solo con `Parseador::con_respuestas`)
+    RespuestaErr(String),
 }
 
 pub fn formatear_payload_debug(payload: &[u8]) -> String {
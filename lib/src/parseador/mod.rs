@@ -1,23 +1,38 @@
+pub mod error_protocolo;
+pub mod limites;
 pub mod mensaje;
 pub mod parametros_conectar;
 pub mod parametros_info;
 
 mod resultado_linea;
 
+use std::io::{self, Read};
+
+use bytes::{Buf, Bytes, BytesMut};
+
 use self::{
-    mensaje::Mensaje, parametros_conectar::ParametrosConectar, parametros_info::ParametrosInfo,
+    error_protocolo::ErrorProtocolo, limites::Limites, mensaje::Mensaje,
+    parametros_conectar::ParametrosConectar, parametros_info::ParametrosInfo,
     resultado_linea::ResultadoLinea,
 };
 
+/// Cantidad de bytes que se intentan leer del stream en cada llamada a `leer_de`
+pub const TAMANIO_LECTURA: usize = 16 * 1024;
+
 pub struct Parseador {
-    // Bytes que fue acumulando que todavía no se pudieron convertir en ninguna estructura
-    bytes_pendientes: Vec<u8>,
-    /// Se utiliza internamente para llevar el estado del parseo en diferentes casos
-    continuar_en_indice: usize,
+    /// Bytes recibidos que todavía no se convirtieron en mensajes. Lo que ya se
+    /// parseó se corta del frente sin copiar y ese espacio se reutiliza cuando
+    /// el buffer tiene que crecer
+    buffer: BytesMut,
+    /// Hasta dónde ya se buscó el fin de la línea actual, para no volver a
+    /// recorrer los mismos bytes cuando la línea llega partida
+    escaneado: usize,
     /// La primera linea del mensaje que se está parseando (ejemplo: se encontró un PUB y falta leer el payload)
     actual: Option<ResultadoLinea>,
-    /// Los headers del mensaje que se está parseando ahora
-    header: Option<Vec<u8>>,
+    /// Tamaños máximos de las líneas y los payloads que se aceptan
+    limites: Limites,
+    /// Si se devuelven los `+OK` y `-ERR` como mensajes en lugar de ignorarlos
+    respuestas: bool,
 }
 
 /// La responsabilidad del parser es recibir bytes de la conexión y tranformarlos a mensajes
@@ -28,8 +43,8 @@ pub struct Parseador {
 /// El proceso de utilizar los bytes que fue recibiendo y convertirlos a mensajes se llama
 /// desde la función `proximo_mensaje`, esta te entrega mensajes uno por uno
 ///
-/// El parser se encarga de ir liberando los bytes que ya utilizó y de tener en cuenta los estados intermedios
-/// (es decir, si le llega un mensaje que no está completo, lo guarda y espera a que lleguen más bytes)
+/// Los headers y payloads de los mensajes son porciones (`Bytes`) del mismo buffer
+/// en el que se recibieron, así que no se copian al parsearlos ni al reenviarlos
 impl Default for Parseador {
     fn default() -> Self {
         Self::new()
@@ -38,193 +53,133 @@ impl Default for Parseador {
 
 impl Parseador {
     pub fn new() -> Self {
+        Self::con_limites(Limites::sin_limites())
+    }
+
+    /// Crea un parser que devuelve `Mensaje::ErrorFatal` cuando se supera alguno de los límites
+    pub fn con_limites(limites: Limites) -> Self {
         Self {
-            bytes_pendientes: Vec::new(),
-            continuar_en_indice: 0,
+            buffer: BytesMut::new(),
+            escaneado: 0,
             actual: None,
-            header: None,
+            limites,
+            respuestas: false,
         }
     }
 
-    /// Agrega bytes al parser
-    pub fn agregar_bytes(&mut self, bytes: &[u8]) {
-        self.bytes_pendientes.extend_from_slice(bytes);
+    /// Devuelve también los `+OK` y `-ERR` del servidor (`Mensaje::RespuestaOk`
+    /// y `Mensaje::RespuestaErr`), para quien necesita saber a qué comando
+    /// responde cada uno
+    pub fn con_respuestas(mut self) -> Self {
+        self.respuestas = true;
+        self
     }
 
-    /// Devuelve la próxima línea que se encuentra en los bytes que se le pasaron
-    /// O `None` si no se encontró ninguna línea (porque no se recibieron suficientes bytes)
-    pub fn proxima_linea(&mut self) -> Option<String> {
-        let mut last_char_cr = false;
-
-        // Pasar por todos los bytes que tenemos que todavía no procesamos
-        // Estamos buscando un salto de linea, este se marca como \r\n
-        for i in self.continuar_en_indice..self.bytes_pendientes.len() {
-            // Si encontramos un \r, marcamos que el último caracter fue un \r
-            if self.bytes_pendientes[i] == b'\r' {
-                // La b es para que lo tome como binario
-                last_char_cr = true;
-            // Si encontramos un \n y el último caracter fue un \r, encontramos un mensaje (o al menos la primera linea)
-            } else if last_char_cr && self.bytes_pendientes[i] == b'\n' {
-                self.continuar_en_indice = i + 1;
-
-                let result: String =
-                    String::from_utf8_lossy(&self.bytes_pendientes[..self.continuar_en_indice])
-                        .trim_end() // Elimina los espacios vacios al final
-                        .to_string();
-
-                self.resetear_bytes();
-                return Some(result);
-            } else {
-                last_char_cr = false;
-            }
-        }
-
-        None
+    /// Cambia los límites para los próximos mensajes
+    pub fn establecer_limites(&mut self, limites: Limites) {
+        self.limites = limites;
     }
 
-    pub fn proximo_mensaje(&mut self) -> Option<Mensaje> {
-        // Si actualmente se está parseando un PUB buscamos el payload
-        if let Some(ResultadoLinea::Pub(topic, reply_to, bytes_totales)) = &self.actual {
-            // No hay suficientes bytes para el payload
-            if self.bytes_pendientes.len() < *bytes_totales {
-                return None;
-            }
-
-            self.continuar_en_indice = *bytes_totales;
-
-            // Si hay suficientes bytes para el payload, devolvemos el mensaje
-            let resultado: Option<Mensaje> = Some(Mensaje::Publicar(
-                topic.to_string(),
-                reply_to.clone(),
-                self.bytes_pendientes[..*bytes_totales].to_vec(),
-            ));
-
-            self.resetear_todo();
-
-            return resultado;
-        }
-
-        // Si actualmente se está parseando un HPUB buscamos el payload
-        if let Some(ResultadoLinea::Hpub(topic, reply_to, headers_bytes, bytes_totales)) =
-            &self.actual
-        {
-            let bytes_totales_con_salto_de_linea: usize = *bytes_totales + 2;
-
-            // Si ya habíamos encontrado los headers antes,
-            // tenemos todo para buscar el payload y si está completo devolver el mensaje
-            if let Some(headers) = &self.header {
-                // No hay suficientes bytes para el payload
-                if self.bytes_pendientes.len() < bytes_totales_con_salto_de_linea {
-                    return None;
-                }
+    /// Agrega bytes al parser
+    pub fn agregar_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
 
-                self.continuar_en_indice = bytes_totales_con_salto_de_linea;
+    /// Lee del stream directamente al buffer del parser, sin buffers intermedios.
+    /// Devuelve lo mismo que `Read::read`
+    pub fn leer_de<R: Read + ?Sized>(&mut self, lector: &mut R) -> io::Result<usize> {
+        // Se lee al final del buffer, llenado con ceros porque `Read` puede
+        // leer lo que se le pasa. Después se descarta lo que no se escribió
+        let largo: usize = self.buffer.len();
+        self.buffer.resize(largo + TAMANIO_LECTURA, 0);
 
-                // Si hay suficientes bytes para el payload, devolvemos el mensaje
-                let resultado: Option<Mensaje> = Some(Mensaje::PublicarConHeader(
-                    topic.to_string(),
-                    reply_to.clone(),
-                    headers.clone(),
-                    self.bytes_pendientes[..*bytes_totales].to_vec(),
-                ));
+        let resultado: io::Result<usize> = lector.read(&mut self.buffer[largo..]);
+        let leidos: usize = *resultado.as_ref().unwrap_or(&0);
+        self.buffer.truncate(largo + leidos.min(TAMANIO_LECTURA));
 
-                self.resetear_todo();
+        resultado
+    }
 
-                return resultado;
-            } else {
-                // Si no encontramos los headers antes, buscamos los headers
-                if self.bytes_pendientes.len() < *headers_bytes {
+    /// Devuelve la próxima línea (sin el `\r\n`) que se encuentra en los bytes que se le pasaron
+    /// O `None` si no se encontró ninguna línea (porque no se recibieron suficientes bytes)
+    fn proxima_linea(&mut self) -> Option<Bytes> {
+        loop {
+            let posicion: usize = match self.buffer[self.escaneado..]
+                .iter()
+                .position(|b| *b == b'\n')
+            {
+                Some(posicion) => self.escaneado + posicion,
+                None => {
+                    self.escaneado = self.buffer.len();
                     return None;
                 }
+            };
 
-                self.header = Some(self.bytes_pendientes[..*headers_bytes].to_vec());
-                self.continuar_en_indice = *headers_bytes;
-                return self.proximo_mensaje();
-            }
-        }
-
-        // Si actualmente se está parseando un MSG buscamos el payload
-        if let Some(ResultadoLinea::Msg(topic, id_suscripcion, reply_to, bytes_totales)) =
-            &self.actual
-        {
-            // No hay suficientes bytes para el payload
-            if self.bytes_pendientes.len() < *bytes_totales {
-                return None;
+            // Solo es un salto de línea si el \n viene después de un \r
+            if posicion > 0 && self.buffer[posicion - 1] == b'\r' {
+                let mut linea: Bytes = self.buffer.split_to(posicion + 1).freeze();
+                linea.truncate(posicion - 1);
+                self.escaneado = 0;
+                return Some(linea);
             }
 
-            self.continuar_en_indice = *bytes_totales;
-
-            // Si hay suficientes bytes para el payload, devolvemos el mensaje
-            let resultado: Option<Mensaje> = Some(Mensaje::Publicacion(
-                topic.to_string(),
-                id_suscripcion.to_string(),
-                reply_to.clone(),
-                self.bytes_pendientes[..*bytes_totales].to_vec(),
-            ));
-
-            self.resetear_todo();
-
-            return resultado;
+            self.escaneado = posicion + 1;
         }
+    }
 
-        // Si actualmente se está parseando un HMSG buscamos el payload
-        if let Some(ResultadoLinea::Hmsg(topic, sid, reply_to, headers_bytes, bytes_totales)) =
-            &self.actual
-        {
-            let bytes_totales_con_salto_de_linea = *bytes_totales + 2;
-
-            // Si ya habíamos encontrado los headers antes,
-            // tenemos todo para buscar el payload y si está completo devolver el mensaje
-            if let Some(headers) = &self.header {
-                // No hay suficientes bytes para el payload
-                if self.bytes_pendientes.len() < bytes_totales_con_salto_de_linea {
-                    return None;
+    pub fn proximo_mensaje(&mut self) -> Option<Mensaje> {
+        loop {
+            // Si actualmente se está parseando un mensaje con payload, lo buscamos
+            if let Some(actual) = self.actual.take() {
+                // El payload tiene que terminar con un salto de línea, si no
+                // el largo que se indicó está mal
+                let fin: usize = actual.bytes_totales();
+                if self.buffer.len() >= fin + 2 && !self.buffer[fin..].starts_with(b"\r\n") {
+                    return Some(self.error_fatal(ErrorProtocolo::OperacionDesconocida));
                 }
 
-                self.continuar_en_indice = bytes_totales_con_salto_de_linea;
-
-                // Si hay suficientes bytes para el payload, devolvemos el mensaje
-                let resultado: Option<Mensaje> = Some(Mensaje::PublicacionConHeader(
-                    topic.to_string(),
-                    sid.to_string(),
-                    reply_to.clone(),
-                    headers.clone(),
-                    self.bytes_pendientes[..*bytes_totales].to_vec(),
-                ));
-
-                self.resetear_todo();
+                return match self.completar_mensaje(actual) {
+                    Ok(mensaje) => Some(mensaje),
+                    Err(actual) => {
+                        // Todavía no llegaron suficientes bytes
+                        self.actual = Some(actual);
+                        None
+                    }
+                };
+            }
 
-                return resultado;
-            } else {
-                // Si no encontramos los headers antes, buscamos los headers
-                if self.bytes_pendientes.len() < *headers_bytes {
-                    return None;
+            let linea: Bytes = match self.proxima_linea() {
+                Some(linea) => linea,
+                // Si la línea todavía no termina pero ya es demasiado larga, no se sigue esperando
+                None if self.escaneado > self.limites.max_control_line => {
+                    return Some(self.error_fatal(ErrorProtocolo::ControlLineExcedida));
                 }
+                None => return None,
+            };
 
-                self.header = Some(self.bytes_pendientes[..*headers_bytes].to_vec());
-                self.continuar_en_indice = *headers_bytes;
-                return self.proximo_mensaje();
+            if linea.len() > self.limites.max_control_line {
+                return Some(self.error_fatal(ErrorProtocolo::ControlLineExcedida));
             }
-        }
-
-        // Si actualmente no se está parseando nada, buscamos la próxima línea
-        if self.actual.is_none() {
-            let linea: String = self.proxima_linea()?;
 
             match self.parsear_linea(&linea) {
-                ResultadoLinea::Hpub(topico, reply_to, bytes_header, bytes_totales) => {
-                    self.actual = Some(ResultadoLinea::Hpub(
-                        topico,
-                        reply_to,
-                        bytes_header,
-                        bytes_totales,
-                    ));
+                resultado @ (ResultadoLinea::Pub(..)
+                | ResultadoLinea::Hpub(..)
+                | ResultadoLinea::Msg(..)
+                | ResultadoLinea::Hmsg(..)) => {
+                    if resultado.bytes_totales() > self.limites.max_payload {
+                        return Some(self.error_fatal(ErrorProtocolo::PayloadExcedido));
+                    }
+
+                    self.actual = Some(resultado);
                 }
-                ResultadoLinea::MensajeIncorrecto => {
-                    return Some(Mensaje::Error("Mensaje incorrecto".to_string()));
+                // Las líneas vacías, +OK y -ERR no generan mensajes, se sigue con la próxima línea
+                ResultadoLinea::Ok if self.respuestas => return Some(Mensaje::RespuestaOk()),
+                ResultadoLinea::Err(error) if self.respuestas => {
+                    return Some(Mensaje::RespuestaErr(error));
                 }
-                ResultadoLinea::StringVacio => {
-                    return self.proximo_mensaje();
+                ResultadoLinea::StringVacio | ResultadoLinea::Ok | ResultadoLinea::Err(_) => {}
+                ResultadoLinea::MensajeIncorrecto => {
+                    return Some(self.error_fatal(ErrorProtocolo::OperacionDesconocida));
                 }
                 ResultadoLinea::Sub(topico, queue_group, sid) => {
                     return Some(Mensaje::Suscribir(topico, queue_group, sid));
@@ -232,331 +187,292 @@ impl Parseador {
                 ResultadoLinea::Unsub(sid, maximos_mensajes) => {
                     return Some(Mensaje::Desuscribir(sid, maximos_mensajes));
                 }
-                ResultadoLinea::Pub(topico, reply_to, bytes) => {
-                    self.actual = Some(ResultadoLinea::Pub(topico, reply_to, bytes));
-                    return self.proximo_mensaje();
-                }
-                ResultadoLinea::Ping => {
-                    return Some(Mensaje::Ping());
-                }
+                ResultadoLinea::Ping => return Some(Mensaje::Ping()),
+                ResultadoLinea::Pong => return Some(Mensaje::Pong()),
                 ResultadoLinea::Connect(parametros_conectar) => {
                     return Some(Mensaje::Conectar(parametros_conectar));
                 }
-                ResultadoLinea::Info(parametros) => {
-                    return Some(Mensaje::Info(parametros));
+                ResultadoLinea::Info(parametros) => return Some(Mensaje::Info(parametros)),
+            }
+        }
+    }
+
+    /// Descarta todo lo que quedaba por parsear, ya que después de un error
+    /// fatal no se puede saber dónde empieza el próximo mensaje
+    fn error_fatal(&mut self, error: ErrorProtocolo) -> Mensaje {
+        self.buffer.clear();
+        self.escaneado = 0;
+        self.actual = None;
+
+        Mensaje::ErrorFatal(error)
+    }
+
+    /// Arma el mensaje cuya primera línea ya se leyó cortando los headers y el
+    /// payload del buffer. Si todavía faltan bytes, devuelve la línea para
+    /// volver a intentarlo más tarde
+    fn completar_mensaje(&mut self, actual: ResultadoLinea) -> Result<Mensaje, ResultadoLinea> {
+        match actual {
+            ResultadoLinea::Pub(topico, reply_to, bytes_totales) => {
+                match self.cortar_payload(bytes_totales) {
+                    Some(payload) => Ok(Mensaje::Publicar(topico, reply_to, payload)),
+                    None => Err(ResultadoLinea::Pub(topico, reply_to, bytes_totales)),
                 }
-                ResultadoLinea::Pong => {
-                    return Some(Mensaje::Pong());
+            }
+            ResultadoLinea::Msg(topico, sid, reply_to, bytes_totales) => {
+                match self.cortar_payload(bytes_totales) {
+                    Some(payload) => Ok(Mensaje::Publicacion(topico, sid, reply_to, payload)),
+                    None => Err(ResultadoLinea::Msg(topico, sid, reply_to, bytes_totales)),
                 }
-                ResultadoLinea::Hmsg(
-                    topico,
-                    id_suscripcion,
-                    reply_to,
-                    bytes_header,
-                    bytes_contenido,
-                ) => {
-                    self.actual = Some(ResultadoLinea::Hmsg(
+            }
+            ResultadoLinea::Hpub(topico, reply_to, bytes_headers, bytes_totales) => {
+                match self.cortar_headers_y_payload(bytes_headers, bytes_totales) {
+                    Some((headers, payload)) => Ok(Mensaje::PublicarConHeader(
+                        topico, reply_to, headers, payload,
+                    )),
+                    None => Err(ResultadoLinea::Hpub(
                         topico,
-                        id_suscripcion,
                         reply_to,
-                        bytes_header,
-                        bytes_contenido,
-                    ));
+                        bytes_headers,
+                        bytes_totales,
+                    )),
                 }
-                ResultadoLinea::Msg(topico, id_suscripcion, reply_to, bytes_contenido) => {
-                    self.actual = Some(ResultadoLinea::Msg(
+            }
+            ResultadoLinea::Hmsg(topico, sid, reply_to, bytes_headers, bytes_totales) => {
+                match self.cortar_headers_y_payload(bytes_headers, bytes_totales) {
+                    Some((headers, payload)) => Ok(Mensaje::PublicacionConHeader(
+                        topico, sid, reply_to, headers, payload,
+                    )),
+                    None => Err(ResultadoLinea::Hmsg(
                         topico,
-                        id_suscripcion,
+                        sid,
                         reply_to,
-                        bytes_contenido,
-                    ));
-                    return self.proximo_mensaje();
+                        bytes_headers,
+                        bytes_totales,
+                    )),
                 }
-                _ => {}
             }
+            otro => Err(otro),
         }
-        None
     }
 
-    /// Se mira la primera palabra de la linea y se devuelve la linea de
-    /// resultado segun el tipo de mensaje
-    fn parsear_linea(&self, linea: &str) -> ResultadoLinea {
-        let palabras: Vec<String> = linea
-            .split(' ')
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>();
-
-        let palabra_vacia: String = "".to_string();
-        let primera_palabra: String = palabras.first().unwrap_or(&palabra_vacia).to_lowercase();
-
-        if primera_palabra.eq("pub") {
-            return Self::linea_pub(&palabras[1..]);
+    /// Corta el payload de un PUB o MSG, cuando ya llegó junto con el salto
+    /// de línea que le sigue
+    fn cortar_payload(&mut self, bytes_totales: usize) -> Option<Bytes> {
+        if self.buffer.len() < bytes_totales + 2 {
+            return None;
         }
 
-        if primera_palabra.eq("hpub") {
-            return Self::linea_hpub(&palabras[1..]);
-        }
+        let payload: Bytes = self.buffer.split_to(bytes_totales).freeze();
+        self.buffer.advance(2);
 
-        if primera_palabra.eq("sub") {
-            return Self::linea_sub(&palabras[1..]);
-        }
-
-        if primera_palabra.eq("unsub") {
-            return Self::linea_unsub(&palabras[1..]);
-        }
-
-        if primera_palabra.eq("") {
-            return ResultadoLinea::StringVacio;
-        }
-
-        if primera_palabra.eq("ping") {
-            return ResultadoLinea::Ping;
-        }
-
-        if primera_palabra.eq("pong") {
-            return ResultadoLinea::Pong;
-        }
-
-        if primera_palabra.eq("+ok") {
-            return ResultadoLinea::Ok;
-        }
-
-        if primera_palabra.eq("-err") {
-            return ResultadoLinea::Err;
-        }
+        Some(payload)
+    }
 
-        if primera_palabra.eq("connect") {
-            if let Ok(parametros_conectar) =
-                ParametrosConectar::desde_json(&palabras[1..].join(" "))
-            {
-                return ResultadoLinea::Connect(parametros_conectar);
-            }
+    /// Corta los headers y el payload de un HPUB o HMSG, que vienen juntos
+    /// seguidos de un salto de linea
+    fn cortar_headers_y_payload(
+        &mut self,
+        bytes_headers: usize,
+        bytes_totales: usize,
+    ) -> Option<(Bytes, Bytes)> {
+        if self.buffer.len() < bytes_totales + 2 {
+            return None;
         }
 
-        if primera_palabra.eq("info") {
-            if let Ok(parametros_info) = ParametrosInfo::desde_json(&palabras[1..].join(" ")) {
-                return ResultadoLinea::Info(parametros_info);
-            }
-        }
+        let mut headers: Bytes = self.buffer.split_to(bytes_totales).freeze();
+        let payload: Bytes = headers.split_off(bytes_headers);
+        self.buffer.advance(2);
 
-        if primera_palabra.eq("msg") {
-            return Self::linea_msg(&palabras[1..]);
-        }
+        Some((headers, payload))
+    }
 
-        if primera_palabra.eq("hmsg") {
-            return Self::linea_hmsg(&palabras[1..]);
+    /// Se mira la primera palabra de la linea y se devuelve la linea de
+    /// resultado segun el tipo de mensaje. Las palabras se comparan como bytes,
+    /// sin convertir antes la línea a texto
+    fn parsear_linea(&self, linea: &[u8]) -> ResultadoLinea {
+        let linea: &[u8] = linea.trim_ascii();
+        let mut palabras = linea
+            .split(|b| *b == b' ' || *b == b'\t')
+            .filter(|palabra| !palabra.is_empty());
+
+        let primera_palabra: &[u8] = match palabras.next() {
+            Some(palabra) => palabra,
+            None => return ResultadoLinea::StringVacio,
+        };
+        let argumentos: Vec<&[u8]> = palabras.collect();
+
+        // CONNECT e INFO llevan un JSON, que se toma tal cual del resto de la línea
+        let json = || String::from_utf8_lossy(linea[primera_palabra.len()..].trim_ascii());
+
+        match primera_palabra.to_ascii_lowercase().as_slice() {
+            b"pub" => Self::linea_pub(&argumentos),
+            b"hpub" => Self::linea_hpub(&argumentos),
+            b"sub" => Self::linea_sub(&argumentos),
+            b"unsub" => Self::linea_unsub(&argumentos),
+            b"msg" => Self::linea_msg(&argumentos),
+            b"hmsg" => Self::linea_hmsg(&argumentos),
+            b"ping" => ResultadoLinea::Ping,
+            b"pong" => ResultadoLinea::Pong,
+            b"+ok" => ResultadoLinea::Ok,
+            b"-err" => ResultadoLinea::Err(json().to_string()),
+            b"connect" => match ParametrosConectar::desde_json(&json()) {
+                Ok(parametros_conectar) => ResultadoLinea::Connect(parametros_conectar),
+                Err(_) => ResultadoLinea::MensajeIncorrecto,
+            },
+            b"info" => match ParametrosInfo::desde_json(&json()) {
+                Ok(parametros_info) => ResultadoLinea::Info(parametros_info),
+                Err(_) => ResultadoLinea::MensajeIncorrecto,
+            },
+            _ => ResultadoLinea::MensajeIncorrecto,
         }
-
-        ResultadoLinea::MensajeIncorrecto
     }
 
-    fn linea_pub(palabras: &[String]) -> ResultadoLinea {
+    /// `PUB <subject> [reply-to] <#bytes>`
+    fn linea_pub(palabras: &[&[u8]]) -> ResultadoLinea {
         // Buscamos si es de 2 o 3 para saber si tiene reply_to
-        if palabras.len() == 2 {
-            let bytes: usize = match palabras[1].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Pub(palabras[0].to_string(), None, bytes);
+        let (topico, reply_to, bytes) = match palabras {
+            [topico, bytes] => (topico, None, bytes),
+            [topico, reply_to, bytes] => (topico, Some(texto(reply_to)), bytes),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match numero(bytes) {
+            Some(bytes) => ResultadoLinea::Pub(texto(topico), reply_to, bytes),
+            None => ResultadoLinea::MensajeIncorrecto,
         }
-
-        if palabras.len() == 3 {
-            let bytes: usize = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Pub(
-                palabras[0].to_string(),
-                Some(palabras[1].to_string()),
-                bytes,
-            );
-        }
-
-        ResultadoLinea::MensajeIncorrecto
     }
 
-    fn linea_hpub(palabras: &[String]) -> ResultadoLinea {
+    /// `HPUB <subject> [reply-to] <#header bytes> <#total bytes>`
+    fn linea_hpub(palabras: &[&[u8]]) -> ResultadoLinea {
         // Buscamos si es de 3 o 4 para saber si tiene reply_to
-        if palabras.len() == 3 {
-            let bytes: usize = match palabras[1].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes: usize = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hpub(palabras[0].to_string(), None, headers_bytes, bytes);
+        let (reply_to, tamanios) = match palabras.len() {
+            3 => (None, &palabras[1..]),
+            4 => (Some(texto(palabras[1])), &palabras[2..]),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match Self::tamanios_headers(tamanios) {
+            Some((bytes_headers, bytes_totales)) => {
+                ResultadoLinea::Hpub(texto(palabras[0]), reply_to, bytes_headers, bytes_totales)
+            }
+            None => ResultadoLinea::MensajeIncorrecto,
         }
+    }
 
-        if palabras.len() == 4 {
-            let bytes: usize = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes: usize = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
+    /// Lee el par `<#header bytes> <#total bytes>` de un HPUB o HMSG. La cantidad
+    /// total incluye a los headers, por lo que nunca puede ser menor
+    fn tamanios_headers(palabras: &[&[u8]]) -> Option<(usize, usize)> {
+        let bytes_headers: usize = numero(palabras.first()?)?;
+        let bytes_totales: usize = numero(palabras.get(1)?)?;
 
-            return ResultadoLinea::Hpub(
-                palabras[0].to_string(),
-                Some(palabras[1].to_string()),
-                headers_bytes,
-                bytes,
-            );
+        if bytes_totales < bytes_headers {
+            return None;
         }
 
-        ResultadoLinea::MensajeIncorrecto
+        Some((bytes_headers, bytes_totales))
     }
 
-    fn linea_sub(palabras: &[String]) -> ResultadoLinea {
+    /// `SUB <subject> [queue group] <sid>`
+    fn linea_sub(palabras: &[&[u8]]) -> ResultadoLinea {
         // Buscamos si es de 2 o 3 para saber si tiene queue_group
-        if palabras.len() == 2 {
-            let topico: &String = &palabras[0];
-            let sid: &String = &palabras[1];
-            return ResultadoLinea::Sub(topico.to_string(), None, sid.to_string());
-        }
-
-        if palabras.len() == 3 {
-            let topico: &String = &palabras[0];
-            let queue_group: &String = &palabras[1];
-            let sid: &String = &palabras[2];
-
-            return ResultadoLinea::Sub(
-                topico.to_string(),
-                Some(queue_group.to_string()),
-                sid.to_string(),
-            );
+        match palabras {
+            [topico, sid] => ResultadoLinea::Sub(texto(topico), None, texto(sid)),
+            [topico, queue_group, sid] => {
+                ResultadoLinea::Sub(texto(topico), Some(texto(queue_group)), texto(sid))
+            }
+            _ => ResultadoLinea::MensajeIncorrecto,
         }
-
-        ResultadoLinea::MensajeIncorrecto
     }
 
-    fn linea_unsub(palabras: &[String]) -> ResultadoLinea {
-        if palabras.len() != 1 {
-            return ResultadoLinea::MensajeIncorrecto;
+    /// `UNSUB <sid> [max_msgs]`
+    fn linea_unsub(palabras: &[&[u8]]) -> ResultadoLinea {
+        match palabras {
+            [sid] => ResultadoLinea::Unsub(texto(sid), None),
+            [sid, maximos_mensajes] => match numero(maximos_mensajes) {
+                Some(maximos_mensajes) => ResultadoLinea::Unsub(texto(sid), Some(maximos_mensajes)),
+                None => ResultadoLinea::MensajeIncorrecto,
+            },
+            _ => ResultadoLinea::MensajeIncorrecto,
         }
-
-        let sid: &String = &palabras[0];
-        let maximos_mensajes: Option<u64> = palabras.get(2).map(|s| s.parse::<u64>().unwrap());
-
-        ResultadoLinea::Unsub(sid.to_string(), maximos_mensajes)
     }
 
-    fn linea_msg(palabras: &[String]) -> ResultadoLinea {
+    /// `MSG <subject> <sid> [reply-to] <#bytes>`
+    fn linea_msg(palabras: &[&[u8]]) -> ResultadoLinea {
         // Buscamos si es de 3 o 4 para saber si tiene reply_to
-        if palabras.len() == 3 {
-            let bytes: usize = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Msg(
-                palabras[0].to_string(),
-                palabras[1].to_string(),
-                None,
-                bytes,
-            );
-        }
-
-        if palabras.len() == 4 {
-            let bytes: usize = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Msg(
-                palabras[0].to_string(),
-                palabras[1].to_string(),
-                Some(palabras[2].to_string()),
-                bytes,
-            );
+        let (topico, sid, reply_to, bytes) = match palabras {
+            [topico, sid, bytes] => (topico, sid, None, bytes),
+            [topico, sid, reply_to, bytes] => (topico, sid, Some(texto(reply_to)), bytes),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match numero(bytes) {
+            Some(bytes) => ResultadoLinea::Msg(texto(topico), texto(sid), reply_to, bytes),
+            None => ResultadoLinea::MensajeIncorrecto,
         }
-
-        ResultadoLinea::MensajeIncorrecto
     }
 
-    fn linea_hmsg(palabras: &[String]) -> ResultadoLinea {
+    /// `HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>`
+    fn linea_hmsg(palabras: &[&[u8]]) -> ResultadoLinea {
         // Buscamos si es de 4 o 5 para saber si tiene reply_to
-        if palabras.len() == 4 {
-            let bytes: usize = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            let headers_bytes: usize = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hmsg(
-                palabras[0].to_string(),
-                palabras[1].to_string(),
-                None,
-                headers_bytes,
-                bytes,
-            );
+        let (reply_to, tamanios) = match palabras.len() {
+            4 => (None, &palabras[2..]),
+            5 => (Some(texto(palabras[2])), &palabras[3..]),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match Self::tamanios_headers(tamanios) {
+            Some((bytes_headers, bytes_totales)) => ResultadoLinea::Hmsg(
+                texto(palabras[0]),
+                texto(palabras[1]),
+                reply_to,
+                bytes_headers,
+                bytes_totales,
+            ),
+            None => ResultadoLinea::MensajeIncorrecto,
         }
+    }
+}
 
-        if palabras.len() == 5 {
-            let bytes: usize = match palabras[4].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            let headers_bytes: usize = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hmsg(
-                palabras[0].to_string(),
-                palabras[1].to_string(),
-                Some(palabras[2].to_string()),
-                headers_bytes,
-                bytes,
-            );
-        }
+/// Convierte una palabra de la línea (tópico, sid, etc) a texto
+fn texto(palabra: &[u8]) -> String {
+    String::from_utf8_lossy(palabra).into_owned()
+}
 
-        ResultadoLinea::MensajeIncorrecto
+/// Parsea un número decimal directamente desde los bytes de la palabra
+fn numero<T: TryFrom<u64>>(palabra: &[u8]) -> Option<T> {
+    // Con más de 19 dígitos podría no entrar en un u64
+    if palabra.is_empty() || palabra.len() > 19 {
+        return None;
     }
 
-    /// Libera los bytes de la parte del mensaje que ya se parseó
-    ///
-    /// Por ejemplo:
-    /// - La primera linea de cualquier mensaje
-    /// - El payload
-    /// - Los headers
-    fn resetear_bytes(&mut self) {
-        self.bytes_pendientes.drain(..self.continuar_en_indice);
-        self.continuar_en_indice = 0;
+    let mut valor: u64 = 0;
+    for b in palabra {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        valor = valor * 10 + u64::from(b - b'0');
     }
 
-    fn resetear_todo(&mut self) {
-        self.resetear_bytes();
-        self.actual = None;
-        self.header = None;
-    }
+    T::try_from(valor).ok()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parseador::resultado_linea::ResultadoLinea;
+    use crate::parseador::{
+        error_protocolo::ErrorProtocolo, limites::Limites, mensaje::Mensaje,
+        resultado_linea::ResultadoLinea,
+    };
 
     #[test]
     fn linea_sub() {
         let parser = super::Parseador::new();
-        let resultado = parser.parsear_linea("sub subject sid");
+        let resultado = parser.parsear_linea(b"sub subject sid");
         assert_eq!(
             resultado,
             ResultadoLinea::Sub("subject".to_string(), None, "sid".to_string())
         );
 
-        let resultado = parser.parsear_linea("sub subject queue_group sid");
+        let resultado = parser.parsear_linea(b"SUB  subject queue_group\tsid ");
         assert_eq!(
             resultado,
             ResultadoLinea::Sub(
@@ -566,7 +482,163 @@ mod tests {
             )
         );
 
-        let resultado = parser.parsear_linea("sub");
+        let resultado = parser.parsear_linea(b"sub");
         assert_eq!(resultado, ResultadoLinea::MensajeIncorrecto);
     }
+
+    #[test]
+    fn linea_unsub_con_maximo() {
+        let parser = super::Parseador::new();
+
+        assert_eq!(
+            parser.parsear_linea(b"UNSUB 1 5"),
+            ResultadoLinea::Unsub("1".to_string(), Some(5))
+        );
+        assert_eq!(
+            parser.parsear_linea(b"UNSUB 1 cinco"),
+            ResultadoLinea::MensajeIncorrecto
+        );
+    }
+
+    #[test]
+    fn respuestas_del_servidor() {
+        let mut parser = super::Parseador::new().con_respuestas();
+        parser.agregar_bytes(b"+OK\r\n-ERR 'Invalid Subject'\r\n");
+
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::RespuestaOk())
+        ));
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::RespuestaErr(error)) if error == "'Invalid Subject'"
+        ));
+
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"+OK\r\nPING\r\n");
+        assert!(matches!(parser.proximo_mensaje(), Some(Mensaje::Ping())));
+    }
+
+    #[test]
+    fn mensajes_partidos_byte_a_byte() {
+        let mut parser = super::Parseador::new();
+        let mut mensajes = Vec::new();
+
+        for b in b"PUB x 6\r\na\r\nb\r\n\r\nPING\r\n+OK\r\nPUB y r 0\r\n\r\n" {
+            parser.agregar_bytes(&[*b]);
+            while let Some(mensaje) = parser.proximo_mensaje() {
+                mensajes.push(mensaje);
+            }
+        }
+
+        match mensajes.as_slice() {
+            [Mensaje::Publicar(x, None, payload), Mensaje::Ping(), Mensaje::Publicar(y, Some(r), vacio)] =>
+            {
+                assert_eq!((x.as_str(), &payload[..]), ("x", &b"a\r\nb\r\n"[..]));
+                assert_eq!((y.as_str(), r.as_str()), ("y", "r"));
+                assert!(vacio.is_empty());
+            }
+            otros => panic!("Mensajes inesperados: {:?}", otros),
+        }
+    }
+
+    #[test]
+    fn payload_sin_salto_de_linea() {
+        // El largo no coincide con el payload: después de 4 bytes no hay \r\n
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"PUB x 4\r\nhola!\r\nPING\r\n");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::ErrorFatal(ErrorProtocolo::OperacionDesconocida))
+        ));
+        assert!(parser.proximo_mensaje().is_none());
+
+        // Hasta que lleguen los dos bytes que siguen al payload no se sabe
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"PUB x 4\r\nhola");
+        assert!(parser.proximo_mensaje().is_none());
+        parser.agregar_bytes(b"\r");
+        assert!(parser.proximo_mensaje().is_none());
+        parser.agregar_bytes(b"\n");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::Publicar(..))
+        ));
+    }
+
+    #[test]
+    fn leer_de_un_stream() {
+        let mut parser = super::Parseador::new();
+        let conectar: &[u8] = b"CONNECT {\"user\": \"admin\", \"pass\": \"1 2\"}\r\n";
+        let mut stream: &[u8] = conectar;
+
+        assert_eq!(parser.leer_de(&mut stream).unwrap(), conectar.len());
+
+        match parser.proximo_mensaje() {
+            Some(Mensaje::Conectar(parametros)) => assert_eq!(parametros.pass_str(), "1 2"),
+            otro => panic!("Mensaje inesperado: {:?}", otro),
+        }
+        assert!(parser.proximo_mensaje().is_none());
+    }
+
+    #[test]
+    fn limites_de_payload_y_control_line() {
+        let limites = Limites::new(4, 24);
+
+        let mut parser = super::Parseador::con_limites(limites);
+        parser.agregar_bytes(b"PUB x 4\r\nhola\r\nPUB x 99999999999\r\n");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::Publicar(..))
+        ));
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::ErrorFatal(ErrorProtocolo::PayloadExcedido))
+        ));
+        assert!(parser.proximo_mensaje().is_none());
+
+        // Una línea sin terminar que ya supera el máximo
+        let mut parser = super::Parseador::con_limites(limites);
+        parser.agregar_bytes(b"SUB un.topico.demasiado.largo");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::ErrorFatal(ErrorProtocolo::ControlLineExcedida))
+        ));
+    }
+
+    #[test]
+    fn hpub_con_headers_y_payload() {
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"HPUB x r 12 16\r\nNATS/1.0\r\n\r\nhola\r\n");
+
+        match parser.proximo_mensaje() {
+            Some(Mensaje::PublicarConHeader(topico, reply_to, headers, payload)) => {
+                assert_eq!(topico, "x");
+                assert_eq!(reply_to, Some("r".to_string()));
+                assert_eq!(headers, &b"NATS/1.0\r\n\r\n"[..]);
+                assert_eq!(payload, &b"hola"[..]);
+            }
+            otro => panic!("Mensaje inesperado: {:?}", otro),
+        }
+    }
+
+    #[test]
+    fn hmsg_partido_en_varias_lecturas() {
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"HMSG x 1 12 14\r\nNATS/1.0");
+        assert!(parser.proximo_mensaje().is_none());
+
+        parser.agregar_bytes(b"\r\n\r\nok\r\n");
+
+        match parser.proximo_mensaje() {
+            Some(Mensaje::PublicacionConHeader(topico, sid, reply_to, headers, payload)) => {
+                assert_eq!(topico, "x");
+                assert_eq!(sid, "1");
+                assert_eq!(reply_to, None);
+                assert_eq!(headers, &b"NATS/1.0\r\n\r\n"[..]);
+                assert_eq!(payload, &b"ok"[..]);
+            }
+            otro => panic!("Mensaje inesperado: {:?}", otro),
+        }
+    }
 }
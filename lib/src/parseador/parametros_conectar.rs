@@ -1,10 +1,18 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Result;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct ParametrosConectar {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub pass: Option<String>,
+    /// Nombre con el que se identifica el cliente (aparece en los eventos del servidor)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Si el servidor tiene que validar estrictamente los tópicos de la conexión
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pedantic: Option<bool>,
 }
 
 impl ParametrosConectar {
@@ -14,6 +22,8 @@ impl ParametrosConectar {
         Self {
             user: Some(user.to_string()),
             pass: Some(pass.to_string()),
+            name: None,
+            pedantic: None,
         }
     }
 
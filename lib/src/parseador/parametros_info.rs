@@ -4,6 +4,12 @@ use serde_json::Result;
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ParametrosInfo {
     pub requiere_auth: Option<bool>,
+    /// Direcciones de otros servidores a los que el cliente puede reconectarse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_urls: Option<Vec<String>>,
+    /// Tamaño máximo del payload que acepta el servidor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_payload: Option<usize>,
 }
 
 impl ParametrosInfo {
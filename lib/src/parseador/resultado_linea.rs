@@ -15,5 +15,19 @@ pub enum ResultadoLinea {
     Info(ParametrosInfo),
     Connect(ParametrosConectar),
     Ok,
-    Err,
+    Err(String),
+}
+
+impl ResultadoLinea {
+    /// Cantidad de bytes (headers y payload) que siguen a la línea. Cero si
+    /// el comando no lleva payload
+    pub fn bytes_totales(&self) -> usize {
+        match self {
+            ResultadoLinea::Pub(_, _, bytes_totales)
+            | ResultadoLinea::Hpub(_, _, _, bytes_totales)
+            | ResultadoLinea::Msg(_, _, _, bytes_totales)
+            | ResultadoLinea::Hmsg(_, _, _, _, bytes_totales) => *bytes_totales,
+            _ => 0,
+        }
+    }
 }
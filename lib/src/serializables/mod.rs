@@ -1,4 +1,4 @@
-use std::str::Lines;
+use crate::csv::csv_separar_registros;
 
 use self::error::DeserializationError;
 
@@ -27,7 +27,8 @@ impl<T: Serializable> Serializable for Vec<T> {
     }
 
     /// Toma un conjunto de bytes, lo convierte a un string, se toma cada
-    /// linea en formato csv, se deserializa la linea obteniendo un elemento
+    /// registro en formato csv (que puede ocupar varias lineas si tiene campos
+    /// entre comillas), se deserializa el registro obteniendo un elemento
     /// de tipo genérico, y ese elemento se añade a un vector
     fn deserializar(datos: &[u8]) -> Result<Self, DeserializationError>
     where
@@ -36,16 +37,14 @@ impl<T: Serializable> Serializable for Vec<T> {
         let texto: String =
             String::from_utf8(datos.to_vec()).map_err(|_| DeserializationError::InvalidData)?;
 
-        let lineas: Lines<'_> = texto.lines();
-
         let mut resultado: Vec<T> = Vec::new();
 
-        for linea in lineas {
-            if linea.trim().is_empty() {
+        for registro in csv_separar_registros(&texto) {
+            if registro.trim().is_empty() {
                 continue;
             }
 
-            let bytes: &[u8] = linea.as_bytes();
+            let bytes: &[u8] = registro.as_bytes();
             let elemento: T = T::deserializar(bytes)?;
             resultado.push(elemento);
         }
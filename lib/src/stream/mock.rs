@@ -1,7 +1,7 @@
 use std::{
     cmp::min,
     io::{self, Read, Write},
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{Receiver, Sender, TryRecvError},
 };
 
 use crate::stream::Stream;
@@ -26,8 +26,19 @@ impl Stream for MockStream {}
 
 impl Read for MockStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        while let Ok(bytes) = self.rx.try_recv() {
-            self.bytes_read.extend_from_slice(&bytes);
+        loop {
+            match self.rx.try_recv() {
+                Ok(bytes) => self.bytes_read.extend_from_slice(&bytes),
+                Err(TryRecvError::Empty) => {
+                    // Como un socket no bloqueante: si no hay nada para leer
+                    // pero la otra punta sigue abierta, no es un fin de stream
+                    if self.bytes_read.is_empty() {
+                        return Err(io::Error::from(io::ErrorKind::WouldBlock));
+                    }
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
         }
 
         let len: usize = min(buf.len(), self.bytes_read.len());
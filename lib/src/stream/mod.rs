@@ -9,3 +9,6 @@ use std::{
 pub trait Stream: Read + Write + Send {}
 
 impl Stream for TcpStream {}
+
+#[cfg(unix)]
+impl Stream for std::os::unix::net::UnixStream {}
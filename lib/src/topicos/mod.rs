@@ -0,0 +1,161 @@
+use std::fmt::Display;
+
+/// Comodín que reemplaza a un token del tópico
+pub const COMODIN_TOKEN: &str = "*";
+/// Comodín que reemplaza a uno o más tokens al final del tópico
+pub const COMODIN_RESTO: &str = ">";
+
+/// Por qué un tópico o un patrón de suscripción no es válido
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorTopico {
+    /// El tópico está vacío
+    Vacio,
+    /// El tópico empieza o termina con `.` o tiene `..` (Ejemplo: `a..b`)
+    TokenVacio,
+    /// El tópico tiene espacios, tabs o saltos de línea
+    Espacios,
+    /// Se usó un comodín en un tópico de publicación (Ejemplo: `PUB a.* 0`)
+    ComodinEnPublicacion,
+    /// `>` tiene que ser el último token (Ejemplo: `a.>.b`)
+    ComodinRestoNoEsElUltimo,
+    /// Un comodín mezclado con texto en el mismo token (Ejemplo: `a.b*`)
+    ComodinDentroDeToken(String),
+}
+
+impl Display for ErrorTopico {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorTopico::Vacio => write!(f, "El tópico está vacío"),
+            ErrorTopico::TokenVacio => write!(f, "El tópico tiene un token vacío"),
+            ErrorTopico::Espacios => write!(f, "El tópico tiene espacios"),
+            ErrorTopico::ComodinEnPublicacion => {
+                write!(f, "No se puede publicar en un tópico con comodines")
+            }
+            ErrorTopico::ComodinRestoNoEsElUltimo => {
+                write!(f, "El comodín '>' tiene que ser el último token")
+            }
+            ErrorTopico::ComodinDentroDeToken(token) => {
+                write!(f, "El token '{}' mezcla un comodín con texto", token)
+            }
+        }
+    }
+}
+
+/// Valida el tópico de un PUB. Siempre tiene que tener algún caracter y no
+/// puede tener espacios. En modo pedante además no puede tener tokens vacíos
+/// ni comodines (ni siquiera como parte de un token)
+pub fn validar_publicacion(topico: &str, pedantico: bool) -> Result<(), ErrorTopico> {
+    validar_caracteres(topico)?;
+
+    if !pedantico {
+        return Ok(());
+    }
+
+    for token in topico.split('.') {
+        if token.is_empty() {
+            return Err(ErrorTopico::TokenVacio);
+        }
+
+        if token.contains(COMODIN_TOKEN) || token.contains(COMODIN_RESTO) {
+            return Err(ErrorTopico::ComodinEnPublicacion);
+        }
+    }
+
+    Ok(())
+}
+
+/// Valida el patrón de un SUB. No puede tener tokens vacíos ni espacios, y
+/// `>` solo puede ser el último token. En modo pedante además los comodines
+/// tienen que ocupar el token entero (`a.b*` no es válido)
+pub fn validar_suscripcion(patron: &str, pedantico: bool) -> Result<(), ErrorTopico> {
+    validar_caracteres(patron)?;
+
+    let tokens: Vec<&str> = patron.split('.').collect();
+
+    for (indice, token) in tokens.iter().enumerate() {
+        if token.is_empty() {
+            return Err(ErrorTopico::TokenVacio);
+        }
+
+        if *token == COMODIN_RESTO && indice + 1 < tokens.len() {
+            return Err(ErrorTopico::ComodinRestoNoEsElUltimo);
+        }
+
+        let es_comodin: bool = *token == COMODIN_TOKEN || *token == COMODIN_RESTO;
+        if pedantico
+            && !es_comodin
+            && (token.contains(COMODIN_TOKEN) || token.contains(COMODIN_RESTO))
+        {
+            return Err(ErrorTopico::ComodinDentroDeToken(token.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+fn validar_caracteres(topico: &str) -> Result<(), ErrorTopico> {
+    if topico.is_empty() {
+        return Err(ErrorTopico::Vacio);
+    }
+
+    if topico.chars().any(char::is_whitespace) {
+        return Err(ErrorTopico::Espacios);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validar_publicacion, validar_suscripcion, ErrorTopico};
+
+    #[test]
+    fn topicos_de_publicacion() {
+        assert!(validar_publicacion("incidentes.1.creado", true).is_ok());
+        assert!(validar_publicacion("a..b", false).is_ok());
+        assert!(validar_publicacion("camaras.*", false).is_ok());
+
+        assert_eq!(validar_publicacion("", false), Err(ErrorTopico::Vacio));
+        assert_eq!(
+            validar_publicacion("a b", false),
+            Err(ErrorTopico::Espacios)
+        );
+        assert_eq!(
+            validar_publicacion("a..b", true),
+            Err(ErrorTopico::TokenVacio)
+        );
+        assert_eq!(
+            validar_publicacion("camaras.*", true),
+            Err(ErrorTopico::ComodinEnPublicacion)
+        );
+        assert_eq!(
+            validar_publicacion("a.b>", true),
+            Err(ErrorTopico::ComodinEnPublicacion)
+        );
+    }
+
+    #[test]
+    fn patrones_de_suscripcion() {
+        assert!(validar_suscripcion("incidentes.*.creado", true).is_ok());
+        assert!(validar_suscripcion("dron.>", true).is_ok());
+        assert!(validar_suscripcion(">", true).is_ok());
+        assert!(validar_suscripcion("a.b*", false).is_ok());
+
+        assert_eq!(
+            validar_suscripcion(".a", false),
+            Err(ErrorTopico::TokenVacio)
+        );
+        assert_eq!(
+            validar_suscripcion("a.", false),
+            Err(ErrorTopico::TokenVacio)
+        );
+        assert_eq!(
+            validar_suscripcion("a.>.b", false),
+            Err(ErrorTopico::ComodinRestoNoEsElUltimo)
+        );
+        assert_eq!(
+            validar_suscripcion("a.b*", true),
+            Err(ErrorTopico::ComodinDentroDeToken("b*".to_string()))
+        );
+    }
+}
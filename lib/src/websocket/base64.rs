@@ -0,0 +1,25 @@
+const ALFABETO: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Codifica en base64 estándar (con `=` de relleno)
+pub fn codificar(datos: &[u8]) -> String {
+    let mut resultado = String::with_capacity(datos.len().div_ceil(3) * 4);
+
+    for grupo in datos.chunks(3) {
+        let b = [
+            grupo[0],
+            grupo.get(1).copied().unwrap_or(0),
+            grupo.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= grupo.len() {
+                resultado.push(ALFABETO[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                resultado.push('=');
+            }
+        }
+    }
+
+    resultado
+}
@@ -0,0 +1,361 @@
+pub mod base64;
+pub mod sha1;
+pub mod trama;
+
+use std::io::{self, Read, Write};
+
+use crate::stream::Stream;
+
+use self::trama::{Opcode, Trama};
+
+/// Constante del RFC 6455 que se concatena a la clave del cliente para
+/// calcular el `Sec-WebSocket-Accept`
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Tamaño máximo del pedido HTTP del handshake
+const MAX_HANDSHAKE: usize = 8 * 1024;
+
+/// El valor de `Sec-WebSocket-Accept` que corresponde a la clave del cliente
+pub fn clave_aceptacion(clave: &str) -> String {
+    base64::codificar(&sha1::sha1(format!("{}{}", clave.trim(), GUID).as_bytes()))
+}
+
+#[derive(Debug, PartialEq)]
+enum Estado {
+    /// Todavía no llegó el pedido HTTP completo
+    Handshake,
+    Abierto,
+    /// Se recibió o se envió la trama de cierre
+    Cerrado,
+}
+
+/// Envuelve un stream del lado del servidor y transporta el protocolo de
+/// texto dentro de tramas de WebSocket, para que `Conexion` y `Parseador`
+/// lo usen igual que a un `TcpStream`.
+///
+/// El handshake se hace a medida que llegan los bytes: hasta que termina,
+/// `read` y `write` devuelven `WouldBlock` como un socket no bloqueante sin
+/// datos. Lo que se escribe se envía en una trama binaria por llamada, y las
+/// tramas de texto y binarias que llegan se leen como un único flujo de bytes
+pub struct StreamWebSocket<S: Read + Write + Send> {
+    stream: S,
+    estado: Estado,
+    /// Bytes recibidos que todavía no forman una trama (o el pedido HTTP) completa
+    entrada: Vec<u8>,
+    /// Contenido de las tramas de datos que todavía no se leyó
+    datos: Vec<u8>,
+    /// Bytes de tramas que el stream todavía no aceptó
+    salida: Vec<u8>,
+    /// Largo máximo del payload de una trama del cliente. Las más grandes
+    /// cierran la conexión, para que no se acumulen en `entrada`
+    max_trama: usize,
+}
+
+impl<S: Read + Write + Send> StreamWebSocket<S> {
+    pub fn servidor(stream: S, max_trama: usize) -> Self {
+        Self {
+            stream,
+            estado: Estado::Handshake,
+            entrada: Vec::new(),
+            datos: Vec::new(),
+            salida: Vec::new(),
+            max_trama,
+        }
+    }
+
+    /// Responde el pedido HTTP de upgrade si ya llegó completo
+    fn intentar_handshake(&mut self) -> io::Result<()> {
+        let fin = match self.entrada.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(fin) => fin + 4,
+            None if self.entrada.len() > MAX_HANDSHAKE => {
+                return self.rechazar_handshake("El pedido HTTP es demasiado largo");
+            }
+            None => return Ok(()),
+        };
+
+        let pedido = String::from_utf8_lossy(&self.entrada[..fin]).to_string();
+        self.entrada.drain(..fin);
+
+        let mut lineas = pedido.split("\r\n");
+        if !lineas.next().is_some_and(|l| l.starts_with("GET ")) {
+            return self.rechazar_handshake("Se esperaba un pedido GET");
+        }
+
+        let mut clave: Option<String> = None;
+        let mut protocolo: Option<String> = None;
+        let mut upgrade = false;
+
+        for linea in lineas {
+            if let Some((nombre, valor)) = linea.split_once(':') {
+                match nombre.trim().to_lowercase().as_str() {
+                    "sec-websocket-key" => clave = Some(valor.trim().to_string()),
+                    "upgrade" => upgrade = valor.trim().eq_ignore_ascii_case("websocket"),
+                    // Se acepta el primer subprotocolo que pide el cliente
+                    "sec-websocket-protocol" => {
+                        protocolo = valor.split(',').next().map(|p| p.trim().to_string())
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let clave = match clave {
+            Some(clave) if upgrade => clave,
+            _ => return self.rechazar_handshake("No es un pedido de upgrade a WebSocket"),
+        };
+
+        let mut respuesta = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n",
+            clave_aceptacion(&clave)
+        );
+        if let Some(protocolo) = protocolo {
+            respuesta.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", protocolo));
+        }
+        respuesta.push_str("\r\n");
+
+        self.salida.extend_from_slice(respuesta.as_bytes());
+        self.estado = Estado::Abierto;
+
+        // Lo que llegó después del pedido ya son tramas
+        self.decodificar_tramas()
+    }
+
+    fn rechazar_handshake(&mut self, razon: &str) -> io::Result<()> {
+        let _ = self
+            .stream
+            .write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+        self.estado = Estado::Cerrado;
+        Err(io::Error::new(io::ErrorKind::InvalidData, razon))
+    }
+
+    /// Procesa las tramas completas que hay en `entrada`
+    fn decodificar_tramas(&mut self) -> io::Result<()> {
+        while self.estado == Estado::Abierto {
+            let (trama, largo) = match Trama::decodificar_de_cliente(&self.entrada, self.max_trama)
+            {
+                Ok(Some(trama)) => trama,
+                Ok(None) => break,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            self.entrada.drain(..largo);
+
+            match trama.opcode {
+                Opcode::Texto | Opcode::Binario | Opcode::Continuacion => {
+                    self.datos.extend_from_slice(&trama.payload)
+                }
+                Opcode::Ping => self
+                    .salida
+                    .extend_from_slice(&Trama::new(Opcode::Pong, trama.payload).codificar()),
+                Opcode::Pong => {}
+                Opcode::Cierre => {
+                    self.salida
+                        .extend_from_slice(&Trama::new(Opcode::Cierre, trama.payload).codificar());
+                    self.estado = Estado::Cerrado;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Escribe todo lo que acepte el stream de `salida`
+    fn vaciar_salida(&mut self) -> io::Result<()> {
+        while !self.salida.is_empty() {
+            match self.stream.write(&self.salida) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.salida.drain(..n);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.stream.flush()
+    }
+
+    /// Como `vaciar_salida`, pero si el stream no acepta más bytes no es un error
+    fn intentar_vaciar_salida(&mut self) -> io::Result<()> {
+        match self.vaciar_salida() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            resultado => resultado,
+        }
+    }
+}
+
+impl<S: Read + Write + Send> Read for StreamWebSocket<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Se aprovecha para enviar lo que quedó pendiente (respuestas a PING,
+        // el handshake o el final de una trama)
+        self.intentar_vaciar_salida()?;
+
+        loop {
+            if !self.datos.is_empty() {
+                let largo = buf.len().min(self.datos.len());
+                buf[..largo].copy_from_slice(&self.datos[..largo]);
+                self.datos.drain(..largo);
+                return Ok(largo);
+            }
+
+            if self.estado == Estado::Cerrado {
+                self.intentar_vaciar_salida()?;
+                return Ok(0);
+            }
+
+            let mut lectura = [0u8; 16 * 1024];
+            let leidos = self.stream.read(&mut lectura)?;
+            if leidos == 0 {
+                return Ok(0);
+            }
+            self.entrada.extend_from_slice(&lectura[..leidos]);
+
+            match self.estado {
+                Estado::Handshake => self.intentar_handshake()?,
+                _ => self.decodificar_tramas()?,
+            }
+            self.intentar_vaciar_salida()?;
+        }
+    }
+}
+
+impl<S: Read + Write + Send> Write for StreamWebSocket<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.estado {
+            Estado::Handshake => return Err(io::ErrorKind::WouldBlock.into()),
+            Estado::Cerrado => return Err(io::ErrorKind::BrokenPipe.into()),
+            Estado::Abierto => {}
+        }
+
+        // Hasta que no se termine de enviar la trama anterior no se acepta otra
+        self.intentar_vaciar_salida()?;
+        if !self.salida.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        self.salida
+            .extend_from_slice(&Trama::new(Opcode::Binario, buf.to_vec()).codificar());
+        self.intentar_vaciar_salida()?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.vaciar_salida()
+    }
+}
+
+impl<S: Read + Write + Send> Stream for StreamWebSocket<S> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use crate::stream::mock_handler::MockHandler;
+
+    use super::{
+        base64, clave_aceptacion, sha1,
+        trama::{Opcode, Trama},
+        StreamWebSocket,
+    };
+
+    /// Trama enmascarada, como las que envía un cliente
+    fn trama_cliente(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mascara = [1, 2, 3, 4];
+        let mut bytes = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        bytes.extend_from_slice(&mascara);
+        bytes.extend(payload.iter().enumerate().map(|(i, b)| b ^ mascara[i % 4]));
+        bytes
+    }
+
+    #[test]
+    fn probar_sha1_y_base64() {
+        assert_eq!(
+            base64::codificar(&sha1::sha1(b"abc")),
+            "qZk+NkcGgWq6PiVxeFDCbJzQ2J0="
+        );
+        assert_eq!(base64::codificar(b"ho"), "aG8=");
+        // Ejemplo del RFC 6455
+        assert_eq!(
+            clave_aceptacion("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn probar_handshake_y_tramas() {
+        let (mut mock, stream) = MockHandler::new();
+        let mut ws = StreamWebSocket::servidor(stream, 1024);
+        let mut buffer = [0u8; 64];
+
+        // Antes del handshake no se puede escribir
+        assert!(ws.write(b"INFO {}\r\n").is_err());
+
+        let mut pedido = b"GET / HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n".to_vec();
+        pedido.extend(trama_cliente(0x1, b"PING\r\n"));
+        mock.escribir_bytes(&pedido);
+
+        let leidos = ws.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..leidos], b"PING\r\n");
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        ws.write_all(b"PONG\r\n").unwrap();
+        let respuesta: Vec<u8> = mock.recibir.try_recv().unwrap();
+        assert_eq!(
+            Trama::decodificar(&respuesta, usize::MAX).unwrap(),
+            Some((Trama::new(Opcode::Binario, b"PONG\r\n".to_vec()), 8))
+        );
+
+        // Ping de WebSocket y cierre
+        mock.escribir_bytes(&trama_cliente(0x9, b"p"));
+        mock.escribir_bytes(&trama_cliente(0x8, b""));
+        assert_eq!(ws.read(&mut buffer).unwrap(), 0);
+        let respuestas: Vec<u8> = mock.recibir.try_iter().flatten().collect();
+        assert_eq!(respuestas, [0x8A, 1, b'p', 0x88, 0]);
+    }
+
+    #[test]
+    fn probar_tramas_invalidas() {
+        const PEDIDO: &[u8] =
+            b"GET / HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: x\r\n\r\n";
+        let mut buffer = [0u8; 64];
+
+        // Con solo el encabezado ya se sabe que es demasiado grande
+        let (mut mock, stream) = MockHandler::new();
+        let mut ws = StreamWebSocket::servidor(stream, 1024);
+        let mut pedido = PEDIDO.to_vec();
+        pedido.extend_from_slice(&[
+            0x82,
+            0x80 | 127,
+            0x7F,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+            0xFF,
+        ]);
+        mock.escribir_bytes(&pedido);
+        let error = ws.read(&mut buffer).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("demasiado grande"));
+
+        let (mut mock, stream) = MockHandler::new();
+        let mut ws = StreamWebSocket::servidor(stream, 4);
+        let mut pedido = PEDIDO.to_vec();
+        pedido.extend(trama_cliente(0x2, b"PING\r\n"));
+        mock.escribir_bytes(&pedido);
+        assert!(ws.read(&mut buffer).is_err());
+
+        // Sin máscara
+        let (mut mock, stream) = MockHandler::new();
+        let mut ws = StreamWebSocket::servidor(stream, 1024);
+        let mut pedido = PEDIDO.to_vec();
+        pedido.extend(Trama::new(Opcode::Binario, b"PING\r\n".to_vec()).codificar());
+        mock.escribir_bytes(&pedido);
+        let error = ws.read(&mut buffer).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("sin máscara"));
+    }
+}
@@ -0,0 +1,55 @@
+/// SHA-1 (RFC 3174). Solo se usa para calcular el `Sec-WebSocket-Accept` del
+/// handshake, no para nada que necesite seguridad
+pub fn sha1(datos: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Se agrega un bit en 1, ceros y el largo en bits hasta completar bloques de 64 bytes
+    let mut mensaje: Vec<u8> = datos.to_vec();
+    mensaje.push(0x80);
+    while mensaje.len() % 64 != 56 {
+        mensaje.push(0);
+    }
+    mensaje.extend_from_slice(&((datos.len() as u64) * 8).to_be_bytes());
+
+    for bloque in mensaje.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, palabra) in bloque.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([palabra[0], palabra[1], palabra[2], palabra[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, palabra) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*palabra);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (valor, nuevo) in h.iter_mut().zip([a, b, c, d, e]) {
+            *valor = valor.wrapping_add(nuevo);
+        }
+    }
+
+    let mut resultado = [0u8; 20];
+    for (i, valor) in h.iter().enumerate() {
+        resultado[i * 4..i * 4 + 4].copy_from_slice(&valor.to_be_bytes());
+    }
+    resultado
+}
@@ -0,0 +1,147 @@
+/// Tipo de una trama de WebSocket (RFC 6455, sección 5.2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    Continuacion,
+    Texto,
+    Binario,
+    Cierre,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn desde_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x0 => Some(Opcode::Continuacion),
+            0x1 => Some(Opcode::Texto),
+            0x2 => Some(Opcode::Binario),
+            0x8 => Some(Opcode::Cierre),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn byte(&self) -> u8 {
+        match self {
+            Opcode::Continuacion => 0x0,
+            Opcode::Texto => 0x1,
+            Opcode::Binario => 0x2,
+            Opcode::Cierre => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trama {
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Trama {
+    pub fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+        Self { opcode, payload }
+    }
+
+    /// Lee una trama del principio del buffer. Devuelve la trama y los bytes
+    /// que ocupaba, `Ok(None)` si todavía no llegó completa o `Err` si el
+    /// opcode no existe o el payload supera `max_largo`
+    pub fn decodificar(buffer: &[u8], max_largo: usize) -> Result<Option<(Trama, usize)>, String> {
+        if buffer.len() < 2 {
+            return Ok(None);
+        }
+
+        let opcode = Opcode::desde_byte(buffer[0] & 0x0F).ok_or(format!(
+            "Opcode de WebSocket desconocido: {}",
+            buffer[0] & 0x0F
+        ))?;
+        let enmascarada = buffer[1] & 0x80 != 0;
+
+        let (largo, mut posicion) = match buffer[1] & 0x7F {
+            126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as usize, 4),
+            127 if buffer.len() >= 10 => {
+                let mut largo = [0u8; 8];
+                largo.copy_from_slice(&buffer[2..10]);
+                // Si no entra en un usize tampoco entra en `max_largo`
+                (
+                    usize::try_from(u64::from_be_bytes(largo)).unwrap_or(usize::MAX),
+                    10,
+                )
+            }
+            126 | 127 => return Ok(None),
+            largo => (largo as usize, 2),
+        };
+
+        // Se rechaza apenas se conoce el largo, sin esperar a que llegue
+        if largo > max_largo {
+            return Err(format!(
+                "Trama de WebSocket demasiado grande: {} bytes",
+                largo
+            ));
+        }
+
+        let mascara: Option<[u8; 4]> = if enmascarada {
+            if buffer.len() < posicion + 4 {
+                return Ok(None);
+            }
+            let mascara = [
+                buffer[posicion],
+                buffer[posicion + 1],
+                buffer[posicion + 2],
+                buffer[posicion + 3],
+            ];
+            posicion += 4;
+            Some(mascara)
+        } else {
+            None
+        };
+
+        if buffer.len() - posicion < largo {
+            return Ok(None);
+        }
+
+        let mut payload = buffer[posicion..posicion + largo].to_vec();
+        if let Some(mascara) = mascara {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mascara[i % 4];
+            }
+        }
+
+        Ok(Some((Trama::new(opcode, payload), posicion + largo)))
+    }
+
+    /// Como `decodificar`, para las tramas que recibe el servidor: el cliente
+    /// las tiene que enviar enmascaradas (RFC 6455, sección 5.1)
+    pub fn decodificar_de_cliente(
+        buffer: &[u8],
+        max_largo: usize,
+    ) -> Result<Option<(Trama, usize)>, String> {
+        if buffer.len() >= 2 && buffer[1] & 0x80 == 0 {
+            return Err("Trama de WebSocket del cliente sin máscara".to_string());
+        }
+
+        Trama::decodificar(buffer, max_largo)
+    }
+
+    /// Serializa la trama sin máscara (como la envía un servidor) y con FIN
+    pub fn codificar(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.payload.len() + 10);
+        bytes.push(0x80 | self.opcode.byte());
+
+        let largo = self.payload.len();
+        if largo < 126 {
+            bytes.push(largo as u8);
+        } else if largo <= u16::MAX as usize {
+            bytes.push(126);
+            bytes.extend_from_slice(&(largo as u16).to_be_bytes());
+        } else {
+            bytes.push(127);
+            bytes.extend_from_slice(&(largo as u64).to_be_bytes());
+        }
+
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+}
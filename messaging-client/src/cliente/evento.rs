@@ -0,0 +1,11 @@
+/// Cambios en la conexión con el servidor que el hilo del cliente le informa
+/// al programa (ver `Cliente::eventos_conexion`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventoConexion {
+    /// Se perdió la conexión; el hilo intenta reconectar
+    Desconectado(String),
+    /// Se conectó a otro servidor de la lista y reenvió las suscripciones
+    Reconectado(String),
+    /// El hilo terminó. Con error si no pudo reconectar a ningún servidor
+    Finalizado(Option<String>),
+}
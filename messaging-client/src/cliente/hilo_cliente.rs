@@ -1,7 +1,9 @@
 use std::{
     collections::HashMap,
-    io::{self, Read, Write},
+    io::{self, Write},
     sync::mpsc::{Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
 };
 
 use lib::{
@@ -9,12 +11,25 @@ use lib::{
     stream::Stream,
 };
 
-use super::{instruccion::Instruccion, publicacion::Publicacion};
+use super::{
+    evento::EventoConexion, instruccion::Instruccion, publicacion::Publicacion,
+    servidores::ListaServidores,
+};
+
+/// Cantidad de veces que se recorre la lista de servidores intentando reconectar
+const MAXIMAS_RONDAS_RECONEXION: usize = 10;
+/// Tiempo de espera entre cada recorrido de la lista de servidores
+const ESPERA_ENTRE_RONDAS: Duration = Duration::from_secs(1);
+/// Tiempo máximo que se espera el PONG del servidor antes de desconectarse
+const TIEMPO_LIMITE_DESCONEXION: Duration = Duration::from_secs(10);
 
 /// El hilo del cliente posee: El stream de la conexion, el canal por el cual se
 /// reciben mensajes, los canales de suscripciones que están asociados a un id
 /// de suscripción por los cuales se pueden enviar publicaciones, el flag que
-/// dice si el cliente se autenticó, el usuario y contraseña y el parseador
+/// dice si el cliente se autenticó, el usuario y contraseña y el parseador.
+///
+/// Si se pierde la conexión, reconecta al siguiente servidor de la lista y
+/// vuelve a enviar todas las suscripciones activas
 pub struct HiloCliente {
     pub stream: Box<dyn Stream>,
     pub canal_recibir_instrucciones: Receiver<Instruccion>,
@@ -22,6 +37,19 @@ pub struct HiloCliente {
     pub autenticado: bool,
     pub user: Option<String>,
     pub pass: Option<String>,
+    /// Servidores a los que se puede reconectar
+    pub servidores: ListaServidores,
+    /// Pedirle al servidor que valide estrictamente los tópicos
+    pub pedantico: bool,
+    /// Nombre que se envía en el CONNECT
+    pub nombre: Option<String>,
+    /// Por donde se informan las desconexiones y reconexiones
+    pub canal_eventos: Option<Sender<EventoConexion>>,
+    /// Tópico y queue group de cada suscripción, para reenviarlas al reconectar
+    suscripciones: HashMap<String, (String, Option<String>)>,
+    /// Si se reconectó y todavía no llegó el primer INFO del servidor nuevo.
+    /// Los INFO que el servidor envía después no reenvían las suscripciones
+    reconectado: bool,
     parseador: Parseador,
 }
 
@@ -38,19 +66,72 @@ impl HiloCliente {
             autenticado: false,
             user: None,
             pass: None,
+            servidores: ListaServidores::default(),
+            pedantico: false,
+            nombre: None,
+            canal_eventos: None,
+            suscripciones: HashMap::new(),
+            reconectado: false,
         }
     }
 
     pub fn ejecutar(&mut self) -> io::Result<()> {
         loop {
-            if !self.ciclo()? {
-                break;
+            match self.ciclo() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    self.informar(EventoConexion::Desconectado(e.to_string()));
+
+                    if !self.reconectar() {
+                        return Err(e);
+                    }
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Intenta conectarse a alguno de los servidores de la lista, empezando por el
+    /// siguiente al actual. Devuelve `false` si no se pudo conectar a ninguno
+    fn reconectar(&mut self) -> bool {
+        for _ in 0..MAXIMAS_RONDAS_RECONEXION {
+            for _ in 0..self.servidores.len() {
+                let servidor = match self.servidores.siguiente() {
+                    Some(servidor) => servidor.clone(),
+                    None => return false,
+                };
+
+                if let Ok(stream) = servidor.conectar() {
+                    self.stream = stream;
+                    self.parseador = Parseador::new();
+                    // Hasta que llegue el INFO del nuevo servidor no se procesan instrucciones
+                    self.autenticado = false;
+                    self.reconectado = true;
+
+                    self.informar(EventoConexion::Reconectado(servidor.to_string()));
+                    return true;
+                }
+            }
+
+            if self.servidores.is_empty() {
+                return false;
+            }
+
+            thread::sleep(ESPERA_ENTRE_RONDAS);
+        }
+
+        false
+    }
+
+    /// Si el programa ya no escucha los eventos, se descartan
+    fn informar(&self, evento: EventoConexion) {
+        if let Some(canal) = &self.canal_eventos {
+            let _ = canal.send(evento);
+        }
+    }
+
     /// Se leen y manejan los proximos mensajes desde el hilo, y si el cliente
     /// se autentica, se reciben y gestionan instrucciones
     fn ciclo(&mut self) -> io::Result<bool> {
@@ -79,49 +160,78 @@ impl HiloCliente {
             Mensaje::Publicacion(topico, id_suscripcion, reply_to, contenido) => {
                 let publicacion: Publicacion = Publicacion {
                     header: None,
-                    payload: contenido,
+                    payload: contenido.to_vec(),
                     replay_to: reply_to,
                     topico,
                 };
 
-                if let Some(canal) = self.canales_subscripciones.get(&id_suscripcion) {
-                    if let Err(e) = canal.send(publicacion) {
-                        return Err(io::Error::new(io::ErrorKind::Other, e));
-                    }
-                }
+                self.entregar_publicacion(&id_suscripcion, publicacion);
+            }
+            // Ejemplo: HMSG 1 1 12 16\r\nNATS/1.0\r\n\r\nhola\r\n
+            Mensaje::PublicacionConHeader(topico, id_suscripcion, reply_to, headers, contenido) => {
+                let publicacion: Publicacion = Publicacion {
+                    header: Some(headers.to_vec()),
+                    payload: contenido.to_vec(),
+                    replay_to: reply_to,
+                    topico,
+                };
+
+                self.entregar_publicacion(&id_suscripcion, publicacion);
             }
             // Ejemplo: INFO {"server_id":"a","version":"2.1.0","go":"go1.15.6","host":"...
             Mensaje::Info(parametros) => {
                 let requiere_auth: bool = parametros.requiere_auth.unwrap_or(false);
 
-                if !requiere_auth {
-                    self.stream.write_all(b"CONNECT {}\r\n")?;
-                } else {
-                    let user: &str = match &self.user {
-                        Some(user) => user,
-                        None => "",
+                if let Some(connect_urls) = &parametros.connect_urls {
+                    self.servidores.agregar_descubiertos(connect_urls);
+                }
+
+                // Los INFO que llegan con la conexión ya establecida solo
+                // actualizan los servidores descubiertos
+                if self.autenticado {
+                    return Ok(());
+                }
+
+                let mut parametros_conectar: ParametrosConectar = ParametrosConectar {
+                    name: self.nombre.clone(),
+                    ..Default::default()
+                };
+                if self.pedantico {
+                    parametros_conectar.pedantic = Some(true);
+                }
+
+                if requiere_auth {
+                    // Las credenciales de la url del servidor tienen prioridad
+                    let servidor = self.servidores.actual();
+
+                    let user: &str = match (servidor.and_then(|s| s.user.as_ref()), &self.user) {
+                        (Some(user), _) | (None, Some(user)) => user,
+                        (None, None) => "",
                     };
 
-                    let pass: &str = match &self.pass {
-                        Some(pass) => pass,
-                        None => "",
+                    let pass: &str = match (servidor.and_then(|s| s.pass.as_ref()), &self.pass) {
+                        (Some(pass), _) | (None, Some(pass)) => pass,
+                        (None, None) => "",
                     };
 
                     // Se envia un CONNECT con los parámetros de usuario y contraseña
-                    self.stream.write_all(
-                        format!(
-                            "CONNECT {}\r\n",
-                            ParametrosConectar::user_pass(user, pass).hacia_json()
-                        )
-                        .as_bytes(),
-                    )?;
+                    parametros_conectar.user = Some(user.to_string());
+                    parametros_conectar.pass = Some(pass.to_string());
                 }
 
+                self.escribir(
+                    format!("CONNECT {}\r\n", parametros_conectar.hacia_json()).as_bytes(),
+                )?;
+
+                if self.reconectado {
+                    self.reenviar_suscripciones()?;
+                    self.reconectado = false;
+                }
                 self.autenticado = true;
             }
             // Ejemplo: PING\r\n
             Mensaje::Ping() => {
-                self.stream.write_all(b"PONG\r\n")?;
+                self.escribir(b"PONG\r\n")?;
             }
             _ => {
                 eprintln!("Mensaje no reconocido: {:?}", mensaje)
@@ -131,6 +241,86 @@ impl HiloCliente {
         Ok(())
     }
 
+    /// Envía un PING y espera el PONG. Como el servidor procesa los mensajes en
+    /// orden, cuando llega el PONG ya leyó todas las publicaciones anteriores.
+    /// Si se cierra el socket con datos sin leer, el sistema operativo corta la
+    /// conexión y el servidor puede perder las últimas publicaciones
+    fn esperar_confirmacion_del_servidor(&mut self) -> io::Result<()> {
+        self.escribir(b"PING\r\n")?;
+        let limite: Instant = Instant::now() + TIEMPO_LIMITE_DESCONEXION;
+
+        while Instant::now() < limite {
+            match self.proximo_mensaje()? {
+                Some(Mensaje::Pong()) => return Ok(()),
+                Some(mensaje) => self.gestionar_nuevo_mensaje(mensaje)?,
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "El servidor no respondió el PING",
+        ))
+    }
+
+    /// Escribe todos los bytes en el stream. Como el stream no es bloqueante, si
+    /// el buffer del socket está lleno se reintenta hasta que haya lugar
+    fn escribir(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut restantes: &[u8] = bytes;
+
+        while !restantes.is_empty() {
+            match self.stream.write(restantes) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => restantes = &restantes[n..],
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::yield_now(),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Envía la publicación al canal de la suscripción correspondiente. Si la
+    /// suscripción ya se descartó (pero todavía no llegó el UNSUB) se ignora
+    fn entregar_publicacion(&mut self, id_suscripcion: &str, publicacion: Publicacion) {
+        if let Some(canal) = self.canales_subscripciones.get(id_suscripcion) {
+            if canal.send(publicacion).is_err() {
+                self.canales_subscripciones.remove(id_suscripcion);
+            }
+        }
+    }
+
+    /// Vuelve a enviar todas las suscripciones activas (después de reconectar)
+    fn reenviar_suscripciones(&mut self) -> io::Result<()> {
+        let suscripciones: Vec<(String, String, Option<String>)> = self
+            .suscripciones
+            .iter()
+            .map(|(id, (topico, queue_group))| (id.clone(), topico.clone(), queue_group.clone()))
+            .collect();
+
+        for (id_suscripcion, topico, queue_group) in suscripciones {
+            self.escribir_suscripcion(&id_suscripcion, &topico, queue_group.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    fn escribir_suscripcion(
+        &mut self,
+        id_suscripcion: &str,
+        topico: &str,
+        queue_group: Option<&str>,
+    ) -> io::Result<()> {
+        if let Some(queue_group) = queue_group {
+            self.escribir(
+                format!("SUB {} {} {}\r\n", topico, queue_group, id_suscripcion).as_bytes(),
+            )
+        } else {
+            self.escribir(format!("SUB {} {}\r\n", topico, id_suscripcion).as_bytes())
+        }
+    }
+
     fn gestionar_nueva_instruccion(&mut self, instruccion: Instruccion) -> io::Result<bool> {
         match instruccion {
             Instruccion::Suscribir {
@@ -141,80 +331,25 @@ impl HiloCliente {
             } => {
                 self.canales_subscripciones
                     .insert(id_suscripcion.to_owned(), canal);
+                self.suscripciones.insert(
+                    id_suscripcion.to_owned(),
+                    (topico.to_owned(), queue_group.to_owned()),
+                );
 
-                if let Some(queue_group) = queue_group {
-                    self.stream.write_all(
-                        format!("SUB {} {} {}\r\n", topico, id_suscripcion, queue_group).as_bytes(),
-                    )?;
-                } else {
-                    self.stream
-                        .write_all(format!("SUB {} {}\r\n", topico, id_suscripcion).as_bytes())?;
-                }
+                self.escribir_suscripcion(&id_suscripcion, &topico, queue_group.as_deref())?;
             }
             Instruccion::Desuscribir { id_suscripcion } => {
                 self.canales_subscripciones
                     .remove(&id_suscripcion.to_string());
-                self.stream
-                    .write_all(format!("UNSUB {}\r\n", id_suscripcion).as_bytes())?;
+                self.suscripciones.remove(&id_suscripcion);
+                self.escribir(format!("UNSUB {}\r\n", id_suscripcion).as_bytes())?;
             }
             Instruccion::Publicar(publicacion) => {
-                if let Some(reply_to) = publicacion.replay_to {
-                    if let Some(header) = &publicacion.header {
-                        self.stream.write_all(
-                            format!(
-                                "PUB {} {} {} {}\r\n",
-                                publicacion.topico,
-                                reply_to,
-                                header.len(),
-                                publicacion.payload.len()
-                            )
-                            .as_bytes(),
-                        )?;
-                        self.stream.write_all(header)?;
-                        self.stream.write_all(b"\r\n")?;
-                        self.stream.write_all(&publicacion.payload)?;
-                        self.stream.write_all(b"\r\n")?;
-                    } else {
-                        self.stream.write_all(
-                            format!(
-                                "PUB {} {} {}\r\n",
-                                publicacion.topico,
-                                reply_to,
-                                publicacion.payload.len()
-                            )
-                            .as_bytes(),
-                        )?;
-                        self.stream.write_all(&publicacion.payload)?;
-                        self.stream.write_all(b"\r\n")?;
-                    }
-                } else if let Some(header) = &publicacion.header {
-                    self.stream.write_all(
-                        format!(
-                            "HPUB {} {} {}\r\n",
-                            publicacion.topico,
-                            header.len(),
-                            publicacion.payload.len()
-                        )
-                        .as_bytes(),
-                    )?;
-                    self.stream.write_all(header)?;
-                    self.stream.write_all(b"\r\n")?;
-                    self.stream.write_all(&publicacion.payload)?;
-                    self.stream.write_all(b"\r\n")?;
-                } else {
-                    self.stream.write_all(
-                        format!(
-                            "PUB {} {}\r\n",
-                            publicacion.topico,
-                            publicacion.payload.len()
-                        )
-                        .as_bytes(),
-                    )?;
-                    self.stream.write_all(&publicacion.payload)?;
-                    self.stream.write_all(b"\r\n")?;
-                }
+                self.escribir_publicacion(&publicacion)?;
             }
             Instruccion::Desconectar => {
+                // Si no se puede confirmar, igual se desconecta
+                let _ = self.esperar_confirmacion_del_servidor();
                 return Ok(false);
             }
         }
@@ -222,14 +357,52 @@ impl HiloCliente {
         Ok(true)
     }
 
+    /// Escribe un PUB, o un HPUB si la publicación tiene headers. En el HPUB
+    /// se indica el tamaño de los headers y el tamaño total (headers + payload)
+    fn escribir_publicacion(&mut self, publicacion: &Publicacion) -> io::Result<()> {
+        let reply_to: String = match &publicacion.replay_to {
+            Some(reply_to) => format!(" {}", reply_to),
+            None => "".to_string(),
+        };
+
+        if let Some(header) = &publicacion.header {
+            self.escribir(
+                format!(
+                    "HPUB {}{} {} {}\r\n",
+                    publicacion.topico,
+                    reply_to,
+                    header.len(),
+                    header.len() + publicacion.payload.len()
+                )
+                .as_bytes(),
+            )?;
+            self.escribir(header)?;
+        } else {
+            self.escribir(
+                format!(
+                    "PUB {}{} {}\r\n",
+                    publicacion.topico,
+                    reply_to,
+                    publicacion.payload.len()
+                )
+                .as_bytes(),
+            )?;
+        }
+
+        self.escribir(&publicacion.payload)?;
+        self.escribir(b"\r\n")
+    }
+
     /// Leer bytes del stream y procesarlos en el parser
     fn proximo_mensaje(&mut self) -> io::Result<Option<Mensaje>> {
-        let mut buffer: [u8; 1024] = [0; 1024];
-
-        match self.stream.read(&mut buffer) {
-            Ok(n) => {
-                self.parseador.agregar_bytes(&buffer[..n]);
+        match self.parseador.leer_de(&mut self.stream) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "El servidor cerró la conexión",
+                ));
             }
+            Ok(_) => {}
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                 // No hay datos para leer (no hay que hacer nada acá)
             }
@@ -357,4 +530,114 @@ mod tests {
             .unwrap()
             .starts_with("SUB Saludar 1\r\n"));
     }
+
+    #[test]
+    fn publicar_con_header_y_reply_to() {
+        let (mut control, stream) = MockHandler::new();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let mut cliente = HiloCliente::new(Box::new(stream), rx);
+
+        control.escribir_bytes(b"INFO {}\r\n");
+        cliente.ciclo().unwrap();
+        control.intentar_recibir_string();
+
+        tx.send(Instruccion::Publicar(Publicacion {
+            header: Some(b"NATS/1.0\r\n\r\n".to_vec()),
+            replay_to: Some("inbox".to_string()),
+            payload: b"Hola".to_vec(),
+            topico: "Saludar".to_string(),
+        }))
+        .unwrap();
+
+        cliente.ciclo().unwrap();
+
+        assert_eq!(
+            control.intentar_recibir_string().unwrap(),
+            "HPUB Saludar inbox 12 16\r\nNATS/1.0\r\n\r\nHola\r\n"
+        );
+    }
+
+    #[test]
+    fn recibir_hmsg() {
+        let (mut control, stream) = MockHandler::new();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let mut cliente = HiloCliente::new(Box::new(stream), rx);
+
+        control.escribir_bytes(b"INFO {}\r\n");
+        cliente.ciclo().unwrap();
+
+        let (tx_pub, rx_pub) = std::sync::mpsc::channel::<Publicacion>();
+
+        tx.send(Instruccion::Suscribir {
+            canal: tx_pub,
+            id_suscripcion: "1".to_string(),
+            queue_group: Some("q".to_string()),
+            topico: "Saludar".to_string(),
+        })
+        .unwrap();
+        cliente.ciclo().unwrap();
+
+        assert!(control
+            .intentar_recibir_string()
+            .unwrap()
+            .ends_with("SUB Saludar q 1\r\n"));
+
+        control.escribir_bytes(b"HMSG Saludar 1 12 16\r\nNATS/1.0\r\n\r\nHola\r\n");
+        cliente.ciclo().unwrap();
+
+        let publicacion = rx_pub.try_recv().unwrap();
+        assert_eq!(publicacion.header, Some(b"NATS/1.0\r\n\r\n".to_vec()));
+        assert_eq!(publicacion.payload, b"Hola");
+    }
+
+    #[test]
+    fn reenviar_suscripciones_al_reconectar() {
+        let (mut control, stream) = MockHandler::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cliente = HiloCliente::new(Box::new(stream), rx);
+
+        control.escribir_bytes(b"INFO {}\r\n");
+        cliente.ciclo().unwrap();
+        control.intentar_recibir_string().unwrap();
+
+        let (tx_pub, _rx_pub) = std::sync::mpsc::channel();
+        tx.send(Instruccion::Suscribir {
+            canal: tx_pub,
+            id_suscripcion: "1".to_string(),
+            queue_group: None,
+            topico: "camaras".to_string(),
+        })
+        .unwrap();
+        cliente.ciclo().unwrap();
+        assert_eq!(
+            control.intentar_recibir_string().unwrap(),
+            "SUB camaras 1\r\n"
+        );
+
+        // El servidor corta la conexión
+        drop(control);
+        assert!(cliente.ciclo().is_err());
+
+        // Se simula la conexión con otro servidor
+        let (mut control, stream) = MockHandler::new();
+        cliente.stream = Box::new(stream);
+        cliente.autenticado = false;
+        cliente.reconectado = true;
+
+        control.escribir_bytes(b"INFO {}\r\n");
+        cliente.ciclo().unwrap();
+
+        let recibido = control.intentar_recibir_string().unwrap();
+        assert!(recibido.starts_with("CONNECT"));
+        assert!(recibido.ends_with("SUB camaras 1\r\n"));
+
+        // Otro INFO con la conexión establecida no vuelve a enviar nada
+        control.escribir_bytes(b"INFO {\"connect_urls\":[\"127.0.0.1:4223\"]}\r\n");
+        cliente.ciclo().unwrap();
+        assert_eq!(control.intentar_recibir_string(), None);
+    }
 }
@@ -1,28 +1,46 @@
 mod hilo_cliente;
 mod instruccion;
 
+pub mod evento;
+pub mod opciones;
 pub mod publicacion;
+pub mod servidores;
 pub mod suscripcion;
 
 use std::{
     io,
-    net::TcpStream,
-    sync::mpsc::{channel, Sender},
+    sync::mpsc::{channel, Receiver, Sender},
     thread::{self, JoinHandle},
     time::Duration,
 };
 
+use lib::{
+    headers::{Headers, HEADER_ID_MENSAJE},
+    serializables::Serializable,
+    stream::Stream,
+    topicos::{self, ErrorTopico},
+};
+
 use self::{
-    hilo_cliente::HiloCliente, instruccion::Instruccion, publicacion::Publicacion,
+    evento::EventoConexion,
+    hilo_cliente::HiloCliente,
+    instruccion::Instruccion,
+    opciones::OpcionesConexion,
+    publicacion::Publicacion,
+    servidores::{DireccionServidor, ListaServidores},
     suscripcion::Suscripcion,
 };
 
 /// El cliente tiene: El hilo donde se gestionan los mensajes, el canal por el cual
 /// se envían mensajes al servidor, y un id
 pub struct Cliente {
-    _hilo_cliente: JoinHandle<()>,
+    hilo_cliente: Option<JoinHandle<()>>,
     canal_instrucciones: Sender<Instruccion>,
     id: usize,
+    /// Validar estrictamente los tópicos
+    pedantico: bool,
+    /// Eventos de la conexión, hasta que el programa los pida
+    eventos_conexion: Option<Receiver<EventoConexion>>,
 }
 
 impl Cliente {
@@ -35,30 +53,101 @@ impl Cliente {
         user: Option<String>,
         pass: Option<String>,
     ) -> io::Result<Cliente> {
-        let stream: TcpStream = TcpStream::connect(direccion)?;
-        stream.set_nonblocking(true)?;
+        let mut opciones: OpcionesConexion = OpcionesConexion::new(&[direccion]);
+        opciones.user = user;
+        opciones.pass = pass;
+
+        Self::conectar_con_opciones(opciones)
+    }
+
+    /// Se conecta al primer servidor disponible de la lista. Si después se pierde
+    /// la conexión, el hilo del cliente reconecta con el siguiente servidor
+    pub fn conectar_con_opciones(opciones: OpcionesConexion) -> io::Result<Cliente> {
+        let direcciones: Vec<DireccionServidor> = opciones
+            .servidores
+            .iter()
+            .map(|url| DireccionServidor::parsear(url))
+            .collect::<io::Result<_>>()?;
+
+        let mut servidores: ListaServidores =
+            ListaServidores::new(direcciones, opciones.aleatorizar);
+
+        let mut ultimo_error: io::Error =
+            io::Error::new(io::ErrorKind::InvalidInput, "No se indicó ningún servidor");
+        let mut stream: Option<Box<dyn Stream>> = None;
+
+        for _ in 0..servidores.len() {
+            let servidor: &DireccionServidor = match servidores.actual() {
+                Some(servidor) => servidor,
+                None => break,
+            };
+
+            match servidor.conectar() {
+                Ok(s) => {
+                    stream = Some(s);
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("No se pudo conectar a {}: {}", servidor, e);
+                    ultimo_error = e;
+                    servidores.siguiente();
+                }
+            }
+        }
+
+        let stream: Box<dyn Stream> = match stream {
+            Some(stream) => stream,
+            None => return Err(ultimo_error),
+        };
 
         let (tx, rx) = channel();
+        let (tx_eventos, rx_eventos) = channel();
+        let pedantico: bool = opciones.pedantico;
 
         let hilo_cliente: JoinHandle<()> = thread::spawn(move || {
-            let mut hilo_cliente: HiloCliente = HiloCliente::new(Box::new(stream), rx);
-            hilo_cliente.user = user;
-            hilo_cliente.pass = pass;
-            if let Err(e) = hilo_cliente.ejecutar() {
-                eprintln!("Error en hilo cliente: {}", e)
-            } else {
-                println!("Hilo cliente finalizado")
+            let mut hilo_cliente: HiloCliente = HiloCliente::new(stream, rx);
+            hilo_cliente.user = opciones.user;
+            hilo_cliente.pass = opciones.pass;
+            hilo_cliente.servidores = servidores;
+            hilo_cliente.pedantico = opciones.pedantico;
+            hilo_cliente.nombre = opciones.nombre;
+            hilo_cliente.canal_eventos = Some(tx_eventos.clone());
+
+            let error: Option<String> = hilo_cliente.ejecutar().err().map(|e| e.to_string());
+            // Aunque nadie lea los eventos, quedarse sin servidores no puede
+            // pasar en silencio
+            if let Some(error) = &error {
+                eprintln!(
+                    "El cliente perdió la conexión con todos los servidores: {}",
+                    error
+                );
             }
+            let _ = tx_eventos.send(EventoConexion::Finalizado(error));
         });
 
         Ok(Cliente {
-            _hilo_cliente: hilo_cliente,
+            hilo_cliente: Some(hilo_cliente),
             canal_instrucciones: tx,
             id: 0,
+            pedantico,
+            eventos_conexion: Some(rx_eventos),
         })
     }
 
+    /// Las desconexiones, reconexiones y el fin del hilo del cliente. Se
+    /// pueden pedir una sola vez
+    pub fn eventos_conexion(&mut self) -> Option<Receiver<EventoConexion>> {
+        self.eventos_conexion.take()
+    }
+
+    /// Valida el tópico de una publicación antes de enviarla al servidor
+    fn validar_publicacion(&self, topico: &str) -> io::Result<()> {
+        topicos::validar_publicacion(topico, self.pedantico).map_err(error_topico)
+    }
+
     pub fn publicar(&self, topico: &str, body: &[u8], reply_to: Option<&str>) -> io::Result<()> {
+        self.validar_publicacion(topico)?;
+
         let publicacion: Publicacion = Publicacion {
             header: None,
             payload: body.to_vec(),
@@ -70,7 +159,7 @@ impl Cliente {
             .canal_instrucciones
             .send(Instruccion::Publicar(publicacion))
         {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(())
@@ -83,6 +172,8 @@ impl Cliente {
         header: &[u8],
         reply_to: Option<&str>,
     ) -> io::Result<()> {
+        self.validar_publicacion(topico)?;
+
         let publicacion: Publicacion = Publicacion {
             header: Some(header.to_vec()),
             payload: body.to_vec(),
@@ -94,12 +185,29 @@ impl Cliente {
             .canal_instrucciones
             .send(Instruccion::Publicar(publicacion))
         {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(())
     }
 
+    /// Publica con el header `Nats-Msg-Id`. Si el servidor deduplica el
+    /// tópico, descarta las publicaciones que repiten el id dentro de la
+    /// ventana configurada (aunque responde como si las hubiera aceptado)
+    pub fn publicar_con_id(&self, topico: &str, body: &[u8], id: &str) -> io::Result<()> {
+        let mut headers: Headers = Headers::new();
+        headers.insertar(HEADER_ID_MENSAJE, id);
+
+        self.publicar_con_header(topico, body, &headers.serializar(), None)
+    }
+
+    /// Como `publicar_con_id`, pero el id se calcula a partir del tópico y el
+    /// contenido: publicar lo mismo más de una vez (por ejemplo, al reintentar)
+    /// siempre usa el mismo id
+    pub fn publicar_deduplicado(&self, topico: &str, body: &[u8]) -> io::Result<()> {
+        self.publicar_con_id(topico, body, &id_de_contenido(topico, body))
+    }
+
     pub fn nuevo_inbox(&self) -> String {
         format!("_INBOX.{}", nuid::next())
     }
@@ -110,10 +218,7 @@ impl Cliente {
         {
             Ok(publicacion)
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "No se recibió respuesta".to_string(),
-            ))
+            Err(io::Error::other("No se recibió respuesta".to_string()))
         }
     }
 
@@ -137,10 +242,7 @@ impl Cliente {
         {
             Ok(publicacion)
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "No se recibió respuesta".to_string(),
-            ))
+            Err(io::Error::other("No se recibió respuesta".to_string()))
         }
     }
 
@@ -185,6 +287,8 @@ impl Cliente {
         topico: &str,
         queue_group: Option<&str>,
     ) -> io::Result<Suscripcion> {
+        topicos::validar_suscripcion(topico, self.pedantico).map_err(error_topico)?;
+
         self.id += 1;
         let id: String = format!("{}", self.id);
 
@@ -200,11 +304,41 @@ impl Cliente {
             queue_group: queue_group.map(|s| s.to_owned()),
             canal: tx,
         }) {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(Suscripcion::new(canal_instrucciones, rx, id))
     }
+
+    /// Se desconecta del servidor esperando a que se terminen de enviar
+    /// todas las publicaciones pendientes
+    pub fn desconectar(&mut self) -> io::Result<()> {
+        let _ = self.canal_instrucciones.send(Instruccion::Desconectar);
+
+        if let Some(hilo_cliente) = self.hilo_cliente.take() {
+            if hilo_cliente.join().is_err() {
+                return Err(io::Error::other("Error al finalizar el hilo del cliente"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn error_topico(error: ErrorTopico) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error.to_string())
+}
+
+/// Hash FNV-1a de 64 bits del tópico y el contenido, en hexadecimal. No
+/// depende del proceso, así que otro cliente que publica lo mismo obtiene el
+/// mismo id
+fn id_de_contenido(topico: &str, body: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in topico.as_bytes().iter().chain(&[0]).chain(body) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
 }
 
 impl Drop for Cliente {
@@ -1 +1,2 @@
 pub mod cliente;
+pub mod servicio;
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use crate::cliente::suscripcion::Suscripcion;
+
+/// Queue group que usan los endpoints si no se especifica otro
+pub const QUEUE_GROUP_POR_DEFECTO: &str = "q";
+
+/// Un endpoint del servicio: un nombre, el tópico en el que escucha
+/// (dentro de un queue group) y sus estadísticas
+pub struct Endpoint {
+    pub nombre: String,
+    pub topico: String,
+    pub queue_group: String,
+    pub estadisticas: EstadisticasEndpoint,
+    pub(super) suscripcion: Suscripcion,
+}
+
+/// Estadísticas que se informan en `$SRV.STATS`
+#[derive(Debug, Clone, Default)]
+pub struct EstadisticasEndpoint {
+    pub peticiones: u64,
+    pub errores: u64,
+    pub ultimo_error: Option<String>,
+    pub tiempo_procesamiento: Duration,
+}
+
+impl EstadisticasEndpoint {
+    /// Tiempo promedio de procesamiento de las peticiones respondidas
+    pub fn tiempo_procesamiento_promedio(&self) -> Duration {
+        if self.peticiones == 0 {
+            return Duration::ZERO;
+        }
+
+        // En nanosegundos, para no truncar la cantidad de peticiones a u32
+        let promedio: u128 = self.tiempo_procesamiento.as_nanos() / self.peticiones as u128;
+        Duration::from_nanos(u64::try_from(promedio).unwrap_or(u64::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::EstadisticasEndpoint;
+
+    #[test]
+    fn promedio_con_muchas_peticiones() {
+        let estadisticas = EstadisticasEndpoint {
+            peticiones: u32::MAX as u64 * 4,
+            tiempo_procesamiento: Duration::from_nanos(u32::MAX as u64 * 8),
+            ..Default::default()
+        };
+        assert_eq!(
+            estadisticas.tiempo_procesamiento_promedio(),
+            Duration::from_nanos(2)
+        );
+        assert_eq!(
+            EstadisticasEndpoint::default().tiempo_procesamiento_promedio(),
+            Duration::ZERO
+        );
+    }
+}
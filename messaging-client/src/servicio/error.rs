@@ -0,0 +1,44 @@
+use lib::{headers::Headers, serializables::Serializable};
+
+use crate::cliente::publicacion::Publicacion;
+
+/// Header con la descripción del error de un servicio
+pub const HEADER_ERROR: &str = "Nats-Service-Error";
+/// Header con el código del error de un servicio
+pub const HEADER_CODIGO_ERROR: &str = "Nats-Service-Error-Code";
+
+/// Error que devolvió un endpoint en lugar de una respuesta
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorServicio {
+    pub codigo: u16,
+    pub descripcion: String,
+}
+
+impl ErrorServicio {
+    pub fn new(codigo: u16, descripcion: &str) -> Self {
+        Self {
+            codigo,
+            descripcion: descripcion.to_string(),
+        }
+    }
+
+    /// Headers con los que se envía el error por el tópico de respuesta
+    pub fn headers(&self) -> Headers {
+        let mut headers: Headers = Headers::new();
+        headers.insertar(HEADER_ERROR, &self.descripcion);
+        headers.insertar(HEADER_CODIGO_ERROR, &self.codigo.to_string());
+        headers
+    }
+
+    /// Si la respuesta recibida de un servicio es un error, lo devuelve
+    pub fn desde_publicacion(publicacion: &Publicacion) -> Option<Self> {
+        let headers: Headers = Headers::deserializar(publicacion.header.as_ref()?).ok()?;
+        let descripcion: &str = headers.obtener(HEADER_ERROR)?;
+        let codigo: u16 = headers
+            .obtener(HEADER_CODIGO_ERROR)
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(500);
+
+        Some(Self::new(codigo, descripcion))
+    }
+}
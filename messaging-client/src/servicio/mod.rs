@@ -0,0 +1,362 @@
+pub mod endpoint;
+pub mod error;
+pub mod peticion;
+pub mod respuestas;
+
+use std::{io, time::Instant};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use lib::serializables::Serializable;
+
+use crate::cliente::{publicacion::Publicacion, suscripcion::Suscripcion, Cliente};
+
+use self::{
+    endpoint::{Endpoint, EstadisticasEndpoint, QUEUE_GROUP_POR_DEFECTO},
+    error::ErrorServicio,
+    peticion::Peticion,
+    respuestas::{
+        InfoEndpoint, RespuestaInfo, RespuestaPing, RespuestaStats, StatsEndpoint, TIPO_INFO,
+        TIPO_PING, TIPO_STATS,
+    },
+};
+
+/// Prefijo de los tópicos de descubrimiento de servicios
+pub const PREFIJO_DESCUBRIMIENTO: &str = "$SRV";
+
+/// Tipos de pedidos de descubrimiento que responde un servicio
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Descubrimiento {
+    Ping,
+    Info,
+    Stats,
+}
+
+impl Descubrimiento {
+    fn verbo(&self) -> &'static str {
+        match self {
+            Descubrimiento::Ping => "PING",
+            Descubrimiento::Info => "INFO",
+            Descubrimiento::Stats => "STATS",
+        }
+    }
+}
+
+/// Un servicio agrupa endpoints de petición/respuesta bajo un nombre.
+///
+/// Cada endpoint escucha en un tópico dentro de un queue group, así varias
+/// instancias del mismo servicio se reparten las peticiones. Además el servicio
+/// responde automáticamente a los pedidos de descubrimiento en `$SRV.PING`,
+/// `$SRV.INFO` y `$SRV.STATS` (en todos los casos también `.<nombre>` y `.<nombre>.<id>`)
+///
+/// El servicio no tiene un hilo propio: hay que llamar a `proxima_peticion`
+/// periódicamente, de la misma forma que se lee de una suscripción
+pub struct Servicio {
+    pub id: String,
+    pub nombre: String,
+    pub version: String,
+    pub descripcion: String,
+    inicio: DateTime<Utc>,
+    endpoints: Vec<Endpoint>,
+    suscripciones_descubrimiento: Vec<(Descubrimiento, Suscripcion)>,
+}
+
+impl Servicio {
+    /// Crea el servicio y lo suscribe a los tópicos de descubrimiento
+    pub fn new(
+        cliente: &mut Cliente,
+        nombre: &str,
+        version: &str,
+        descripcion: &str,
+    ) -> io::Result<Self> {
+        if !nombre_valido(nombre) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Nombre de servicio inválido: {}", nombre),
+            ));
+        }
+
+        let id: String = nuid::next().to_string();
+        let mut suscripciones_descubrimiento = Vec::new();
+
+        for descubrimiento in [
+            Descubrimiento::Ping,
+            Descubrimiento::Info,
+            Descubrimiento::Stats,
+        ] {
+            let base: String = format!("{}.{}", PREFIJO_DESCUBRIMIENTO, descubrimiento.verbo());
+
+            for topico in [
+                base.clone(),
+                format!("{}.{}", base, nombre),
+                format!("{}.{}.{}", base, nombre, id),
+            ] {
+                suscripciones_descubrimiento
+                    .push((descubrimiento, cliente.suscribirse(&topico, None)?));
+            }
+        }
+
+        Ok(Self {
+            id,
+            nombre: nombre.to_string(),
+            version: version.to_string(),
+            descripcion: descripcion.to_string(),
+            inicio: Utc::now(),
+            endpoints: Vec::new(),
+            suscripciones_descubrimiento,
+        })
+    }
+
+    /// Registra un endpoint. Si no se indica queue group se usa `q`
+    pub fn agregar_endpoint(
+        &mut self,
+        cliente: &mut Cliente,
+        nombre: &str,
+        topico: &str,
+        queue_group: Option<&str>,
+    ) -> io::Result<()> {
+        if !nombre_valido(nombre) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Nombre de endpoint inválido: {}", nombre),
+            ));
+        }
+
+        let queue_group: &str = queue_group.unwrap_or(QUEUE_GROUP_POR_DEFECTO);
+        let suscripcion: Suscripcion = cliente.suscribirse(topico, Some(queue_group))?;
+
+        self.endpoints.push(Endpoint {
+            nombre: nombre.to_string(),
+            topico: topico.to_string(),
+            queue_group: queue_group.to_string(),
+            estadisticas: EstadisticasEndpoint::default(),
+            suscripcion,
+        });
+
+        Ok(())
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    /// Responde los pedidos de descubrimiento pendientes y devuelve la próxima
+    /// petición que haya llegado a alguno de los endpoints (si hay alguna)
+    pub fn proxima_peticion(&mut self, cliente: &Cliente) -> io::Result<Option<Peticion>> {
+        self.responder_descubrimiento(cliente)?;
+
+        for endpoint in self.endpoints.iter_mut() {
+            if let Some(publicacion) = endpoint.suscripcion.intentar_leer()? {
+                endpoint.estadisticas.peticiones += 1;
+
+                return Ok(Some(Peticion {
+                    endpoint: endpoint.nombre.to_string(),
+                    topico: publicacion.topico,
+                    replay_to: publicacion.replay_to,
+                    payload: publicacion.payload,
+                    header: publicacion.header,
+                    recibida: Instant::now(),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Envía la respuesta de una petición por su tópico de respuesta
+    pub fn responder(
+        &mut self,
+        cliente: &Cliente,
+        peticion: &Peticion,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        self.registrar_tiempo(peticion);
+
+        if let Some(replay_to) = &peticion.replay_to {
+            cliente.publicar(replay_to, payload, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Envía un error como respuesta de una petición. El error viaja en los
+    /// headers `Nats-Service-Error` y `Nats-Service-Error-Code`
+    pub fn responder_error(
+        &mut self,
+        cliente: &Cliente,
+        peticion: &Peticion,
+        error: &ErrorServicio,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        self.registrar_tiempo(peticion);
+
+        if let Some(endpoint) = self.endpoint_mut(&peticion.endpoint) {
+            endpoint.estadisticas.errores += 1;
+            endpoint.estadisticas.ultimo_error =
+                Some(format!("{}:{}", error.codigo, error.descripcion));
+        }
+
+        if let Some(replay_to) = &peticion.replay_to {
+            cliente.publicar_con_header(replay_to, payload, &error.headers().serializar(), None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Vuelve a cero las estadísticas de todos los endpoints
+    pub fn reiniciar_estadisticas(&mut self) {
+        for endpoint in self.endpoints.iter_mut() {
+            endpoint.estadisticas = EstadisticasEndpoint::default();
+        }
+    }
+
+    fn endpoint_mut(&mut self, nombre: &str) -> Option<&mut Endpoint> {
+        self.endpoints.iter_mut().find(|e| e.nombre.eq(nombre))
+    }
+
+    fn registrar_tiempo(&mut self, peticion: &Peticion) {
+        if let Some(endpoint) = self.endpoint_mut(&peticion.endpoint) {
+            endpoint.estadisticas.tiempo_procesamiento += peticion.recibida.elapsed();
+        }
+    }
+
+    fn responder_descubrimiento(&self, cliente: &Cliente) -> io::Result<()> {
+        for (descubrimiento, suscripcion) in &self.suscripciones_descubrimiento {
+            while let Some(publicacion) = suscripcion.intentar_leer()? {
+                self.responder_pedido_descubrimiento(cliente, *descubrimiento, &publicacion)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn responder_pedido_descubrimiento(
+        &self,
+        cliente: &Cliente,
+        descubrimiento: Descubrimiento,
+        publicacion: &Publicacion,
+    ) -> io::Result<()> {
+        let replay_to: &str = match &publicacion.replay_to {
+            Some(replay_to) => replay_to,
+            None => return Ok(()),
+        };
+
+        let json: serde_json::Result<String> = match descubrimiento {
+            Descubrimiento::Ping => serde_json::to_string(&self.ping()),
+            Descubrimiento::Info => serde_json::to_string(&self.info()),
+            Descubrimiento::Stats => serde_json::to_string(&self.stats()),
+        };
+
+        match json {
+            Ok(json) => cliente.publicar(replay_to, json.as_bytes(), None),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    pub fn ping(&self) -> RespuestaPing {
+        RespuestaPing {
+            tipo: TIPO_PING.to_string(),
+            nombre: self.nombre.to_string(),
+            id: self.id.to_string(),
+            version: self.version.to_string(),
+        }
+    }
+
+    pub fn info(&self) -> RespuestaInfo {
+        RespuestaInfo {
+            tipo: TIPO_INFO.to_string(),
+            nombre: self.nombre.to_string(),
+            id: self.id.to_string(),
+            version: self.version.to_string(),
+            descripcion: self.descripcion.to_string(),
+            endpoints: self
+                .endpoints
+                .iter()
+                .map(|e| InfoEndpoint {
+                    nombre: e.nombre.to_string(),
+                    topico: e.topico.to_string(),
+                    queue_group: e.queue_group.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn stats(&self) -> RespuestaStats {
+        RespuestaStats {
+            tipo: TIPO_STATS.to_string(),
+            nombre: self.nombre.to_string(),
+            id: self.id.to_string(),
+            version: self.version.to_string(),
+            inicio: self.inicio.to_rfc3339_opts(SecondsFormat::Millis, true),
+            endpoints: self
+                .endpoints
+                .iter()
+                .map(|e| StatsEndpoint {
+                    nombre: e.nombre.to_string(),
+                    topico: e.topico.to_string(),
+                    queue_group: e.queue_group.to_string(),
+                    peticiones: e.estadisticas.peticiones,
+                    errores: e.estadisticas.errores,
+                    ultimo_error: e.estadisticas.ultimo_error.clone().unwrap_or_default(),
+                    tiempo_procesamiento: e.estadisticas.tiempo_procesamiento.as_nanos(),
+                    tiempo_procesamiento_promedio: e
+                        .estadisticas
+                        .tiempo_procesamiento_promedio()
+                        .as_nanos(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Los nombres de servicios y endpoints forman parte de los tópicos de
+/// descubrimiento, así que solo pueden tener letras, números, `-` y `_`
+fn nombre_valido(nombre: &str) -> bool {
+    !nombre.is_empty()
+        && nombre
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cliente::publicacion::Publicacion;
+
+    use super::{error::ErrorServicio, nombre_valido};
+
+    #[test]
+    fn nombres_validos() {
+        assert!(nombre_valido("camaras"));
+        assert!(nombre_valido("comandos_camaras-1"));
+        assert!(!nombre_valido(""));
+        assert!(!nombre_valido("camaras.comandos"));
+        assert!(!nombre_valido("camaras *"));
+    }
+
+    #[test]
+    fn error_en_headers_de_respuesta() {
+        let error = ErrorServicio::new(400, "Comando inválido");
+
+        let publicacion = Publicacion {
+            topico: "_INBOX.1".to_string(),
+            replay_to: None,
+            payload: Vec::new(),
+            header: Some(lib::serializables::Serializable::serializar(
+                &error.headers(),
+            )),
+        };
+
+        assert_eq!(ErrorServicio::desde_publicacion(&publicacion), Some(error));
+    }
+
+    #[test]
+    fn respuesta_sin_error() {
+        let publicacion = Publicacion {
+            topico: "_INBOX.1".to_string(),
+            replay_to: None,
+            payload: b"Ok".to_vec(),
+            header: None,
+        };
+
+        assert_eq!(ErrorServicio::desde_publicacion(&publicacion), None);
+    }
+}
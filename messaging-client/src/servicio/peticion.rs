@@ -0,0 +1,15 @@
+use std::time::Instant;
+
+/// Petición recibida por un endpoint del servicio
+#[derive(Debug)]
+pub struct Peticion {
+    /// Nombre del endpoint que recibió la petición
+    pub endpoint: String,
+    pub topico: String,
+    /// Tópico al que hay que responder. Si es `None` la petición no espera respuesta
+    pub replay_to: Option<String>,
+    pub payload: Vec<u8>,
+    pub header: Option<Vec<u8>>,
+    /// Momento en el que se leyó la petición, para medir el tiempo de procesamiento
+    pub(super) recibida: Instant,
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+pub const TIPO_PING: &str = "io.nats.micro.v1.ping_response";
+pub const TIPO_INFO: &str = "io.nats.micro.v1.info_response";
+pub const TIPO_STATS: &str = "io.nats.micro.v1.stats_response";
+
+/// Respuesta a `$SRV.PING`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RespuestaPing {
+    #[serde(rename = "type")]
+    pub tipo: String,
+    #[serde(rename = "name")]
+    pub nombre: String,
+    pub id: String,
+    pub version: String,
+}
+
+/// Respuesta a `$SRV.INFO`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RespuestaInfo {
+    #[serde(rename = "type")]
+    pub tipo: String,
+    #[serde(rename = "name")]
+    pub nombre: String,
+    pub id: String,
+    pub version: String,
+    #[serde(rename = "description")]
+    pub descripcion: String,
+    pub endpoints: Vec<InfoEndpoint>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct InfoEndpoint {
+    #[serde(rename = "name")]
+    pub nombre: String,
+    #[serde(rename = "subject")]
+    pub topico: String,
+    pub queue_group: String,
+}
+
+/// Respuesta a `$SRV.STATS`. Los tiempos están en nanosegundos
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RespuestaStats {
+    #[serde(rename = "type")]
+    pub tipo: String,
+    #[serde(rename = "name")]
+    pub nombre: String,
+    pub id: String,
+    pub version: String,
+    /// Fecha de inicio del servicio en formato RFC 3339
+    #[serde(rename = "started")]
+    pub inicio: String,
+    pub endpoints: Vec<StatsEndpoint>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatsEndpoint {
+    #[serde(rename = "name")]
+    pub nombre: String,
+    #[serde(rename = "subject")]
+    pub topico: String,
+    pub queue_group: String,
+    #[serde(rename = "num_requests")]
+    pub peticiones: u64,
+    #[serde(rename = "num_errors")]
+    pub errores: u64,
+    #[serde(rename = "last_error")]
+    pub ultimo_error: String,
+    #[serde(rename = "processing_time")]
+    pub tiempo_procesamiento: u128,
+    #[serde(rename = "average_processing_time")]
+    pub tiempo_procesamiento_promedio: u128,
+}
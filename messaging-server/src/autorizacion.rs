@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use lib::configuracion::Configuracion;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conexion::id::IdConexion,
+    sistema::{nuevo_id_aleatorio, PREFIJO_SISTEMA},
+};
+
+/// Tópico en el que el servidor pide autorizar a los usuarios que no están
+/// en el archivo de cuentas
+pub const TOPICO_AUTORIZACION: &str = "$SYS.REQ.USER.AUTH";
+
+/// Id de la suscripción interna con la que cada conexión espera su respuesta.
+/// Los clientes no pueden usarlo porque las conexiones sin autenticar no se
+/// pueden suscribir. Solo se aceptan las respuestas que publica la cuenta de
+/// sistema
+pub const SID_AUTORIZACION: &str = "$autorizacion";
+
+/// Segundos que se espera la respuesta del servicio por defecto
+pub const TIEMPO_LIMITE_POR_DEFECTO: f64 = 2.;
+
+/// `$SYS.AUTH.RESPUESTA.<id al azar>`, donde la conexión espera la
+/// respuesta. Es distinto en cada pedido para que nadie pueda adivinarlo
+pub fn nuevo_topico_respuesta() -> String {
+    format!(
+        "{}.AUTH.RESPUESTA.{}",
+        PREFIJO_SISTEMA,
+        nuevo_id_aleatorio()
+    )
+}
+
+/// Autorización de los usuarios con un servicio externo que responde en
+/// `$SYS.REQ.USER.AUTH`. Se activa con `autorizacion_externa=true`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutorizacionExterna {
+    /// Cuánto se espera la respuesta antes de rechazar al usuario
+    /// (`autorizacion_tiempo_limite`, en segundos)
+    pub tiempo_limite: Duration,
+}
+
+impl AutorizacionExterna {
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Option<Self> {
+        if !configuracion
+            .obtener::<bool>("autorizacion_externa")
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        // Si no entra en un `Duration` se usa el tiempo por defecto
+        let tiempo_limite: Duration = configuracion
+            .obtener::<f64>("autorizacion_tiempo_limite")
+            .and_then(|segundos| Duration::try_from_secs_f64(segundos.max(0.)).ok())
+            .unwrap_or(Duration::from_secs_f64(TIEMPO_LIMITE_POR_DEFECTO));
+
+        Some(Self { tiempo_limite })
+    }
+}
+
+/// Lo que se le envía al servicio de autorización
+#[derive(Debug, Serialize)]
+pub struct SolicitudAutorizacion {
+    pub conexion: IdConexion,
+    pub nombre: Option<String>,
+    pub user: Option<String>,
+    /// La contraseña va en texto plano: la recibe cualquiera que esté
+    /// suscrito a `$SYS.REQ.USER.AUTH`, es decir el usuario de la cuenta de
+    /// sistema
+    pub pass: Option<String>,
+}
+
+/// Lo que responde el servicio de autorización:
+/// `{"permitido": true, "permisos": {"publicar": ["drones.>"]}}`
+#[derive(Debug, Deserialize)]
+pub struct RespuestaAutorizacion {
+    pub permitido: bool,
+    #[serde(default)]
+    pub permisos: Permisos,
+    /// Por qué se rechazó al usuario, para los registros
+    #[serde(default)]
+    pub razon: Option<String>,
+}
+
+/// Tópicos en los que puede publicar y suscribirse un usuario. Sin lista se
+/// permite todo, salvo suscribirse a `$SYS`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Permisos {
+    #[serde(default)]
+    pub publicar: Option<Vec<String>>,
+    #[serde(default)]
+    pub suscribir: Option<Vec<String>>,
+}
+
+impl Permisos {
+    pub fn puede_publicar(&self, topico: &str) -> bool {
+        permitido(&self.publicar, topico)
+    }
+
+    /// El patrón tiene que estar contenido en alguno de los permitidos: con
+    /// `drones.>` se puede suscribir a `drones.*.estado` pero no a `>`
+    pub fn puede_suscribirse(&self, patron: &str) -> bool {
+        permitido(&self.suscribir, patron)
+    }
+}
+
+fn permitido(permisos: &Option<Vec<String>>, patron: &str) -> bool {
+    match permisos {
+        Some(permisos) => permisos.iter().any(|permiso| incluye(permiso, patron)),
+        None => true,
+    }
+}
+
+/// Si todos los tópicos de `patron` también son de `permiso`
+fn incluye(permiso: &str, patron: &str) -> bool {
+    let mut patron = patron.split('.');
+
+    for token in permiso.split('.') {
+        match (token, patron.next()) {
+            (">", Some(_)) => return true,
+            (_, None) => return false,
+            ("*", Some(siguiente)) if siguiente != ">" => {}
+            (token, Some(siguiente)) if token == siguiente => {}
+            _ => return false,
+        }
+    }
+
+    patron.next().is_none()
+}
+
+/// Una conexión que espera la respuesta del servicio de autorización
+#[derive(Debug)]
+pub struct AutorizacionPendiente {
+    pub limite: Instant,
+    pub respuesta: Option<Bytes>,
+}
+
+impl AutorizacionPendiente {
+    pub fn new(externa: AutorizacionExterna) -> Self {
+        Self {
+            limite: Instant::now() + externa.tiempo_limite,
+            respuesta: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permisos, RespuestaAutorizacion};
+
+    #[test]
+    fn probar_permisos() {
+        let respuesta: RespuestaAutorizacion = serde_json::from_str(
+            r#"{"permitido": true, "permisos": {"publicar": ["drones.*.estado", "camaras.>"]}}"#,
+        )
+        .unwrap();
+        assert!(respuesta.permitido);
+        let permisos: Permisos = respuesta.permisos;
+
+        assert!(permisos.puede_publicar("drones.1.estado"));
+        assert!(permisos.puede_publicar("camaras.1.video"));
+        assert!(!permisos.puede_publicar("drones.1.comando"));
+        assert!(!permisos.puede_publicar("camaras"));
+        assert!(!permisos.puede_publicar("drones.1.estado.extra"));
+        assert!(permisos.puede_suscribirse(">"));
+
+        let permisos = Permisos {
+            publicar: None,
+            suscribir: Some(vec!["drones.>".to_string(), "alertas.*".to_string()]),
+        };
+        assert!(permisos.puede_suscribirse("drones.*.estado"));
+        assert!(permisos.puede_suscribirse("drones.>"));
+        assert!(permisos.puede_suscribirse("alertas.*"));
+        assert!(!permisos.puede_suscribirse("alertas.>"));
+        assert!(!permisos.puede_suscribirse(">"));
+        assert!(!permisos.puede_suscribirse("*.1"));
+    }
+}
@@ -2,19 +2,36 @@ pub mod id;
 pub mod respuesta;
 pub mod tick_contexto;
 
+use lib::parseador::error_protocolo::ErrorProtocolo;
 use lib::parseador::mensaje::{formatear_mensaje_debug, formatear_payload_debug};
 use lib::parseador::parametros_info::ParametrosInfo;
 use lib::parseador::Parseador;
+use lib::topicos;
 use lib::{parseador::mensaje::Mensaje, stream::Stream};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{fmt::Debug, io};
 
+use bytes::{Buf, BytesMut};
 use chrono::{DateTime, Local};
 
+use crate::autorizacion::{
+    nuevo_topico_respuesta, AutorizacionExterna, AutorizacionPendiente, Permisos,
+    RespuestaAutorizacion, SolicitudAutorizacion, SID_AUTORIZACION, TOPICO_AUTORIZACION,
+};
 use crate::cuenta::Cuenta;
+use crate::limite_tasa::{LimiteTasa, LimitesCuentas, PoliticaTasa};
+use crate::limites::{LimitesServidor, LugarConexion};
 use crate::{
     publicacion::{mensaje::PublicacionMensaje, Publicacion},
-    registrador::Registrador,
+    recarga::Recarga,
+    registrador::{registro::NivelRegistro, Registrador},
+    sistema::{
+        es_topico_de_sistema,
+        estadisticas::{Contadores, Estadisticas},
+        evento::{EventoCliente, TipoEvento},
+    },
     suscripciones::{suscripcion::Suscripcion, topico::Topico},
 };
 
@@ -28,8 +45,10 @@ pub struct Conexion {
     registrador: Registrador,
     /// El parser se encarga de leer los bytes y generar mensajes
     parser: Parseador,
-    /// Tiempo del ultimo PING
+    /// Tiempo del ultimo PING, o de lo último que se recibió del cliente
     tiempo_ultimo_ping: DateTime<Local>,
+    /// PINGs enviados que el cliente todavía no respondió
+    pings_pendientes: u32,
 
     pub desconectado: bool,
 
@@ -39,6 +58,52 @@ pub struct Conexion {
 
     /// Cuentas de usuario
     pub cuentas: Option<Arc<Vec<Cuenta>>>,
+
+    /// Si el cliente pidió en el CONNECT que se validen estrictamente los tópicos
+    pub pedantico: bool,
+
+    /// Límites que se aplican a lo que envía y recibe la conexión
+    limites: LimitesServidor,
+    /// Suscripciones activas de la conexión (id -> patrón)
+    suscripciones: HashMap<String, String>,
+    /// Bytes que todavía no se pudieron escribir al stream (el cliente no los está leyendo)
+    pendientes: BytesMut,
+    /// Límite de tasa de las publicaciones de esta conexión
+    tasa: LimiteTasa,
+    /// Límites de tasa de todas las cuentas del servidor
+    limites_cuentas: LimitesCuentas,
+    /// Límite de tasa que comparte con las otras conexiones del mismo usuario
+    tasa_cuenta: Option<Arc<Mutex<LimiteTasa>>>,
+    /// Publicación que superó el límite de tasa y espera a que se pueda
+    /// publicar. Mientras tanto no se lee nada más de la conexión
+    demorado: Option<Mensaje>,
+    /// Lugar que ocupa la conexión entre las conexiones abiertas del servidor.
+    /// Se libera al descartar la conexión
+    lugar: Option<LugarConexion>,
+    /// Servicio que autoriza a los usuarios que no están en las cuentas
+    autorizacion_externa: Option<AutorizacionExterna>,
+    /// Respuesta del servicio de autorización que se está esperando. Mientras
+    /// tanto no se lee nada más de la conexión
+    autorizacion_pendiente: Option<AutorizacionPendiente>,
+    /// Si el usuario lo autorizó el servicio externo y no las cuentas
+    autorizado_externamente: bool,
+    /// Tópicos en los que puede publicar y suscribirse el usuario
+    permisos: Permisos,
+
+    /// Nombre que envió el cliente en el CONNECT
+    nombre: Option<String>,
+    /// Usuario con el que se autenticó el cliente
+    user: Option<String>,
+    /// Contraseña con la que se autenticó, para volver a validarla si cambian las cuentas
+    pass: Option<String>,
+    /// Si el usuario es de la cuenta de sistema
+    sistema: bool,
+    /// Mensajes y bytes de esta conexión
+    contadores: Contadores,
+    /// Mensajes y bytes de todo el servidor
+    estadisticas: Estadisticas,
+    /// Por qué se cerró la conexión (se informa en el evento de desconexión)
+    razon: Option<String>,
 }
 
 impl Conexion {
@@ -47,16 +112,52 @@ impl Conexion {
         stream: Box<dyn Stream>,
         registrador: Registrador,
         cuentas: Option<Arc<Vec<Cuenta>>>,
+        limites: LimitesServidor,
+    ) -> Self {
+        Self::con_autorizacion_externa(id, stream, registrador, cuentas, None, limites)
+    }
+
+    /// Igual que `new`, pero los usuarios que no están en las cuentas se
+    /// autorizan con el servicio externo. Se recibe al construir la conexión
+    /// porque el INFO inicial ya informa si se requiere autenticación
+    pub fn con_autorizacion_externa(
+        id: IdConexion,
+        stream: Box<dyn Stream>,
+        registrador: Registrador,
+        cuentas: Option<Arc<Vec<Cuenta>>>,
+        autorizacion_externa: Option<AutorizacionExterna>,
+        limites: LimitesServidor,
     ) -> Self {
         let mut con = Self {
             id,
             stream,
-            parser: Parseador::new(),
+            parser: Parseador::con_limites(limites.parseador()),
             registrador,
             tiempo_ultimo_ping: Local::now(),
+            pings_pendientes: 0,
             desconectado: false,
             autenticado: false,
             cuentas,
+            pedantico: false,
+            limites,
+            suscripciones: HashMap::new(),
+            pendientes: BytesMut::new(),
+            tasa: limites.tasa(),
+            limites_cuentas: LimitesCuentas::default(),
+            tasa_cuenta: None,
+            demorado: None,
+            lugar: None,
+            autorizacion_externa,
+            autorizacion_pendiente: None,
+            autorizado_externamente: false,
+            permisos: Permisos::default(),
+            nombre: None,
+            user: None,
+            pass: None,
+            sistema: false,
+            contadores: Contadores::default(),
+            estadisticas: Estadisticas::default(),
+            razon: None,
         };
 
         con.enviar_info();
@@ -64,28 +165,151 @@ impl Conexion {
         con
     }
 
+    /// Asocia la conexión al lugar que se le reservó en el servidor
+    pub fn ocupar_lugar(&mut self, lugar: LugarConexion) {
+        self.lugar = Some(lugar);
+    }
+
+    /// Los mensajes de la conexión se suman a las estadísticas del servidor
+    pub fn compartir_estadisticas(&mut self, estadisticas: Estadisticas) {
+        self.estadisticas = estadisticas;
+    }
+
+    /// Los límites de tasa de las cuentas, que se comparten entre todas las
+    /// conexiones del servidor
+    pub fn compartir_limites_cuentas(&mut self, limites_cuentas: LimitesCuentas) {
+        self.limites_cuentas = limites_cuentas;
+    }
+
+    /// El evento de sistema con los datos actuales de la conexión
+    pub fn evento(&self, id_servidor: &str) -> EventoCliente {
+        let mut evento = EventoCliente::new(id_servidor, self.id);
+        evento.nombre = self.nombre.clone();
+        evento.user = self.user.clone();
+        evento.suscripciones = self.suscripciones.values().cloned().collect();
+        evento.suscripciones.sort();
+        evento.contadores = self.contadores.clone();
+        evento.razon = self.razon.clone();
+        evento
+    }
+
+    /// Aplica las cuentas y los límites de la configuración recargada. Si ya
+    /// no existe la cuenta con la que se autenticó, se cierra la conexión.
+    /// Devuelve los ids de las suscripciones a `$SYS` que se eliminan porque
+    /// el usuario dejó de ser de la cuenta de sistema
+    pub fn recargar(&mut self, recarga: &Recarga) -> Vec<String> {
+        self.limites = recarga.limites;
+        self.parser.establecer_limites(recarga.limites.parseador());
+        self.tasa.cambiar(
+            recarga.limites.max_mensajes_por_segundo,
+            recarga.limites.max_bytes_por_segundo,
+        );
+        self.cuentas = recarga.cuentas.clone();
+
+        if !self.autenticado || self.desconectado {
+            return Vec::new();
+        }
+
+        self.asignar_tasa_cuenta();
+
+        // Los permisos del servicio externo valen hasta que se reconecte
+        if self.autorizado_externamente {
+            return Vec::new();
+        }
+
+        self.sistema = match &self.cuentas {
+            Some(cuentas) => {
+                let user = self.user.clone().unwrap_or_default();
+                let pass = self.pass.clone().unwrap_or_default();
+
+                match cuentas.iter().find(|cuenta| cuenta.matches(&user, &pass)) {
+                    Some(cuenta) => cuenta.sistema,
+                    None => {
+                        self.registrador.advertencia(
+                            "Se revocaron las credenciales del usuario",
+                            Some(self.id),
+                        );
+                        self.error_protocolo(ErrorProtocolo::ViolacionDeAutorizacion);
+                        return Vec::new();
+                    }
+                }
+            }
+            None => false,
+        };
+
+        if self.sistema {
+            return Vec::new();
+        }
+
+        let revocadas: Vec<String> = self
+            .suscripciones
+            .iter()
+            .filter(|(_, topico)| es_topico_de_sistema(topico))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in &revocadas {
+            if let Some(topico) = self.suscripciones.remove(id) {
+                self.error_protocolo(ErrorProtocolo::PermisoDeSuscripcion(topico));
+            }
+        }
+
+        revocadas
+    }
+
+    /// Cierra la conexión guardando el motivo para el evento de desconexión
+    fn desconectar(&mut self, razon: &str) {
+        if self.razon.is_none() {
+            self.razon = Some(razon.to_string());
+        }
+        self.desconectado = true;
+    }
+
     pub fn tick(&mut self, salida: &mut TickContexto) {
         if self.desconectado {
             return;
         }
+
+        // Se intenta enviar lo que quedó pendiente de ticks anteriores
+        if let Err(e) = self.vaciar_pendientes() {
+            self.registrador
+                .advertencia(&format!("Error al escribir al stream {}", e), Some(self.id));
+            self.desconectar("Error de escritura");
+            return;
+        }
+        // Lee los bytes del stream y los envía al parser. Con una publicación
+        // demorada no se lee, así el cliente se frena cuando se llena el socket
+        if self.demorado.is_none() && self.autorizacion_pendiente.is_none() {
+            self.leer_bytes();
+        }
+
         // Si hace falta enviar un PING o no
         if self.enviar_ping() {
+            if self.pings_pendientes >= self.limites.max_pings_out {
+                self.error_protocolo(ErrorProtocolo::ConexionInactiva);
+                return;
+            }
+
+            self.pings_pendientes += 1;
             _ = self.escribir_bytes(b"PING\r\n");
         }
 
-        // Lee los bytes del stream y los envía al parser
-        self.leer_bytes();
+        // Hasta que responda el servicio de autorización no se procesa nada más
+        if self.autorizacion_pendiente.is_some() {
+            self.revisar_autorizacion(salida);
+            return;
+        }
 
         // Lee mensaje y actua en consecuencia
         self.leer_mensajes(salida);
     }
 
-    /// Chequea si pasaron 20 segundos desde el ultimo PING enviado
+    /// Chequea si pasó `ping_interval` desde el ultimo PING enviado
     fn enviar_ping(&mut self) -> bool {
         let tiempo_actual = Local::now();
         let duracion_ultimo_ping = tiempo_actual.signed_duration_since(self.tiempo_ultimo_ping);
 
-        if duracion_ultimo_ping.num_seconds() >= 20 {
+        if duracion_ultimo_ping.to_std().unwrap_or_default() >= self.limites.ping_interval {
             self.tiempo_ultimo_ping = tiempo_actual;
             true
         } else {
@@ -93,31 +317,53 @@ impl Conexion {
         }
     }
 
-    /// Este método lo envia el Hilo cuando recibe un mensaje
-    pub fn escribir_publicacion_mensaje(&mut self, mensaje: &PublicacionMensaje) {
-        self.registrador
-            .info(&format!("MSG: {:?}", mensaje), Some(self.id));
+    /// Cualquier cosa que envíe el cliente demuestra que la conexión sigue
+    /// viva, así que no hace falta esperar el PONG ni enviar el próximo PING
+    fn registrar_actividad(&mut self) {
+        self.pings_pendientes = 0;
+        self.tiempo_ultimo_ping = Local::now();
+    }
+
+    /// Este método lo envia el Hilo cuando recibe un mensaje. Devuelve el
+    /// error si no se pudo escribir (para la traza del mensaje)
+    pub fn escribir_publicacion_mensaje(&mut self, mensaje: &PublicacionMensaje) -> io::Result<()> {
+        // La respuesta del servicio de autorización no es para el cliente
+        if mensaje.sid == SID_AUTORIZACION {
+            if !mensaje.de_sistema {
+                self.registrador.advertencia(
+                    "Se ignoró una respuesta de autorización que no publicó la cuenta de sistema",
+                    Some(self.id),
+                );
+            } else if let Some(pendiente) = self.autorizacion_pendiente.as_mut() {
+                pendiente.respuesta = Some(mensaje.payload.clone());
+            }
+            return Ok(());
+        }
 
-        if self.escribir_bytes(&mensaje.serializar_msg()).is_err() {
+        if self.registrador.habilitado(NivelRegistro::Trace) {
+            self.registrador
+                .trace(&format!("MSG: {:?}", mensaje), Some(self.id));
+        }
+
+        let bytes = mensaje.bytes_contenido();
+        self.contadores.sumar_enviado(bytes);
+        self.estadisticas.sumar_enviado(bytes);
+
+        let resultado: io::Result<()> = self.escribir_bytes(&mensaje.serializar_msg());
+        if resultado.is_err() {
             self.registrador
                 .advertencia("Error al enviar mensaje", Some(self.id));
         }
+        resultado
     }
 
-    /// Lee los bytes del stream y los envía al parser
+    /// Lee los bytes del stream directamente al buffer del parser
     pub fn leer_bytes(&mut self) {
-        let mut buffer = [0; 1024]; // 1kb
-                                    // 1. Leer una vez
-        match self.stream.read(&mut buffer) {
-            Ok(n) => {
-                if n == 0 {
-                    self.desconectado = true;
-                    return;
-                }
-
-                // 2. Enviar bytes a parser y leer nuevos mensajes generados
-                self.parser.agregar_bytes(&buffer[..n]);
+        match self.parser.leer_de(&mut *self.stream) {
+            Ok(0) => {
+                self.desconectar("Cliente cerrado");
             }
+            Ok(_) => self.registrar_actividad(),
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                 // No hay datos para leer (no hay que hacer nada acá)
             }
@@ -126,20 +372,58 @@ impl Conexion {
                     .error(&format!("Error al leer del stream {}", e), Some(self.id));
                 self.registrador.error("Error al leer bytes", Some(self.id));
 
-                self.desconectado = true;
+                self.desconectar("Error de lectura");
             }
         }
     }
 
-    /// Escribir al stream
+    /// Escribir al stream. Lo que el stream no acepta en el momento queda
+    /// pendiente para el próximo tick, hasta un máximo de `max_pending` bytes
     pub fn escribir_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
-        if let Err(e) = self.stream.write_all(bytes) {
+        self.pendientes.extend_from_slice(bytes);
+
+        if let Err(e) = self.vaciar_pendientes() {
             self.registrador
                 .advertencia(&format!("Error al escribir al stream {}", e), Some(self.id));
-            self.desconectado = true;
+            self.desconectar("Error de escritura");
             return Err(e);
         }
 
+        if self.pendientes.len() > self.limites.max_pending {
+            self.registrador.advertencia(
+                &format!(
+                    "Consumidor lento: {} bytes sin leer, se desconecta",
+                    self.pendientes.len()
+                ),
+                Some(self.id),
+            );
+
+            // Lo que no se envió se descarta, y en su lugar se intenta avisar
+            // al cliente antes de cerrar
+            self.pendientes.clear();
+            let error = Respuesta::Err(Some(ErrorProtocolo::ConsumidorLento.to_string()));
+            self.pendientes.extend_from_slice(&error.serializar());
+            let _ = self.vaciar_pendientes();
+
+            self.desconectar("Slow Consumer");
+            return Err(io::Error::other(ErrorProtocolo::ConsumidorLento.mensaje()));
+        }
+
+        Ok(())
+    }
+
+    /// Escribe al stream todo lo pendiente que acepte sin bloquear
+    fn vaciar_pendientes(&mut self) -> io::Result<()> {
+        while !self.pendientes.is_empty() {
+            match self.stream.write(&self.pendientes) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.pendientes.advance(n),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
         Ok(())
     }
 
@@ -160,22 +444,46 @@ impl Conexion {
     }
 
     pub fn enviar_info(&mut self) {
-        let require_auth = self.cuentas.is_some();
+        let require_auth = self.cuentas.is_some() || self.autorizacion_externa.is_some();
         self.escribir_respuesta(&Respuesta::Info(ParametrosInfo {
             requiere_auth: Some(require_auth),
+            connect_urls: None,
+            max_payload: Some(self.limites.max_payload),
         }));
     }
 
     pub fn leer_mensajes(&mut self, contexto: &mut TickContexto) {
-        while let Some(mensaje) = self.parser.proximo_mensaje() {
-            self.registrador.info(
-                &format!("Mensaje recibido: {:?}", formatear_mensaje_debug(&mensaje)),
-                Some(self.id),
-            );
+        loop {
+            // Primero se reintenta la publicación demorada por el límite de tasa
+            let (mensaje, demorado) = match self.demorado.take() {
+                Some(mensaje) => (mensaje, true),
+                None => match self.parser.proximo_mensaje() {
+                    Some(mensaje) => (mensaje, false),
+                    None => return,
+                },
+            };
+
+            if self.registrador.habilitado(NivelRegistro::Trace) && !demorado {
+                self.registrador.trace(
+                    &format!("Mensaje recibido: {:?}", formatear_mensaje_debug(&mensaje)),
+                    Some(self.id),
+                );
+            }
+
+            // Después de un error fatal no se puede seguir leyendo la conexión
+            if let Mensaje::ErrorFatal(error) = mensaje {
+                self.error_protocolo(error);
+                return;
+            }
 
             if !self.autenticado {
                 match mensaje {
                     Mensaje::Conectar(parametros) => {
+                        self.pedantico = parametros.pedantic.unwrap_or(false);
+                        self.nombre = parametros.name.clone();
+                        self.user = parametros.user.clone();
+                        self.pass = parametros.pass.clone();
+
                         if let Some(cuentas) = &self.cuentas {
                             for cuenta in cuentas.iter() {
                                 if cuenta.matches(&parametros.user_str(), &parametros.pass_str()) {
@@ -185,65 +493,144 @@ impl Conexion {
                                     );
 
                                     self.autenticado = true;
+                                    self.sistema = cuenta.sistema;
+                                    self.asignar_tasa_cuenta();
+                                    contexto.evento(TipoEvento::Conexion);
                                     self.escribir_respuesta(&Respuesta::Ok(Some(
                                         "connect".to_string(),
                                     )));
                                     return;
                                 }
                             }
+                        }
 
-                            self.escribir_err(Some("Usuario o contraseña incorrectos".to_string()));
-                            self.desconectado = true;
+                        if let Some(externa) = self.autorizacion_externa {
+                            self.solicitar_autorizacion(externa, contexto);
+                            return;
+                        }
+
+                        if self.cuentas.is_some() {
+                            self.rechazar_usuario("Usuario o contraseña incorrectos", contexto);
                             return;
                         }
 
                         self.autenticado = true;
+                        self.asignar_tasa_cuenta();
+                        contexto.evento(TipoEvento::Conexion);
                         self.escribir_respuesta(&Respuesta::Ok(Some("connect".to_string())));
                     }
                     _ => {
-                        self.escribir_err(Some(
-                            "Primero debe enviar un mensaje de conexión".to_string(),
-                        ));
-                        self.desconectado = true;
+                        self.registrador.advertencia(
+                            "Primero debe enviar un mensaje de conexión",
+                            Some(self.id),
+                        );
+                        self.error_protocolo(ErrorProtocolo::ViolacionDeAutorizacion);
                         return;
                     }
                 }
                 continue;
             }
 
+            let bytes_publicados: Option<usize> = match &mensaje {
+                Mensaje::Publicar(_, _, payload) => Some(payload.len()),
+                Mensaje::PublicarConHeader(_, _, headers, payload) => {
+                    Some(headers.len() + payload.len())
+                }
+                _ => None,
+            };
+
+            if let Some(bytes) = bytes_publicados {
+                if !self.respetar_tasa(bytes) {
+                    if !demorado {
+                        self.contadores.sumar_limitado();
+                        self.estadisticas.sumar_limitado();
+                    }
+
+                    match self.limites.politica_tasa {
+                        PoliticaTasa::Demorar => {
+                            // El cliente sigue activo aunque no se lea lo que envía
+                            self.registrar_actividad();
+                            self.demorado = Some(mensaje);
+                            return;
+                        }
+                        PoliticaTasa::Error => {
+                            self.escribir_err(Some(ErrorProtocolo::LimiteDeTasa.to_string()));
+                            continue;
+                        }
+                    }
+                }
+            }
+
             // proximo mensaje va a leer los bytes nuevos y devuelve si es una accion valida
             match mensaje {
+                Mensaje::Publicar(subject, ..) | Mensaje::PublicarConHeader(subject, ..)
+                    if !self.topico_de_publicacion_valido(&subject) =>
+                {
+                    self.error_protocolo(ErrorProtocolo::TopicoDePublicacionInvalido);
+                }
+                // Solo el servidor (y la cuenta de sistema) publica en `$SYS`
+                Mensaje::Publicar(subject, ..) | Mensaje::PublicarConHeader(subject, ..)
+                    if (es_topico_de_sistema(&subject) && !self.sistema)
+                        || !self.permisos.puede_publicar(&subject) =>
+                {
+                    self.error_protocolo(ErrorProtocolo::PermisoDePublicacion(subject));
+                }
                 Mensaje::Publicar(subject, replay_to, payload) => {
-                    self.registrador.info(
-                        &format!(
-                            "Publicación: {:?} {:?} {:?}",
-                            subject,
-                            replay_to,
-                            formatear_payload_debug(&payload)
-                        ),
-                        Some(self.id),
-                    );
+                    if self.registrador.habilitado(NivelRegistro::Trace) {
+                        self.registrador.trace(
+                            &format!(
+                                "Publicación: {:?} {:?} {:?}",
+                                subject,
+                                replay_to,
+                                formatear_payload_debug(&payload)
+                            ),
+                            Some(self.id),
+                        );
+                    }
 
-                    contexto.publicar(Publicacion::new(subject, payload, None, replay_to));
+                    self.sumar_recibido(payload.len());
+                    contexto.publicar(
+                        Publicacion::new(subject, payload, None, replay_to)
+                            .con_origen_sistema(self.sistema),
+                    );
                     self.escribir_ok(Some("pub".to_string()));
                 }
                 Mensaje::PublicarConHeader(subject, replay_to, headers, payload) => {
-                    self.registrador.info(
-                        &format!(
-                            "Publicación con header: {:?} {:?} {:?} {:?}",
-                            subject,
-                            headers,
-                            replay_to,
-                            formatear_payload_debug(&payload)
-                        ),
-                        Some(self.id),
-                    );
+                    if self.registrador.habilitado(NivelRegistro::Trace) {
+                        self.registrador.trace(
+                            &format!(
+                                "Publicación con header: {:?} {:?} {:?} {:?}",
+                                subject,
+                                headers,
+                                replay_to,
+                                formatear_payload_debug(&payload)
+                            ),
+                            Some(self.id),
+                        );
+                    }
 
-                    contexto.publicar(Publicacion::new(subject, payload, Some(headers), replay_to));
+                    self.sumar_recibido(headers.len() + payload.len());
+                    contexto.publicar(
+                        Publicacion::new(subject, payload, Some(headers), replay_to)
+                            .con_origen_sistema(self.sistema),
+                    );
                     self.escribir_ok(Some("hpub".to_string()));
                 }
-                Mensaje::Suscribir(topico, grupo, id) => match Topico::new(topico) {
+                Mensaje::Suscribir(_, _, id)
+                    if !self.suscripciones.contains_key(&id)
+                        && self.suscripciones.len() >= self.limites.max_suscripciones =>
+                {
+                    self.escribir_err(Some(ErrorProtocolo::MaximoSuscripciones.to_string()));
+                }
+                Mensaje::Suscribir(topico, ..)
+                    if (es_topico_de_sistema(&topico) && !self.sistema)
+                        || !self.permisos.puede_suscribirse(&topico) =>
+                {
+                    self.error_protocolo(ErrorProtocolo::PermisoDeSuscripcion(topico));
+                }
+                Mensaje::Suscribir(topico, grupo, id) => match self.topico_de_suscripcion(topico) {
                     Ok(topico) => {
+                        self.suscripciones.insert(id.clone(), topico.a_texto());
                         contexto.suscribir(Suscripcion::new(
                             contexto.id_hilo,
                             self.id,
@@ -253,33 +640,210 @@ impl Conexion {
                         ));
                         self.escribir_ok(Some("sub".to_string()));
                     }
-                    Err(_) => {
-                        self.escribir_err(Some("Tópico de subscripción incorrecto".to_string()));
+                    Err(error) => {
+                        self.registrador.advertencia(
+                            &format!("Tópico de suscripción incorrecto: {}", error),
+                            Some(self.id),
+                        );
+                        self.error_protocolo(ErrorProtocolo::TopicoInvalido);
                     }
                 },
                 Mensaje::Desuscribir(id, _max_msgs) => {
+                    self.suscripciones.remove(&id);
                     contexto.desuscribir(id);
                     self.escribir_ok(Some("unsub".to_string()));
                 }
-                Mensaje::Error(msg) => {
-                    // self.respuestas.push(Respuesta::Err(msg));
-                    self.escribir_err(Some(msg));
-                }
                 Mensaje::Conectar(_) => {
                     self.escribir_err(Some("Ya se recibió un mensaje de conexión".to_string()));
                 }
                 Mensaje::Ping() => {
                     self.escribir_respuesta(&Respuesta::Pong());
                 }
+                // Respuesta a los PING del servidor
+                Mensaje::Pong() => self.pings_pendientes = 0,
                 _ => {
-                    self.escribir_respuesta(&Respuesta::Err(Some(
-                        "Mensaje no reconocido".to_string(),
-                    )));
+                    self.error_protocolo(ErrorProtocolo::OperacionDesconocida);
+                    if self.desconectado {
+                        return;
+                    }
                 }
             }
         }
     }
 
+    /// Le pide al servicio externo que autorice al usuario del CONNECT. La
+    /// respuesta llega a una suscripción interna de la conexión
+    fn solicitar_autorizacion(
+        &mut self,
+        externa: AutorizacionExterna,
+        contexto: &mut TickContexto,
+    ) {
+        let topico: String = nuevo_topico_respuesta();
+        let suscripcion: Topico = match Topico::new(topico.clone()) {
+            Ok(suscripcion) => suscripcion,
+            Err(_) => {
+                self.rechazar_usuario("Tópico de autorización inválido", contexto);
+                return;
+            }
+        };
+        contexto.suscribir(Suscripcion::new(
+            contexto.id_hilo,
+            self.id,
+            suscripcion,
+            SID_AUTORIZACION.to_string(),
+            None,
+        ));
+
+        let solicitud = SolicitudAutorizacion {
+            conexion: self.id,
+            nombre: self.nombre.clone(),
+            user: self.user.clone(),
+            pass: self.pass.clone(),
+        };
+        let json: String = serde_json::to_string(&solicitud).unwrap_or("{}".to_string());
+        contexto.publicar(Publicacion::new(
+            TOPICO_AUTORIZACION.to_string(),
+            json.into(),
+            None,
+            Some(topico),
+        ));
+
+        self.registrador.debug(
+            "Se pidió la autorización del usuario al servicio externo",
+            Some(self.id),
+        );
+        self.autorizacion_pendiente = Some(AutorizacionPendiente::new(externa));
+    }
+
+    /// Aplica la respuesta del servicio de autorización, si ya llegó, o
+    /// rechaza al usuario si pasó el tiempo límite
+    fn revisar_autorizacion(&mut self, contexto: &mut TickContexto) {
+        let respuesta = match &self.autorizacion_pendiente {
+            Some(pendiente) => match &pendiente.respuesta {
+                Some(respuesta) => Some(respuesta.clone()),
+                None if Instant::now() < pendiente.limite => return,
+                None => None,
+            },
+            None => return,
+        };
+
+        self.autorizacion_pendiente = None;
+        contexto.desuscribir(SID_AUTORIZACION.to_string());
+
+        let respuesta: RespuestaAutorizacion = match respuesta
+            .map(|respuesta| serde_json::from_slice::<RespuestaAutorizacion>(&respuesta))
+        {
+            Some(Ok(respuesta)) => respuesta,
+            Some(Err(e)) => {
+                self.rechazar_usuario(
+                    &format!("Respuesta de autorización inválida: {}", e),
+                    contexto,
+                );
+                return;
+            }
+            None => {
+                self.rechazar_usuario("No respondió el servicio de autorización", contexto);
+                return;
+            }
+        };
+
+        if !respuesta.permitido {
+            let razon: String = respuesta.razon.unwrap_or_else(|| "sin razón".to_string());
+            self.rechazar_usuario(&format!("Usuario rechazado: {}", razon), contexto);
+            return;
+        }
+
+        self.registrador.info(
+            &format!(
+                "Usuario autorizado por el servicio externo: {}",
+                self.user.clone().unwrap_or_default()
+            ),
+            Some(self.id),
+        );
+
+        self.autenticado = true;
+        self.autorizado_externamente = true;
+        self.permisos = respuesta.permisos;
+        self.asignar_tasa_cuenta();
+        contexto.evento(TipoEvento::Conexion);
+        self.escribir_respuesta(&Respuesta::Ok(Some("connect".to_string())));
+    }
+
+    fn rechazar_usuario(&mut self, motivo: &str, contexto: &mut TickContexto) {
+        self.registrador.advertencia(motivo, Some(self.id));
+        self.error_protocolo(ErrorProtocolo::ViolacionDeAutorizacion);
+        contexto.evento(TipoEvento::ErrorAutenticacion);
+    }
+
+    /// Le informa el error al cliente y, si es fatal, cierra la conexión
+    fn error_protocolo(&mut self, error: ErrorProtocolo) {
+        self.registrador
+            .advertencia(&format!("Error de protocolo: {}", error), Some(self.id));
+        self.escribir_err(Some(error.to_string()));
+
+        if error.es_fatal() {
+            self.desconectar(error.mensaje().trim_matches('\''));
+        }
+    }
+
+    /// Busca el límite de tasa de la cuenta del usuario autenticado
+    fn asignar_tasa_cuenta(&mut self) {
+        self.tasa_cuenta = self
+            .user
+            .as_deref()
+            .and_then(|user| self.limites_cuentas.de_usuario(user));
+    }
+
+    /// Si la conexión y su cuenta pueden publicar `bytes` bytes ahora. Si
+    /// pueden, se descuentan de los dos límites
+    fn respetar_tasa(&mut self, bytes: usize) -> bool {
+        let mut tasa_cuenta = self.tasa_cuenta.as_ref().and_then(|tasa| tasa.lock().ok());
+
+        let permite: bool =
+            self.tasa.permite(bytes) && tasa_cuenta.as_mut().is_none_or(|tasa| tasa.permite(bytes));
+
+        if permite {
+            self.tasa.consumir(bytes);
+            if let Some(tasa) = tasa_cuenta.as_mut() {
+                tasa.consumir(bytes);
+            }
+        }
+
+        permite
+    }
+
+    fn sumar_recibido(&mut self, bytes: usize) {
+        self.contadores.sumar_recibido(bytes);
+        self.estadisticas.sumar_recibido(bytes);
+    }
+
+    fn topico_de_publicacion_valido(&self, topico: &str) -> bool {
+        match topicos::validar_publicacion(topico, self.pedantico) {
+            Ok(()) => true,
+            Err(error) => {
+                self.registrador.advertencia(
+                    &format!("Tópico de publicación incorrecto: {}", error),
+                    Some(self.id),
+                );
+                false
+            }
+        }
+    }
+
+    /// En modo pedante el patrón se valida de forma estricta antes de crear el tópico
+    fn topico_de_suscripcion(&self, patron: String) -> Result<Topico, topicos::ErrorTopico> {
+        if self.pedantico {
+            topicos::validar_suscripcion(&patron, true)?;
+        }
+
+        Topico::new(patron)
+    }
+
+    /// Bytes que todavía no se pudieron escribir al stream
+    pub fn bytes_pendientes(&self) -> usize {
+        self.pendientes.len()
+    }
+
     pub fn esta_conectado(&self) -> bool {
         !self.desconectado
     }
@@ -297,11 +861,29 @@ impl Debug for Conexion {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
-    use lib::{serializables::deserializar_vec, stream::mock_handler::MockHandler};
-
-    use crate::registrador::Registrador;
+    use std::{
+        io::{self, Read, Write},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use lib::{
+        serializables::deserializar_vec,
+        stream::{mock_handler::MockHandler, Stream},
+    };
+
+    use crate::{
+        autorizacion::{AutorizacionExterna, SID_AUTORIZACION, TOPICO_AUTORIZACION},
+        cuenta::Cuenta,
+        limites::LimitesServidor,
+        publicacion::mensaje::PublicacionMensaje,
+        recarga::Recarga,
+        registrador::Registrador,
+        sistema::evento::TipoEvento,
+    };
 
     use super::{tick_contexto::TickContexto, Conexion};
 
@@ -312,7 +894,13 @@ mod tests {
         let registrador = Registrador::new();
 
         // Conexion representa el cliente del lado del servidor
-        Conexion::new(1, Box::new(stream), registrador, None);
+        Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesServidor::default(),
+        );
 
         assert!(control
             .intentar_recibir_string()
@@ -326,7 +914,13 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new();
 
-        let mut con = Conexion::new(1, Box::new(stream), registrador, None);
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesServidor::default(),
+        );
 
         mock.escribir_bytes(b"CONNECT {}\r\n");
 
@@ -343,7 +937,13 @@ mod tests {
 
         let cuentas = deserializar_vec("1,admin,1234".as_bytes()).unwrap();
 
-        let mut con = Conexion::new(1, Box::new(stream), registrador, Some(Arc::new(cuentas)));
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            Some(Arc::new(cuentas)),
+            LimitesServidor::default(),
+        );
 
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"1234\"}\r\n");
 
@@ -358,7 +958,13 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new();
 
-        let mut con = Conexion::new(1, Box::new(stream), registrador, None);
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesServidor::default(),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -379,7 +985,13 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new();
 
-        let mut con = Conexion::new(1, Box::new(stream), registrador, None);
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesServidor::default(),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -394,7 +1006,7 @@ mod tests {
 
         assert_eq!(contexto.publicaciones.len(), 1);
         assert_eq!(contexto.publicaciones[0].topico, "x");
-        assert_eq!(contexto.publicaciones[0].payload, b"hola");
+        assert_eq!(contexto.publicaciones[0].payload, &b"hola"[..]);
     }
 
     #[test]
@@ -402,7 +1014,13 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new();
 
-        let mut con = Conexion::new(1, Box::new(stream), registrador, None);
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesServidor::default(),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -416,4 +1034,448 @@ mod tests {
         assert_eq!(contexto.desuscripciones.len(), 1);
         assert_eq!(contexto.desuscripciones[0], "1");
     }
+
+    #[test]
+    fn probar_limites() {
+        let (mut mock, stream) = MockHandler::new();
+        let limites = LimitesServidor {
+            max_payload: 8,
+            max_suscripciones: 1,
+            ..LimitesServidor::default()
+        };
+
+        let mut con = Conexion::new(1, Box::new(stream), Registrador::new(), None, limites);
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("\"max_payload\":8"));
+
+        mock.escribir_bytes(b"CONNECT {}\r\nSUB x 1\r\nSUB y 2\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert_eq!(contexto.suscripciones.len(), 1);
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Maximum Subscriptions Exceeded'"));
+        assert!(con.esta_conectado());
+
+        mock.escribir_bytes(b"PUB x 9\r\n123456789\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert!(contexto.publicaciones.is_empty());
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Maximum Payload Violation'"));
+        assert!(!con.esta_conectado());
+    }
+
+    #[test]
+    fn probar_topicos_en_modo_pedante() {
+        let (mut mock, stream) = MockHandler::new();
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            Registrador::new(),
+            None,
+            LimitesServidor::default(),
+        );
+        mock.intentar_recibir_string();
+
+        mock.escribir_bytes(b"CONNECT {\"pedantic\": true}\r\nSUB a..b 1\r\nPUB a.* 0\r\n\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        let respuestas = mock.intentar_recibir_string().unwrap();
+        assert!(con.pedantico);
+        assert!(respuestas.contains("-ERR 'Invalid Subject'"));
+        assert!(respuestas.contains("-ERR 'Invalid Publish Subject'"));
+        assert!(contexto.suscripciones.is_empty() && contexto.publicaciones.is_empty());
+        assert!(con.esta_conectado());
+
+        mock.escribir_bytes(b"FOO bar\r\n");
+        con.tick(&mut TickContexto::new(0, 1));
+
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Unknown Protocol Operation'"));
+        assert!(!con.esta_conectado());
+    }
+
+    /// Un cliente que, cuando es `lento`, lee de a poco: cada escritura que
+    /// el socket acepta (de hasta 30 bytes) llena su buffer, y la siguiente
+    /// bloquea
+    struct StreamLento {
+        escritos: Arc<Mutex<Vec<u8>>>,
+        lento: Arc<AtomicBool>,
+        lleno: bool,
+    }
+
+    impl Stream for StreamLento {}
+
+    impl Read for StreamLento {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl Write for StreamLento {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut largo = buf.len();
+
+            if self.lento.load(Ordering::Relaxed) {
+                self.lleno = !self.lleno;
+                if !self.lleno {
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                largo = largo.min(30);
+            }
+
+            self.escritos
+                .lock()
+                .unwrap()
+                .extend_from_slice(&buf[..largo]);
+            Ok(largo)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn probar_consumidor_lento() {
+        let escritos = Arc::new(Mutex::new(Vec::new()));
+        let lento = Arc::new(AtomicBool::new(false));
+        let stream = StreamLento {
+            escritos: escritos.clone(),
+            lento: lento.clone(),
+            lleno: false,
+        };
+        let limites = LimitesServidor {
+            max_pending: 50,
+            ..LimitesServidor::default()
+        };
+        let mut con = Conexion::new(1, Box::new(stream), Registrador::new(), None, limites);
+        escritos.lock().unwrap().clear();
+        lento.store(true, Ordering::Relaxed);
+
+        // Se acumulan 10 y después 40 bytes pendientes, sin pasar el máximo
+        assert!(con.escribir_bytes(&[b'x'; 40]).is_ok());
+        assert!(con.escribir_bytes(&[b'x'; 60]).is_ok());
+        assert!(con.esta_conectado());
+
+        // 70 bytes pendientes: se descartan y se avisa antes de desconectar
+        assert!(con.escribir_bytes(&[b'x'; 60]).is_err());
+        assert!(!con.esta_conectado());
+
+        let escritos = String::from_utf8(escritos.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            escritos,
+            format!("{}-ERR 'Slow Consumer'\r\n", "x".repeat(90))
+        );
+    }
+
+    #[test]
+    fn probar_conexion_inactiva() {
+        let (mut mock, stream) = MockHandler::new();
+        let limites = LimitesServidor {
+            ping_interval: Duration::ZERO,
+            max_pings_out: 2,
+            ..LimitesServidor::default()
+        };
+        let mut con = Conexion::new(1, Box::new(stream), Registrador::new(), None, limites);
+        mock.intentar_recibir_string();
+
+        // Cualquier mensaje del cliente cuenta como respuesta
+        for _ in 0..2 {
+            con.tick(&mut TickContexto::new(0, 1));
+        }
+        mock.escribir_bytes(b"CONNECT {}\r\n");
+        con.tick(&mut TickContexto::new(0, 1));
+        assert_eq!(con.pings_pendientes, 1);
+
+        con.tick(&mut TickContexto::new(0, 1));
+        con.tick(&mut TickContexto::new(0, 1));
+        assert!(!con.esta_conectado());
+
+        let respuestas = mock.intentar_recibir_string().unwrap();
+        assert_eq!(respuestas.matches("PING").count(), 4);
+        assert!(respuestas.ends_with("-ERR 'Stale Connection'\r\n"));
+    }
+
+    #[test]
+    fn probar_eventos_de_sistema() {
+        let mut cuentas: Vec<Cuenta> =
+            deserializar_vec("1,admin,1234\n2,sys,1234".as_bytes()).unwrap();
+        cuentas[1].sistema = true;
+        let cuentas = Arc::new(cuentas);
+
+        // Un usuario común no puede suscribirse a los eventos del servidor
+        let (mut mock, stream) = MockHandler::new();
+        let limites = LimitesServidor::default();
+        let mut con = Conexion::new(
+            1,
+            Box::new(stream),
+            Registrador::new(),
+            Some(cuentas.clone()),
+            limites,
+        );
+        mock.escribir_bytes(
+            b"CONNECT {\"user\": \"admin\", \"pass\": \"1234\", \"name\": \"cli\"}\r\n",
+        );
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        assert_eq!(contexto.eventos, vec![TipoEvento::Conexion]);
+        mock.intentar_recibir_string();
+
+        mock.escribir_bytes(b"SUB $SYS.> 1\r\nSUB x 2\r\nPUB x 4\r\nhola\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        assert_eq!(contexto.suscripciones.len(), 1);
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Permissions Violation for Subscription to $SYS.>'"));
+        assert!(con.esta_conectado());
+
+        let evento = con.evento("S");
+        assert_eq!(evento.nombre.as_deref(), Some("cli"));
+        assert_eq!(evento.user.as_deref(), Some("admin"));
+        assert_eq!(evento.suscripciones, vec!["x".to_string()]);
+        assert_eq!(evento.contadores.bytes_recibidos, 4);
+
+        // Ni publicar eventos falsos
+        mock.escribir_bytes(
+            b"PUB $SYS.SERVER.S.CLIENT.CONNECT 2\r\n{}\r\nHPUB $SYS.SERVER.S.STATSZ 12 12\r\nNATS/1.0\r\n\r\n\r\n",
+        );
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        assert!(contexto.publicaciones.is_empty());
+        let respuestas = mock.intentar_recibir_string().unwrap();
+        assert!(respuestas
+            .contains("-ERR 'Permissions Violation for Publish to $SYS.SERVER.S.CLIENT.CONNECT'"));
+        assert!(
+            respuestas.contains("-ERR 'Permissions Violation for Publish to $SYS.SERVER.S.STATSZ'")
+        );
+        assert!(con.esta_conectado());
+
+        // El usuario de sistema sí puede
+        let (mut mock, stream) = MockHandler::new();
+        let mut con = Conexion::new(
+            2,
+            Box::new(stream),
+            Registrador::new(),
+            Some(cuentas.clone()),
+            limites,
+        );
+        mock.escribir_bytes(b"CONNECT {\"user\": \"sys\", \"pass\": \"1234\"}\r\nSUB $SYS.> 1\r\n");
+        con.tick(&mut TickContexto::new(0, 2));
+        mock.escribir_bytes(b"PUB $SYS.REQ.X 0\r\n\r\n");
+        let mut contexto = TickContexto::new(0, 2);
+        con.tick(&mut contexto);
+        assert_eq!(contexto.suscripciones.len(), 1);
+        assert_eq!(contexto.publicaciones.len(), 1);
+
+        // Credenciales incorrectas
+        let (mut mock, stream) = MockHandler::new();
+        let mut con = Conexion::new(
+            3,
+            Box::new(stream),
+            Registrador::new(),
+            Some(cuentas),
+            limites,
+        );
+        mock.escribir_bytes(b"CONNECT {\"user\": \"sys\", \"pass\": \"mal\"}\r\n");
+        let mut contexto = TickContexto::new(0, 3);
+        con.tick(&mut contexto);
+        assert_eq!(contexto.eventos, vec![TipoEvento::ErrorAutenticacion]);
+        assert_eq!(
+            con.evento("S").razon.as_deref(),
+            Some("Authorization Violation")
+        );
+    }
+
+    #[test]
+    fn probar_recarga_de_cuentas() {
+        let mut cuentas: Vec<Cuenta> =
+            deserializar_vec("1,admin,1234\n2,sys,1234".as_bytes()).unwrap();
+        cuentas[1].sistema = true;
+        let limites = LimitesServidor::default();
+
+        let (mut mock, stream) = MockHandler::new();
+        let mut sys = Conexion::new(
+            1,
+            Box::new(stream),
+            Registrador::new(),
+            Some(Arc::new(cuentas.clone())),
+            limites,
+        );
+        mock.escribir_bytes(b"CONNECT {\"user\": \"sys\", \"pass\": \"1234\"}\r\n");
+        sys.tick(&mut TickContexto::new(0, 1));
+        mock.escribir_bytes(b"SUB $SYS.> 1\r\nSUB x 2\r\n");
+        sys.tick(&mut TickContexto::new(0, 1));
+
+        let (mut mock, stream) = MockHandler::new();
+        let mut admin = Conexion::new(
+            2,
+            Box::new(stream),
+            Registrador::new(),
+            Some(Arc::new(cuentas.clone())),
+            limites,
+        );
+        mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"1234\"}\r\n");
+        admin.tick(&mut TickContexto::new(0, 2));
+
+        // sys deja de ser de la cuenta de sistema y admin cambia de contraseña
+        cuentas[0].pass = "otra".to_string();
+        cuentas[1].sistema = false;
+        let recarga = Recarga {
+            cuentas: Some(Arc::new(cuentas)),
+            limites,
+            mapeos: Default::default(),
+        };
+
+        assert_eq!(sys.recargar(&recarga), vec!["1".to_string()]);
+        assert!(sys.esta_conectado());
+        assert!(admin.recargar(&recarga).is_empty());
+        assert!(!admin.esta_conectado());
+    }
+
+    #[test]
+    fn probar_autorizacion_externa() {
+        let cuentas = deserializar_vec("1,admin,1234".as_bytes()).unwrap();
+        let cuentas_compartidas = Arc::new(cuentas);
+        let externa = AutorizacionExterna {
+            tiempo_limite: Duration::from_secs(60),
+        };
+
+        let (mut mock, stream) = MockHandler::new();
+        let mut con = Conexion::con_autorizacion_externa(
+            1,
+            Box::new(stream),
+            Registrador::new(),
+            Some(cuentas_compartidas.clone()),
+            Some(externa),
+            LimitesServidor::default(),
+        );
+        mock.intentar_recibir_string();
+
+        // El usuario no está en las cuentas: se le pregunta al servicio
+        mock.escribir_bytes(b"CONNECT {\"user\": \"dron\", \"pass\": \"x\"}\r\nPUB a 0\r\n\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert!(!con.autenticado);
+        assert_eq!(contexto.suscripciones.len(), 1);
+        assert_eq!(contexto.publicaciones.len(), 1);
+        let solicitud = &contexto.publicaciones[0];
+        assert_eq!(solicitud.topico, TOPICO_AUTORIZACION);
+        assert!(String::from_utf8_lossy(&solicitud.payload).contains("\"user\":\"dron\""));
+        let respuesta: String = solicitud.replay_to.clone().unwrap();
+        assert!(respuesta.starts_with("$SYS.AUTH.RESPUESTA."));
+        assert_ne!(respuesta, "$SYS.AUTH.RESPUESTA.1");
+
+        // Un usuario que no es de sistema no puede responder por el servicio
+        let (mut otro, stream) = MockHandler::new();
+        let mut falso = Conexion::new(
+            3,
+            Box::new(stream),
+            Registrador::new(),
+            Some(cuentas_compartidas.clone()),
+            LimitesServidor::default(),
+        );
+        otro.escribir_bytes(
+            format!(
+                "CONNECT {{\"user\": \"admin\", \"pass\": \"1234\"}}\r\nPUB {} 19\r\n{{\"permitido\": true}}\r\n",
+                respuesta
+            )
+            .as_bytes(),
+        );
+        let mut contexto = TickContexto::new(0, 3);
+        falso.tick(&mut contexto);
+        falso.tick(&mut contexto);
+        assert!(contexto.publicaciones.is_empty());
+        assert!(otro
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Permissions Violation for Publish to $SYS.AUTH.RESPUESTA."));
+
+        let permitido: &str =
+            r#"{"permitido": true, "permisos": {"publicar": ["a"], "suscribir": ["b.>"]}}"#;
+        con.escribir_publicacion_mensaje(&PublicacionMensaje::new(
+            SID_AUTORIZACION.to_string(),
+            respuesta.clone(),
+            permitido.into(),
+            None,
+            None,
+        ))
+        .unwrap();
+        con.tick(&mut TickContexto::new(0, 1));
+        assert!(!con.autenticado);
+
+        con.escribir_publicacion_mensaje(
+            &PublicacionMensaje::new(
+                SID_AUTORIZACION.to_string(),
+                respuesta,
+                permitido.into(),
+                None,
+                None,
+            )
+            .con_origen_sistema(true),
+        )
+        .unwrap();
+        assert!(mock.intentar_recibir_string().is_none());
+
+        // Se aplica la respuesta y después se procesa lo que quedó pendiente
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        assert!(con.autenticado);
+        assert_eq!(contexto.desuscripciones, vec![SID_AUTORIZACION.to_string()]);
+        assert_eq!(contexto.eventos, vec![TipoEvento::Conexion]);
+
+        mock.escribir_bytes(b"PUB c 0\r\n\r\nSUB b.* 1\r\nSUB c 2\r\n");
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        let respuestas = mock.intentar_recibir_string().unwrap();
+        assert!(respuestas.contains("-ERR 'Permissions Violation for Publish to c'"));
+        assert!(respuestas.contains("-ERR 'Permissions Violation for Subscription to c'"));
+        assert_eq!(contexto.publicaciones.len(), 1);
+        assert_eq!(contexto.suscripciones.len(), 1);
+        assert!(con.esta_conectado());
+
+        // Sin respuesta a tiempo se rechaza al usuario
+        let (mut mock, stream) = MockHandler::new();
+        let mut con = Conexion::con_autorizacion_externa(
+            2,
+            Box::new(stream),
+            Registrador::new(),
+            None,
+            Some(AutorizacionExterna {
+                tiempo_limite: Duration::ZERO,
+            }),
+            LimitesServidor::default(),
+        );
+
+        // Aunque no haya cuentas, el INFO avisa que se requiere autenticación
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("\"requiere_auth\":true"));
+        mock.escribir_bytes(b"CONNECT {}\r\n");
+        con.tick(&mut TickContexto::new(0, 2));
+        let mut contexto = TickContexto::new(0, 2);
+        con.tick(&mut contexto);
+
+        assert!(!con.esta_conectado());
+        assert_eq!(contexto.eventos, vec![TipoEvento::ErrorAutenticacion]);
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .contains("-ERR 'Authorization Violation'"));
+    }
 }
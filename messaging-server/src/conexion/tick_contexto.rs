@@ -1,6 +1,7 @@
 use crate::{
     hilo::id::IdHilo,
     publicacion::Publicacion,
+    sistema::evento::TipoEvento,
     suscripciones::{id::IdSuscripcion, suscripcion::Suscripcion},
 };
 
@@ -11,6 +12,8 @@ pub struct TickContexto {
     pub suscripciones: Vec<Suscripcion>,
     pub desuscripciones: Vec<IdSuscripcion>,
     pub publicaciones: Vec<Publicacion>,
+    /// Eventos de sistema de la conexión que el hilo tiene que publicar
+    pub eventos: Vec<TipoEvento>,
     pub id_hilo: IdHilo,
     pub id_conexion: IdConexion,
 }
@@ -21,6 +24,7 @@ impl TickContexto {
             suscripciones: Vec::new(),
             desuscripciones: Vec::new(),
             publicaciones: Vec::new(),
+            eventos: Vec::new(),
             id_hilo,
             id_conexion,
         }
@@ -37,4 +41,8 @@ impl TickContexto {
     pub fn publicar(&mut self, publicacion: Publicacion) {
         self.publicaciones.push(publicacion);
     }
+
+    pub fn evento(&mut self, tipo: TipoEvento) {
+        self.eventos.push(tipo);
+    }
 }
@@ -10,6 +10,9 @@ pub struct Cuenta {
     pub id: u64,
     pub user: String,
     pub pass: String,
+    /// Los usuarios de la cuenta de sistema son los únicos que pueden
+    /// suscribirse a los eventos del servidor (`$SYS.>`)
+    pub sistema: bool,
 }
 
 impl Cuenta {
@@ -52,6 +55,7 @@ impl Serializable for Cuenta {
             id,
             user: user.to_string(),
             pass: pass.to_string(),
+            sistema: false,
         })
     }
 }
@@ -0,0 +1,226 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use lib::{
+    configuracion::Configuracion,
+    headers::{Headers, HEADER_ID_MENSAJE},
+    serializables::Serializable,
+};
+
+use crate::{publicacion::Publicacion, suscripciones::topico::Topico};
+
+/// Prefijo de las claves de configuración: `deduplicar.<patrón>=<segundos>`
+pub const PREFIJO_DEDUPLICACION: &str = "deduplicar.";
+
+/// Ids de mensaje vistos en los tópicos de un patrón
+#[derive(Debug)]
+struct Regla {
+    patron: String,
+    topico: Topico,
+    ventana: Duration,
+    vistos: HashMap<String, Instant>,
+    /// Los mismos ids en el orden en que se vieron, para olvidarlos cuando
+    /// pasa la ventana sin recorrer todo `vistos`
+    orden: VecDeque<(Instant, String)>,
+}
+
+impl Regla {
+    fn olvidar_vencidos(&mut self, ahora: Instant) {
+        while let Some((momento, _)) = self.orden.front() {
+            if ahora.duration_since(*momento) < self.ventana {
+                break;
+            }
+            if let Some((momento, id)) = self.orden.pop_front() {
+                if self.vistos.get(&id) == Some(&momento) {
+                    self.vistos.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+/// Los patrones de una configuración, ya validados. Se leen antes de
+/// reemplazar los del deduplicador, así un error no deja nada a medias
+#[derive(Debug)]
+pub struct ReglasDeduplicacion(Vec<Regla>);
+
+/// Descarta las publicaciones que repiten el header `Nats-Msg-Id` de otra
+/// publicación reciente. Cada patrón configurado tiene su propia ventana de
+/// tiempo, y los ids se recuerdan por patrón (el mismo id en tópicos de
+/// patrones distintos no es un duplicado). Las publicaciones sin id o de
+/// tópicos sin patrón no se revisan.
+///
+/// Se comparte entre todos los hilos del servidor, porque los duplicados
+/// pueden llegar por conexiones distintas
+#[derive(Debug, Clone, Default)]
+pub struct Deduplicador {
+    reglas: Arc<Mutex<Vec<Regla>>>,
+}
+
+impl Deduplicador {
+    pub fn desde_configuracion(configuracion: &Configuracion) -> io::Result<Self> {
+        let deduplicador: Deduplicador = Deduplicador::default();
+        deduplicador.actualizar(configuracion)?;
+        Ok(deduplicador)
+    }
+
+    /// Reemplaza los patrones por los de la configuración. Los patrones que
+    /// siguen estando conservan los ids que ya vieron
+    pub fn actualizar(&self, configuracion: &Configuracion) -> io::Result<()> {
+        self.reemplazar(Deduplicador::leer_reglas(configuracion)?);
+        Ok(())
+    }
+
+    /// Los patrones `deduplicar.<patrón>=<segundos>` de la configuración
+    pub fn leer_reglas(configuracion: &Configuracion) -> io::Result<ReglasDeduplicacion> {
+        let mut nuevas: Vec<Regla> = Vec::new();
+
+        for (patron, segundos) in configuracion.con_prefijo(PREFIJO_DEDUPLICACION) {
+            let ventana: Duration = segundos
+                .parse()
+                .ok()
+                .filter(|s: &f64| *s > 0.)
+                .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                .ok_or_else(|| error(format!("Ventana inválida para {}", patron)))?;
+            let topico: Topico = Topico::new(patron.clone())
+                .map_err(|e| error(format!("Patrón inválido {}: {}", patron, e)))?;
+
+            nuevas.push(Regla {
+                patron,
+                topico,
+                ventana,
+                vistos: HashMap::new(),
+                orden: VecDeque::new(),
+            });
+        }
+
+        Ok(ReglasDeduplicacion(nuevas))
+    }
+
+    /// Usa los patrones leídos con `leer_reglas`
+    pub fn reemplazar(&self, nuevas: ReglasDeduplicacion) {
+        let ReglasDeduplicacion(mut nuevas) = nuevas;
+        // Las reglas se reemplazan completas, así que no importa si otro hilo
+        // dejó el lock envenenado
+        let mut reglas = self.reglas.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for nueva in nuevas.iter_mut() {
+            if let Some(anterior) = reglas.iter_mut().find(|r| r.patron == nueva.patron) {
+                nueva.vistos = std::mem::take(&mut anterior.vistos);
+                nueva.orden = std::mem::take(&mut anterior.orden);
+            }
+        }
+        *reglas = nuevas;
+    }
+
+    /// Si la publicación repite el id de otra dentro de la ventana de su
+    /// patrón. Si no, se recuerda su id
+    pub fn es_duplicado(&self, publicacion: &Publicacion) -> bool {
+        let header: &[u8] = match &publicacion.header {
+            Some(header) => header,
+            None => return false,
+        };
+
+        let mut reglas = match self.reglas.lock() {
+            Ok(reglas) => reglas,
+            Err(_) => return false,
+        };
+
+        let regla: &mut Regla = match reglas
+            .iter_mut()
+            .find(|regla| regla.topico.test(&publicacion.topico))
+        {
+            Some(regla) => regla,
+            None => return false,
+        };
+
+        let id: String = match Headers::deserializar(header)
+            .ok()
+            .and_then(|headers| headers.obtener(HEADER_ID_MENSAJE).map(str::to_string))
+        {
+            Some(id) if !id.is_empty() => id,
+            _ => return false,
+        };
+
+        let ahora: Instant = Instant::now();
+        regla.olvidar_vencidos(ahora);
+
+        if regla.vistos.contains_key(&id) {
+            return true;
+        }
+
+        regla.vistos.insert(id.clone(), ahora);
+        regla.orden.push_back((ahora, id));
+        false
+    }
+}
+
+fn error(mensaje: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, mensaje)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use bytes::Bytes;
+    use lib::configuracion::Configuracion;
+
+    use crate::publicacion::Publicacion;
+
+    use super::Deduplicador;
+
+    fn publicacion(topico: &str, id: Option<&str>) -> Publicacion {
+        let header = id.map(|id| Bytes::from(format!("NATS/1.0\r\nNats-Msg-Id: {}\r\n\r\n", id)));
+        Publicacion::new(topico.to_string(), Bytes::from("1"), header, None)
+    }
+
+    #[test]
+    fn probar_duplicados_en_la_ventana() {
+        let deduplicador = Deduplicador::desde_configuracion(&Configuracion::parsear(
+            "deduplicar.dron.*.id.incidente.atendido=0.2",
+        ))
+        .unwrap();
+
+        let atendido = publicacion("dron.1.id.incidente.atendido", Some("a"));
+        assert!(!deduplicador.es_duplicado(&atendido));
+        assert!(deduplicador.es_duplicado(&atendido));
+        assert!(deduplicador.es_duplicado(&publicacion("dron.2.id.incidente.atendido", Some("a"))));
+
+        // Sin id o fuera de los patrones no se revisa
+        let sin_id = publicacion("dron.1.id.incidente.atendido", None);
+        assert!(!deduplicador.es_duplicado(&sin_id));
+        assert!(!deduplicador.es_duplicado(&sin_id));
+        let otro = publicacion("incidentes.creado", Some("a"));
+        assert!(!deduplicador.es_duplicado(&otro));
+        assert!(!deduplicador.es_duplicado(&otro));
+
+        // Se recargó la configuración con el mismo patrón: se conservan los ids
+        deduplicador
+            .actualizar(&Configuracion::parsear(
+                "deduplicar.dron.*.id.incidente.atendido=0.2",
+            ))
+            .unwrap();
+        assert!(deduplicador.es_duplicado(&atendido));
+
+        // Después de la ventana ya no es un duplicado
+        thread::sleep(Duration::from_millis(250));
+        assert!(!deduplicador.es_duplicado(&atendido));
+    }
+
+    #[test]
+    fn probar_configuracion_invalida() {
+        for linea in [
+            "deduplicar.a=0",
+            "deduplicar.a=x",
+            "deduplicar.a..b=1",
+            "deduplicar.a=1e30",
+        ] {
+            assert!(Deduplicador::desde_configuracion(&Configuracion::parsear(linea)).is_err());
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt::Display,
+    io,
+    net::{SocketAddr, TcpListener},
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use lib::stream::Stream;
+
+/// Dirección en la que escucha el servidor: un socket TCP o, en Unix, un
+/// socket de dominio Unix para los procesos de la misma máquina
+pub enum Escucha {
+    Tcp(TcpListener, String),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Escucha {
+    /// Escucha en `direccion:puerto`. Las direcciones IPv6 pueden ir con o sin
+    /// corchetes
+    pub fn tcp(direccion: &str, puerto: u16) -> io::Result<Self> {
+        let direccion: String = direccion_con_puerto(direccion, puerto);
+
+        let listener: TcpListener =
+            TcpListener::bind(&direccion).map_err(|e| error_al_escuchar(&direccion, e))?;
+        listener
+            .set_nonblocking(true) // Hace que el listener no bloquee el hilo principal
+            .map_err(|e| error_al_escuchar(&direccion, e))?;
+
+        // Con el puerto 0 el sistema elige uno libre
+        let direccion: String = listener
+            .local_addr()
+            .map(|direccion| direccion.to_string())
+            .unwrap_or(direccion);
+
+        Ok(Escucha::Tcp(listener, direccion))
+    }
+
+    /// Escucha en el socket de dominio Unix de la ruta. Si quedó el archivo de
+    /// un servidor anterior que ya no está escuchando, se reemplaza
+    #[cfg(unix)]
+    pub fn unix(ruta: &str) -> io::Result<Self> {
+        let ruta: PathBuf = PathBuf::from(ruta);
+
+        if ruta.exists() {
+            if UnixStream::connect(&ruta).is_ok() {
+                return Err(error_al_escuchar(
+                    &ruta.display(),
+                    io::Error::new(io::ErrorKind::AddrInUse, "ya hay un servidor escuchando"),
+                ));
+            }
+            std::fs::remove_file(&ruta).map_err(|e| error_al_escuchar(&ruta.display(), e))?;
+        }
+
+        let listener: UnixListener =
+            UnixListener::bind(&ruta).map_err(|e| error_al_escuchar(&ruta.display(), e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| error_al_escuchar(&ruta.display(), e))?;
+
+        Ok(Escucha::Unix(listener, ruta))
+    }
+
+    /// La dirección TCP en la que quedó escuchando (con el puerto que eligió
+    /// el sistema si se pidió el 0)
+    pub fn direccion_local(&self) -> Option<SocketAddr> {
+        match self {
+            Escucha::Tcp(listener, _) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Escucha::Unix(..) => None,
+        }
+    }
+
+    /// Acepta una conexión nueva y la pone en modo no bloqueante. Si no hay
+    /// ninguna devuelve `WouldBlock`
+    pub fn aceptar(&self) -> io::Result<Box<dyn Stream>> {
+        match self {
+            Escucha::Tcp(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Escucha::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+impl Display for Escucha {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Escucha::Tcp(_, direccion) => write!(f, "{}", direccion),
+            #[cfg(unix)]
+            Escucha::Unix(_, ruta) => write!(f, "unix://{}", ruta.display()),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Escucha {
+    /// El archivo del socket no se borra solo al cerrarlo
+    fn drop(&mut self) {
+        if let Escucha::Unix(_, ruta) = self {
+            let _ = std::fs::remove_file(ruta);
+        }
+    }
+}
+
+/// `direccion:puerto`, con la dirección entre corchetes si es IPv6
+pub fn direccion_con_puerto(direccion: &str, puerto: u16) -> String {
+    if direccion.contains(':') && !direccion.starts_with('[') {
+        format!("[{}]:{}", direccion, puerto)
+    } else {
+        format!("{}:{}", direccion, puerto)
+    }
+}
+
+/// Agrega la dirección al error, conservando el tipo
+fn error_al_escuchar(direccion: &impl Display, error: io::Error) -> io::Error {
+    io::Error::new(
+        error.kind(),
+        format!("No se pudo escuchar en {}: {}", direccion, error),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use super::{direccion_con_puerto, Escucha};
+
+    #[test]
+    fn probar_direcciones_y_errores() {
+        assert_eq!(direccion_con_puerto("::1", 4222), "[::1]:4222");
+        assert_eq!(direccion_con_puerto("[::1]", 4222), "[::1]:4222");
+        assert_eq!(direccion_con_puerto("127.0.0.1", 4222), "127.0.0.1:4222");
+
+        let escucha = Escucha::tcp("127.0.0.1", 0).unwrap();
+        let puerto = match &escucha {
+            Escucha::Tcp(listener, _) => listener.local_addr().unwrap().port(),
+            #[cfg(unix)]
+            _ => unreachable!(),
+        };
+
+        // El puerto ya está en uso
+        let error = Escucha::tcp("127.0.0.1", puerto).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        assert!(error.to_string().contains(&format!("127.0.0.1:{}", puerto)));
+
+        assert!(Escucha::tcp("no es una direccion", 1).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probar_socket_unix() {
+        use std::os::unix::net::UnixStream;
+
+        let ruta = std::env::temp_dir().join(format!("escucha-{}.sock", std::process::id()));
+        let ruta_texto = ruta.to_str().unwrap().to_string();
+
+        let escucha = Escucha::unix(&ruta_texto).unwrap();
+        assert!(matches!(
+            escucha.aceptar().err().map(|e| e.kind()),
+            Some(io::ErrorKind::WouldBlock)
+        ));
+
+        let mut cliente = UnixStream::connect(&ruta).unwrap();
+        cliente.write_all(b"PING\r\n").unwrap();
+        assert!(escucha.aceptar().is_ok());
+
+        // Otro servidor no puede usar la misma ruta mientras se escucha
+        assert_eq!(
+            Escucha::unix(&ruta_texto).err().unwrap().kind(),
+            io::ErrorKind::AddrInUse
+        );
+
+        drop(escucha);
+        assert!(!ruta.exists());
+    }
+}
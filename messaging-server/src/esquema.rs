@@ -0,0 +1,299 @@
+use lib::configuracion::esquema::{Clave, Esquema, TipoValor};
+
+use crate::{
+    autorizacion::TIEMPO_LIMITE_POR_DEFECTO, deduplicacion::PREFIJO_DEDUPLICACION,
+    hilo::canal::CAPACIDAD_COLA_POR_DEFECTO, limite_tasa::PREFIJO_LIMITE_CUENTA,
+    limites::LimitesServidor, mapeos::PREFIJO_MAPEO,
+};
+
+const NIVELES_REGISTRO: &[&str] = &[
+    "trace",
+    "debug",
+    "info",
+    "informacion",
+    "advertencia",
+    "warn",
+    "error",
+    "ninguno",
+];
+
+/// Las claves de configuración del servidor
+pub fn esquema() -> Esquema {
+    let limites: LimitesServidor = LimitesServidor::default();
+
+    Esquema::new(
+        "messaging-server",
+        "Servidor de mensajería compatible con NATS",
+    )
+    .con_clave(
+        Clave::new(
+            "direccion",
+            TipoValor::Texto,
+            "Direcciones separadas por comas",
+        )
+        .con_defecto("127.0.0.1"),
+    )
+    .con_clave(
+        Clave::new("puerto", TipoValor::Natural, "Puerto de los clientes NATS")
+            .con_defecto(4222)
+            .con_rango(0., 65535.),
+    )
+    .con_clave(
+        Clave::new(
+            "puerto_websocket",
+            TipoValor::Natural,
+            "Puerto de los clientes WebSocket",
+        )
+        .con_rango(0., 65535.),
+    )
+    .con_clave(
+        Clave::new(
+            "puerto_mqtt",
+            TipoValor::Natural,
+            "Puerto de los clientes MQTT",
+        )
+        .con_rango(0., 65535.),
+    )
+    .con_clave(Clave::new(
+        "socket_unix",
+        TipoValor::Texto,
+        "Ruta de un socket Unix para clientes locales",
+    ))
+    .con_clave(
+        Clave::new(
+            "hilos",
+            TipoValor::Natural,
+            "Hilos que atienden a los clientes",
+        )
+        .con_defecto(4)
+        .con_minimo(1.),
+    )
+    .con_clave(Clave::new(
+        "id_servidor",
+        TipoValor::Texto,
+        "Identificador en los tópicos de sistema (por defecto, uno al azar)",
+    ))
+    .con_clave(Clave::new(
+        "cuentas",
+        TipoValor::Texto,
+        "Archivo csv con las cuentas (id,user,pass)",
+    ))
+    .con_clave(Clave::new(
+        "cuenta_sistema",
+        TipoValor::Texto,
+        "Usuario que puede suscribirse a $SYS",
+    ))
+    .con_clave(
+        Clave::new(
+            "intervalo_estadisticas",
+            TipoValor::Decimal,
+            "Segundos entre las publicaciones de STATSZ",
+        )
+        .con_defecto(10)
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "intervalo_recarga",
+            TipoValor::Decimal,
+            "Segundos entre las revisiones de los archivos (0 no recarga)",
+        )
+        .con_defecto(2)
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "max_payload",
+            TipoValor::Natural,
+            "Bytes máximos de una publicación",
+        )
+        .con_defecto(limites.max_payload),
+    )
+    .con_clave(
+        Clave::new(
+            "max_control_line",
+            TipoValor::Natural,
+            "Largo máximo de la línea de un comando",
+        )
+        .con_defecto(limites.max_control_line),
+    )
+    .con_clave(
+        Clave::new(
+            "max_conexiones",
+            TipoValor::Natural,
+            "Conexiones abiertas en todo el servidor",
+        )
+        .con_defecto(limites.max_conexiones),
+    )
+    .con_clave(Clave::new(
+        "max_suscripciones",
+        TipoValor::Natural,
+        "Suscripciones por conexión (por defecto, sin límite)",
+    ))
+    .con_clave(
+        Clave::new(
+            "max_pending",
+            TipoValor::Natural,
+            "Bytes sin leer antes de desconectar a un consumidor lento",
+        )
+        .con_defecto(limites.max_pending),
+    )
+    .con_clave(
+        Clave::new(
+            "ping_interval",
+            TipoValor::Decimal,
+            "Segundos entre los PING a un cliente inactivo",
+        )
+        .con_defecto(limites.ping_interval.as_secs_f64())
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "max_pings_out",
+            TipoValor::Natural,
+            "PINGs sin respuesta antes de desconectar",
+        )
+        .con_defecto(limites.max_pings_out),
+    )
+    .con_clave(
+        Clave::new(
+            "max_mensajes_por_segundo",
+            TipoValor::Decimal,
+            "Publicaciones por segundo de cada conexión",
+        )
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "max_bytes_por_segundo",
+            TipoValor::Decimal,
+            "Bytes publicados por segundo de cada conexión",
+        )
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::prefijo(
+            PREFIJO_LIMITE_CUENTA,
+            TipoValor::Decimal,
+            "<usuario>.mensajes_por_segundo o <usuario>.bytes_por_segundo",
+        )
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "politica_tasa",
+            TipoValor::Opciones(&["demorar", "error"]),
+            "Qué hacer con lo que supera el límite de tasa",
+        )
+        .con_defecto("demorar"),
+    )
+    .con_clave(
+        Clave::new(
+            "capacidad_cola_hilos",
+            TipoValor::Natural,
+            "Instrucciones que acumula la cola de cada hilo",
+        )
+        .con_defecto(CAPACIDAD_COLA_POR_DEFECTO)
+        .con_minimo(1.),
+    )
+    .con_clave(
+        Clave::new(
+            "politica_cola_hilos",
+            TipoValor::Opciones(&["bloquear", "descartar"]),
+            "Qué hacer cuando la cola de un hilo está llena",
+        )
+        .con_defecto("bloquear"),
+    )
+    .con_clave(Clave::prefijo(
+        PREFIJO_MAPEO,
+        TipoValor::Texto,
+        "Tópico al que se mapea el origen",
+    ))
+    .con_clave(
+        Clave::prefijo(
+            PREFIJO_DEDUPLICACION,
+            TipoValor::Decimal,
+            "Segundos de la ventana de deduplicación del patrón",
+        )
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "autorizacion_externa",
+            TipoValor::Booleano,
+            "Autorizar en $SYS.REQ.USER.AUTH a los usuarios que no están en las cuentas",
+        )
+        .con_defecto(false),
+    )
+    .con_clave(
+        Clave::new(
+            "autorizacion_tiempo_limite",
+            TipoValor::Decimal,
+            "Segundos que se espera la respuesta de autorización",
+        )
+        .con_defecto(TIEMPO_LIMITE_POR_DEFECTO)
+        .con_minimo(0.),
+    )
+    .con_clave(
+        Clave::new(
+            "log_nivel",
+            TipoValor::Opciones(NIVELES_REGISTRO),
+            "Nivel mínimo de los registros",
+        )
+        .con_defecto("info"),
+    )
+    .con_clave(Clave::new(
+        "log_objetivos",
+        TipoValor::Texto,
+        "Nivel de cada subsistema (conexion:debug,hilo:advertencia)",
+    ))
+    .con_clave(
+        Clave::new(
+            "log_formato",
+            TipoValor::Opciones(&["texto", "json"]),
+            "Formato de los registros",
+        )
+        .con_defecto("texto"),
+    )
+    .con_clave(Clave::new(
+        "log_archivo",
+        TipoValor::Texto,
+        "Archivo de registros (por defecto, la salida estándar)",
+    ))
+    .con_clave(Clave::new(
+        "log_tamanio_maximo",
+        TipoValor::Natural,
+        "Bytes del archivo de registros antes de rotarlo",
+    ))
+    .con_clave(
+        Clave::new(
+            "log_antiguedad_maxima",
+            TipoValor::Decimal,
+            "Segundos del archivo de registros antes de rotarlo",
+        )
+        .con_minimo(0.),
+    )
+    .con_clave(Clave::new(
+        "log_archivos_maximos",
+        TipoValor::Natural,
+        "Archivos de registros rotados que se conservan",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::configuracion::Configuracion;
+
+    use super::esquema;
+
+    #[test]
+    fn probar_claves_del_readme() {
+        let configuracion = Configuracion::parsear(
+            "puerto=4222\nmax_mensajes_por_segundo=1000\nlimite_cuenta.dron.mensajes_por_segundo=50\nmapeo.dron.*.informacion=drones.{{wildcard(1)}}.telemetria\ndeduplicar.incidentes.>=120\nlog_nivel=debug",
+        );
+        assert!(esquema().validar(&configuracion).is_empty());
+
+        let configuracion = Configuracion::parsear("puerto=4222x\nhilo=2");
+        assert_eq!(esquema().validar(&configuracion).len(), 2);
+    }
+}
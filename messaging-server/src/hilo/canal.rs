@@ -0,0 +1,158 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+};
+
+use super::instruccion::Instruccion;
+
+/// Instrucciones que puede acumular la cola de cada hilo por defecto
+pub const CAPACIDAD_COLA_POR_DEFECTO: usize = 64 * 1024;
+
+/// Qué hace un hilo cuando la cola de otro hilo está llena
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PoliticaCola {
+    /// Guarda las instrucciones hasta que haya lugar y, mientras tanto, deja
+    /// de leer sus conexiones para que los clientes se frenen
+    #[default]
+    Bloquear,
+    /// Descarta las publicaciones (y las cuenta). Las suscripciones y las
+    /// recargas nunca se descartan
+    Descartar,
+}
+
+impl FromStr for PoliticaCola {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bloquear" => Ok(PoliticaCola::Bloquear),
+            "descartar" => Ok(PoliticaCola::Descartar),
+            _ => Err(format!("Política de cola desconocida: {}", s)),
+        }
+    }
+}
+
+/// Resultado de enviar una instrucción a la cola de un hilo
+#[derive(Debug)]
+pub enum Envio {
+    Enviada,
+    /// La cola está llena: se devuelve la instrucción para reintentar
+    Llena(Box<Instruccion>),
+    /// El hilo terminó
+    Cerrada,
+}
+
+/// Crea la cola de instrucciones de un hilo, con lugar para `capacidad`
+/// instrucciones
+pub fn canal_instrucciones(capacidad: usize) -> (EmisorInstrucciones, ReceptorInstrucciones) {
+    let (tx, rx) = sync_channel(capacidad.max(1));
+    let en_cola: Arc<AtomicUsize> = Arc::default();
+
+    (
+        EmisorInstrucciones {
+            tx,
+            en_cola: en_cola.clone(),
+        },
+        ReceptorInstrucciones { rx, en_cola },
+    )
+}
+
+/// Punta emisora de la cola de instrucciones de un hilo
+#[derive(Debug, Clone)]
+pub struct EmisorInstrucciones {
+    tx: SyncSender<Instruccion>,
+    en_cola: Arc<AtomicUsize>,
+}
+
+impl EmisorInstrucciones {
+    /// Envía la instrucción sin bloquear
+    pub fn intentar_enviar(&self, instruccion: Instruccion) -> Envio {
+        self.en_cola.fetch_add(1, Ordering::Relaxed);
+        match self.tx.try_send(instruccion) {
+            Ok(()) => Envio::Enviada,
+            Err(error) => {
+                self.en_cola.fetch_sub(1, Ordering::Relaxed);
+                match error {
+                    TrySendError::Full(instruccion) => Envio::Llena(Box::new(instruccion)),
+                    TrySendError::Disconnected(_) => Envio::Cerrada,
+                }
+            }
+        }
+    }
+
+    /// Envía la instrucción esperando a que haya lugar. Solo la usa el
+    /// servidor: si un hilo esperara a otro se podrían bloquear entre ellos
+    pub fn enviar(&self, instruccion: Instruccion) -> Envio {
+        self.en_cola.fetch_add(1, Ordering::Relaxed);
+        match self.tx.send(instruccion) {
+            Ok(()) => Envio::Enviada,
+            Err(_) => {
+                self.en_cola.fetch_sub(1, Ordering::Relaxed);
+                Envio::Cerrada
+            }
+        }
+    }
+}
+
+/// Punta receptora de la cola de instrucciones de un hilo
+#[derive(Debug)]
+pub struct ReceptorInstrucciones {
+    rx: Receiver<Instruccion>,
+    en_cola: Arc<AtomicUsize>,
+}
+
+impl ReceptorInstrucciones {
+    /// La próxima instrucción de la cola, sin esperar
+    pub fn intentar_recibir(&self) -> Option<Instruccion> {
+        let instruccion: Instruccion = self.rx.try_recv().ok()?;
+        self.en_cola.fetch_sub(1, Ordering::Relaxed);
+        Some(instruccion)
+    }
+
+    /// Instrucciones que esperan en la cola
+    pub fn en_cola(&self) -> usize {
+        self.en_cola.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hilo::instruccion::Instruccion, publicacion::Publicacion};
+
+    use super::{canal_instrucciones, Envio};
+
+    fn publicar() -> Instruccion {
+        Instruccion::Publicar(Publicacion::new("a".to_string(), "1".into(), None, None))
+    }
+
+    #[test]
+    fn probar_cola_llena() {
+        let (tx, rx) = canal_instrucciones(2);
+
+        assert!(matches!(tx.intentar_enviar(publicar()), Envio::Enviada));
+        assert!(matches!(
+            tx.clone().intentar_enviar(publicar()),
+            Envio::Enviada
+        ));
+        match tx.intentar_enviar(publicar()) {
+            Envio::Llena(instruccion) => assert!(matches!(*instruccion, Instruccion::Publicar(_))),
+            otro => panic!("{:?}", otro),
+        }
+        assert_eq!(rx.en_cola(), 2);
+
+        assert!(rx.intentar_recibir().is_some());
+        assert_eq!(rx.en_cola(), 1);
+        assert!(matches!(tx.intentar_enviar(publicar()), Envio::Enviada));
+        assert!(rx.intentar_recibir().is_some());
+        assert!(rx.intentar_recibir().is_some());
+        assert!(rx.intentar_recibir().is_none());
+        assert_eq!(rx.en_cola(), 0);
+
+        drop(rx);
+        assert!(matches!(tx.intentar_enviar(publicar()), Envio::Cerrada));
+    }
+}
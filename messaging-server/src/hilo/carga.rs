@@ -0,0 +1,143 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde::Serialize;
+
+use super::id::IdHilo;
+
+/// Mensajes por segundo que pesan lo mismo que una conexión más al elegir el
+/// hilo menos cargado
+const MENSAJES_POR_CONEXION: f64 = 1000.;
+/// Bytes pendientes que pesan lo mismo que una conexión más
+const PENDIENTES_POR_CONEXION: f64 = 64. * 1024.;
+
+/// Cuánto trabajo tiene un hilo. La comparten el hilo, que la actualiza en
+/// cada tick, y el servidor, que la usa para repartir las conexiones nuevas
+#[derive(Debug, Clone, Default)]
+pub struct CargaHilo {
+    /// Conexiones asignadas al hilo (incluye las que todavía no recibió)
+    conexiones: Arc<AtomicUsize>,
+    /// Mensajes recibidos y enviados por segundo en el último intervalo medido
+    mensajes_por_segundo: Arc<AtomicU64>,
+    /// Bytes que las conexiones del hilo todavía no pudieron escribir
+    bytes_pendientes: Arc<AtomicUsize>,
+    /// Instrucciones que esperaban en la cola del hilo al empezar el último tick
+    instrucciones_en_cola: Arc<AtomicUsize>,
+    /// Instrucciones que el hilo no pudo enviar porque la cola de otro hilo
+    /// estaba llena, y que espera para reenviar
+    instrucciones_en_espera: Arc<AtomicUsize>,
+    /// Publicaciones que el hilo descartó porque la cola de otro hilo estaba llena
+    instrucciones_descartadas: Arc<AtomicU64>,
+}
+
+/// Los valores de la carga de un hilo en un momento
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EstadisticasHilo {
+    pub id: IdHilo,
+    pub conexiones: usize,
+    pub mensajes_por_segundo: u64,
+    pub bytes_pendientes: usize,
+    pub instrucciones_en_cola: usize,
+    pub instrucciones_en_espera: usize,
+    pub instrucciones_descartadas: u64,
+}
+
+impl CargaHilo {
+    /// El servidor le asignó una conexión al hilo
+    pub fn sumar_conexion(&self) {
+        self.conexiones.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// El hilo descartó una conexión terminada
+    pub fn restar_conexion(&self) {
+        self.conexiones.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn actualizar_mensajes_por_segundo(&self, mensajes_por_segundo: u64) {
+        self.mensajes_por_segundo
+            .store(mensajes_por_segundo, Ordering::Relaxed);
+    }
+
+    pub fn actualizar_bytes_pendientes(&self, bytes_pendientes: usize) {
+        self.bytes_pendientes
+            .store(bytes_pendientes, Ordering::Relaxed);
+    }
+
+    pub fn actualizar_instrucciones(&self, en_cola: usize, en_espera: usize) {
+        self.instrucciones_en_cola.store(en_cola, Ordering::Relaxed);
+        self.instrucciones_en_espera
+            .store(en_espera, Ordering::Relaxed);
+    }
+
+    pub fn sumar_instruccion_descartada(&self) {
+        self.instrucciones_descartadas
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn estadisticas(&self, id: IdHilo) -> EstadisticasHilo {
+        EstadisticasHilo {
+            id,
+            conexiones: self.conexiones.load(Ordering::Relaxed),
+            mensajes_por_segundo: self.mensajes_por_segundo.load(Ordering::Relaxed),
+            bytes_pendientes: self.bytes_pendientes.load(Ordering::Relaxed),
+            instrucciones_en_cola: self.instrucciones_en_cola.load(Ordering::Relaxed),
+            instrucciones_en_espera: self.instrucciones_en_espera.load(Ordering::Relaxed),
+            instrucciones_descartadas: self.instrucciones_descartadas.load(Ordering::Relaxed),
+        }
+    }
+
+    /// La carga medida en "conexiones": cada conexión suma uno, y el tráfico y
+    /// los bytes pendientes suman en proporción
+    pub fn valor(&self) -> f64 {
+        let estadisticas = self.estadisticas(0);
+
+        estadisticas.conexiones as f64
+            + estadisticas.mensajes_por_segundo as f64 / MENSAJES_POR_CONEXION
+            + estadisticas.bytes_pendientes as f64 / PENDIENTES_POR_CONEXION
+    }
+}
+
+/// El índice del hilo con menos carga (el primero, si hay empate)
+pub fn menos_cargado(cargas: &[CargaHilo]) -> usize {
+    let mut menor: usize = 0;
+    let mut menor_valor: f64 = f64::INFINITY;
+
+    for (indice, carga) in cargas.iter().enumerate() {
+        let valor = carga.valor();
+        if valor < menor_valor {
+            menor = indice;
+            menor_valor = valor;
+        }
+    }
+
+    menor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{menos_cargado, CargaHilo};
+
+    #[test]
+    fn probar_menos_cargado() {
+        let cargas: Vec<CargaHilo> = (0..3).map(|_| CargaHilo::default()).collect();
+        assert_eq!(menos_cargado(&cargas), 0);
+
+        cargas[0].sumar_conexion();
+        assert_eq!(menos_cargado(&cargas), 1);
+
+        // Una conexión con mucho tráfico pesa más que dos sin tráfico
+        cargas[1].sumar_conexion();
+        cargas[1].actualizar_mensajes_por_segundo(5000);
+        cargas[2].sumar_conexion();
+        cargas[2].sumar_conexion();
+        assert_eq!(menos_cargado(&cargas), 0);
+
+        cargas[0].actualizar_bytes_pendientes(1024 * 1024);
+        assert_eq!(menos_cargado(&cargas), 2);
+
+        cargas[2].restar_conexion();
+        assert_eq!(cargas[2].estadisticas(2).conexiones, 1);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     conexion::id::IdConexion,
     publicacion::Publicacion,
+    recarga::Recarga,
     suscripciones::{id::IdSuscripcion, suscripcion::Suscripcion},
 };
 
@@ -19,4 +20,33 @@ pub enum Instruccion {
     /// generada por un cliente y enviada al propio thread, esto se hace para evitar
     /// Que el servidor envie la publicación antes de que se genere la suscripcion
     NuevaPublicacion(Publicacion),
+    /// Se recargó la configuración: hay que aplicar las cuentas y los límites
+    /// nuevos a las conexiones del hilo
+    Recargar(Recarga),
+    /// Se detuvo el servidor: el hilo cierra sus conexiones y termina
+    Detener,
+}
+
+impl Instruccion {
+    /// Si se puede descartar cuando la cola del hilo de destino está llena.
+    /// Perder una suscripción o una recarga dejaría al hilo en un estado
+    /// incorrecto, perder una publicación no
+    pub fn es_descartable(&self) -> bool {
+        matches!(
+            self,
+            Instruccion::Publicar(_)
+                | Instruccion::PublicarExacto(..)
+                | Instruccion::NuevaPublicacion(_)
+        )
+    }
+
+    /// La publicación que lleva la instrucción, si lleva una
+    pub fn publicacion(&self) -> Option<&Publicacion> {
+        match self {
+            Instruccion::Publicar(publicacion)
+            | Instruccion::PublicarExacto(_, publicacion)
+            | Instruccion::NuevaPublicacion(publicacion) => Some(publicacion),
+            _ => None,
+        }
+    }
 }
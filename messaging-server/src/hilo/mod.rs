@@ -1,57 +1,119 @@
+pub mod canal;
+pub mod carga;
 pub mod id;
 pub mod instruccion;
 
 use std::{
-    collections::HashMap,
-    sync::mpsc::{Receiver, SendError, Sender},
+    collections::{HashMap, VecDeque},
+    sync::mpsc::Receiver,
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
     conexion::{id::IdConexion, tick_contexto::TickContexto, Conexion},
+    deduplicacion::Deduplicador,
+    mapeos::Mapeos,
     publicacion::Publicacion,
-    registrador::Registrador,
+    recarga::Recarga,
+    registrador::{registro::NivelRegistro, Registrador},
+    sistema::{
+        evento::TipoEvento,
+        traza::{EntregaTraza, EventoTraza, Salto, SeleccionGrupo},
+    },
     suscripciones::{suscripcion::Suscripcion, Suscripciones},
 };
 
-use self::{id::IdHilo, instruccion::Instruccion};
+use self::{
+    canal::{EmisorInstrucciones, Envio, PoliticaCola, ReceptorInstrucciones},
+    carga::CargaHilo,
+    id::IdHilo,
+    instruccion::Instruccion,
+};
+
+/// Cada cuánto se calcula la cantidad de mensajes por segundo del hilo
+const INTERVALO_MEDICION: Duration = Duration::from_secs(1);
 
 pub struct Hilo {
     id: u64,
     /// Canal para **recibir** instrucciones de otros procesos
     canal_recibir_instrucciones_conexiones: Receiver<(IdConexion, Conexion)>,
     /// Canales a otros hilos para **enviar** instrucciones (ejemplo: publicar, suscribir, desuscribir, etc.)
-    canales_enviar_instrucciones: HashMap<IdHilo, Sender<Instruccion>>,
+    canales_enviar_instrucciones: HashMap<IdHilo, EmisorInstrucciones>,
     /// Canal para **recibir** instrucciones de otros procesos
-    canal_recibir_instrucciones_instrucciones: Receiver<Instruccion>,
+    canal_recibir_instrucciones_instrucciones: ReceptorInstrucciones,
+    /// Instrucciones que no entraron en la cola de otro hilo, por hilo de
+    /// destino y en orden, para reenviarlas cuando haya lugar
+    en_espera: HashMap<IdHilo, VecDeque<Instruccion>>,
+    /// Qué se hace cuando la cola de otro hilo está llena
+    politica_cola: PoliticaCola,
+    /// Si el servidor se detuvo y el hilo tiene que terminar
+    detenido: bool,
     /// Suscripciones de este hilo
     suscripciones: Suscripciones,
     /// Registrador de eventos
     registrador: Registrador,
     /// Conexiones de este hilo
     conexiones: HashMap<IdConexion, Conexion>,
+    /// Id del servidor, para los tópicos de los eventos de sistema
+    id_servidor: String,
+    /// Carga del hilo, compartida con el servidor
+    carga: CargaHilo,
+    /// Mensajes recibidos y enviados desde `inicio_medicion`
+    mensajes_medidos: u64,
+    inicio_medicion: Instant,
+    /// Mapeos de tópicos que se aplican a las publicaciones nuevas
+    mapeos: Mapeos,
+    /// Descarta las publicaciones repetidas (compartido con los otros hilos)
+    deduplicador: Deduplicador,
 }
 
 impl Hilo {
     pub fn new(
         id: u64,
         canal_recibir_instrucciones_conexiones: Receiver<(IdConexion, Conexion)>,
-        canales_enviar_instrucciones: HashMap<IdHilo, Sender<Instruccion>>,
-        canal_recibir_instrucciones_instrucciones: Receiver<Instruccion>,
+        canales_enviar_instrucciones: HashMap<IdHilo, EmisorInstrucciones>,
+        canal_recibir_instrucciones_instrucciones: ReceptorInstrucciones,
         registrador: Registrador,
+        id_servidor: String,
+        carga: CargaHilo,
     ) -> Self {
         Self {
             id,
             canal_recibir_instrucciones_conexiones,
             canales_enviar_instrucciones,
             canal_recibir_instrucciones_instrucciones,
+            en_espera: HashMap::new(),
+            politica_cola: PoliticaCola::default(),
+            detenido: false,
             registrador,
             suscripciones: Suscripciones::new(),
             conexiones: HashMap::new(),
+            id_servidor,
+            carga,
+            mensajes_medidos: 0,
+            inicio_medicion: Instant::now(),
+            mapeos: Mapeos::default(),
+            deduplicador: Deduplicador::default(),
         }
     }
 
+    /// Mapeos de tópicos que se aplican a las publicaciones de las conexiones
+    pub fn con_mapeos(mut self, mapeos: Mapeos) -> Self {
+        self.mapeos = mapeos;
+        self
+    }
+
+    pub fn con_deduplicador(mut self, deduplicador: Deduplicador) -> Self {
+        self.deduplicador = deduplicador;
+        self
+    }
+
+    pub fn con_politica_cola(mut self, politica_cola: PoliticaCola) -> Self {
+        self.politica_cola = politica_cola;
+        self
+    }
+
     /// Iniciar la ejecución del hilo
     pub fn iniciar(mut hilo: Hilo) -> JoinHandle<()> {
         thread::spawn(move || {
@@ -60,10 +122,10 @@ impl Hilo {
         })
     }
 
-    /// Punto inicial de ejecución del hilo, este nunca termina
-    /// (A menos que ocurra un error fatal).
+    /// Punto inicial de ejecución del hilo. Termina cuando se detiene el
+    /// servidor, y al descartarse el hilo se cierran sus conexiones
     pub fn inicio(&mut self) {
-        loop {
+        while !self.detenido {
             self.tick();
             thread::sleep(Duration::from_micros(500));
         }
@@ -75,8 +137,35 @@ impl Hilo {
     pub fn tick(&mut self) {
         self.recibir_conexiones();
         self.recibir_instrucciones();
-        self.tick_conexiones();
+        self.reenviar_en_espera();
+        // Si hay instrucciones esperando lugar en la cola de otro hilo no se
+        // leen las conexiones, así los clientes se frenan en vez de acumular
+        // memoria. Se siguen recibiendo instrucciones para que ningún hilo
+        // quede esperando a otro
+        if self.en_espera.is_empty() {
+            self.tick_conexiones();
+        }
         self.eliminar_conexiones_terminadas();
+        self.medir_carga();
+    }
+
+    /// Actualiza los mensajes por segundo y los bytes pendientes del hilo
+    fn medir_carga(&mut self) {
+        let transcurrido = self.inicio_medicion.elapsed();
+        if transcurrido >= INTERVALO_MEDICION {
+            let por_segundo = self.mensajes_medidos as f64 / transcurrido.as_secs_f64();
+            self.carga
+                .actualizar_mensajes_por_segundo(por_segundo as u64);
+            self.mensajes_medidos = 0;
+            self.inicio_medicion = Instant::now();
+        }
+
+        let pendientes: usize = self
+            .conexiones
+            .values()
+            .map(|conexion| conexion.bytes_pendientes())
+            .sum();
+        self.carga.actualizar_bytes_pendientes(pendientes);
     }
 
     // Mientras se reciban conexiones, el registrador informa
@@ -93,9 +182,19 @@ impl Hilo {
     }
 
     pub fn recibir_instrucciones(&mut self) {
-        while let Ok(instruccion) = self.canal_recibir_instrucciones_instrucciones.try_recv() {
-            self.registrador
-                .info(&format!("Recibida instrucción: {:?}", &instruccion), None);
+        self.carga.actualizar_instrucciones(
+            self.canal_recibir_instrucciones_instrucciones.en_cola(),
+            self.en_espera.values().map(VecDeque::len).sum(),
+        );
+
+        while let Some(instruccion) = self
+            .canal_recibir_instrucciones_instrucciones
+            .intentar_recibir()
+        {
+            if self.registrador.habilitado(NivelRegistro::Trace) {
+                self.registrador
+                    .trace(&format!("Recibida instrucción: {:?}", &instruccion), None);
+            }
             self.recibir_instruccion(instruccion);
         }
     }
@@ -114,22 +213,82 @@ impl Hilo {
             Instruccion::PublicarExacto(suscripcion, publicacion) => {
                 self.recibir_publicacion_exacto(&suscripcion, publicacion);
             }
-            Instruccion::NuevaPublicacion(publicacion) => {
-                self.enviar_instruccion_publicar(publicacion)
+            Instruccion::NuevaPublicacion(mut publicacion) => {
+                let mut topico_original: Option<String> = None;
+
+                // El tópico se mapea antes de buscar a los suscriptores
+                if let Some(topico) = self.mapeos.aplicar(&publicacion.topico) {
+                    if self.registrador.habilitado(NivelRegistro::Trace) {
+                        self.registrador.trace(
+                            &format!("Tópico mapeado: {} -> {}", publicacion.topico, topico),
+                            None,
+                        );
+                    }
+                    topico_original = Some(std::mem::replace(&mut publicacion.topico, topico));
+                }
+                self.enviar_instruccion_publicar(publicacion, topico_original)
+            }
+            Instruccion::Recargar(recarga) => {
+                self.recargar(&recarga);
             }
+            Instruccion::Detener => self.detenido = true,
+        }
+    }
+
+    /// Aplica la configuración nueva a todas las conexiones del hilo y elimina
+    /// las suscripciones que ya no tienen permitidas
+    pub fn recargar(&mut self, recarga: &Recarga) {
+        self.mapeos = recarga.mapeos.clone();
+
+        let mut suscripciones_a_eliminar: Vec<(IdConexion, String)> = Vec::new();
+
+        for (id_conexion, conexion) in self.conexiones.iter_mut() {
+            for id_suscripcion in conexion.recargar(recarga) {
+                suscripciones_a_eliminar.push((*id_conexion, id_suscripcion));
+            }
+        }
+
+        for (id_conexion, id_suscripcion) in suscripciones_a_eliminar {
+            self.enviar_instruccion(Instruccion::Desuscribir(id_conexion, id_suscripcion));
         }
     }
 
     pub fn recibir_publicacion(&mut self, publicacion: Publicacion) {
+        let destino_traza: Option<String> = publicacion.destino_traza();
+        let mut entregas: Vec<EntregaTraza> = Vec::new();
+
         // Iterar sobre las suscripciones y enviar la publicación a cada una
         // Cabe destacar que solo itera en las suscripciones que coinciden con el tópico de la publicación
         for suscripcion in self.suscripciones.suscripciones_topico(&publicacion.topico) {
-            if let Some(conexion) = self.conexiones.get_mut(suscripcion.id_conexion()) {
-                conexion.escribir_publicacion_mensaje(
-                    &publicacion.mensaje(suscripcion.id().to_owned()),
-                );
+            // Las suscripciones de otros hilos las atienden esos hilos
+            if *suscripcion.id_hilo() != self.id {
+                continue;
+            }
+
+            let error: Option<String> = match self.conexiones.get_mut(suscripcion.id_conexion()) {
+                Some(conexion) => {
+                    self.mensajes_medidos += 1;
+                    conexion
+                        .escribir_publicacion_mensaje(
+                            &publicacion.mensaje(suscripcion.id().to_owned()),
+                        )
+                        .err()
+                        .map(|e| e.to_string())
+                }
+                None => Some("La conexión ya no existe".to_string()),
+            };
+
+            if destino_traza.is_some() {
+                entregas.push(EntregaTraza::new(suscripcion, error));
             }
         }
+
+        if let Some(destino) = destino_traza {
+            let salto = Salto::Entrega {
+                suscripciones: entregas,
+            };
+            self.trazar(destino, &publicacion.topico, salto);
+        }
     }
 
     pub fn recibir_publicacion_exacto(
@@ -137,9 +296,22 @@ impl Hilo {
         suscripcion: &Suscripcion,
         publicacion: Publicacion,
     ) {
-        if let Some(conexion) = self.conexiones.get_mut(suscripcion.id_conexion()) {
-            conexion
-                .escribir_publicacion_mensaje(&publicacion.mensaje(suscripcion.id().to_owned()));
+        let error: Option<String> = match self.conexiones.get_mut(suscripcion.id_conexion()) {
+            Some(conexion) => {
+                self.mensajes_medidos += 1;
+                conexion
+                    .escribir_publicacion_mensaje(&publicacion.mensaje(suscripcion.id().to_owned()))
+                    .err()
+                    .map(|e| e.to_string())
+            }
+            None => Some("La conexión ya no existe".to_string()),
+        };
+
+        if let Some(destino) = publicacion.destino_traza() {
+            let salto = Salto::Entrega {
+                suscripciones: vec![EntregaTraza::new(suscripcion, error)],
+            };
+            self.trazar(destino, &publicacion.topico, salto);
         }
     }
 
@@ -164,65 +336,182 @@ impl Hilo {
                 ));
             }
 
+            self.mensajes_medidos += salida.publicaciones.len() as u64;
             for publicacion in salida.publicaciones {
+                let duplicado: bool = self.deduplicador.es_duplicado(&publicacion);
+
+                if let Some(destino) = publicacion.destino_traza() {
+                    let salto = Salto::Ingreso {
+                        conexion: salida.id_conexion,
+                        duplicado,
+                    };
+                    self.trazar(destino, &publicacion.topico, salto);
+                }
+
+                // La conexión ya respondió +OK, pero no se entrega
+                if duplicado {
+                    self.registrador.debug(
+                        &format!("Publicación duplicada descartada en {}", publicacion.topico),
+                        Some(salida.id_conexion),
+                    );
+                    continue;
+                }
                 self.enviar_instruccion_si_mismo(Instruccion::NuevaPublicacion(publicacion));
             }
+
+            for tipo in salida.eventos {
+                self.publicar_evento(salida.id_conexion, tipo);
+            }
         }
     }
 
-    pub fn enviar_instruccion_si_mismo(&mut self, instruccion: Instruccion) {
-        if let Some(canal) = self.canales_enviar_instrucciones.get(&self.id) {
-            let r = canal.send(instruccion.clone());
-            if r.is_err() {
-                self.registrador
-                    .error("No se pudo enviar la instrucción a si", None);
-            }
+    /// Publica el evento de sistema de una de las conexiones del hilo
+    fn publicar_evento(&mut self, id_conexion: IdConexion, tipo: TipoEvento) {
+        if let Some(conexion) = self.conexiones.get(&id_conexion) {
+            let publicacion = conexion.evento(&self.id_servidor).publicacion(tipo);
+            self.enviar_instruccion_si_mismo(Instruccion::NuevaPublicacion(publicacion));
         }
     }
 
-    pub fn enviar_instruccion(&self, instruccion: Instruccion) {
-        for tx in self.canales_enviar_instrucciones.values() {
-            let r: Result<(), SendError<Instruccion>> = tx.send(instruccion.clone());
-            if r.is_err() {
-                self.registrador
-                    .error("No se pudo enviar la instrucción a otro proceso", None);
-            }
+    /// Publica un paso de una publicación trazada en el tópico de su
+    /// `Nats-Trace-Dest`
+    fn trazar(&mut self, destino: String, topico: &str, salto: Salto) {
+        let evento = EventoTraza::new(&self.id_servidor, self.id, topico, salto);
+        self.enviar_instruccion_si_mismo(Instruccion::NuevaPublicacion(
+            evento.publicacion(destino),
+        ));
+    }
+
+    pub fn enviar_instruccion_si_mismo(&mut self, instruccion: Instruccion) {
+        self.enviar_instruccion_a(self.id, instruccion);
+    }
+
+    pub fn enviar_instruccion(&mut self, instruccion: Instruccion) {
+        let hilos: Vec<IdHilo> = self.canales_enviar_instrucciones.keys().copied().collect();
+        for hilo in hilos {
+            self.enviar_instruccion_a(hilo, instruccion.clone());
         }
     }
 
-    pub fn enviar_instruccion_publicar(&self, publicacion: Publicacion) {
+    /// Envía la publicación a los hilos con suscripciones y a un suscriptor de
+    /// cada queue group. `topico_original` es el publicado, si se mapeó
+    pub fn enviar_instruccion_publicar(
+        &mut self,
+        publicacion: Publicacion,
+        topico_original: Option<String>,
+    ) {
         let hilos = self
             .suscripciones
             .hilos_suscriptos_topico(&publicacion.topico);
 
+        let exactas: Vec<Suscripcion> = self
+            .suscripciones
+            .grupos_topico(&publicacion.topico)
+            .into_iter()
+            .filter_map(|grupo| grupo.suscripcion_random().cloned())
+            .collect();
+
+        if let Some(destino) = publicacion.destino_traza() {
+            let mut hilos: Vec<IdHilo> = hilos.iter().copied().collect();
+            hilos.sort();
+            let salto = Salto::Ruteo {
+                topico_original,
+                hilos,
+                grupos: exactas.iter().map(SeleccionGrupo::new).collect(),
+            };
+            self.trazar(destino, &publicacion.topico, salto);
+        }
+
         for hilo in hilos {
-            if let Some(tx) = self.canales_enviar_instrucciones.get(&hilo) {
-                let r = tx.send(Instruccion::Publicar(publicacion.clone()));
-                if r.is_err() {
+            self.enviar_instruccion_a(hilo, Instruccion::Publicar(publicacion.clone()));
+        }
+
+        for suscripcion in exactas {
+            self.enviar_instruccion_a(
+                *suscripcion.id_hilo(),
+                Instruccion::PublicarExacto(suscripcion, publicacion.clone()),
+            );
+        }
+    }
+
+    /// Envía la instrucción a la cola del hilo sin bloquear. Si la cola está
+    /// llena, según la política, la instrucción espera a que haya lugar o se
+    /// descarta (solo si es una publicación)
+    fn enviar_instruccion_a(&mut self, hilo: IdHilo, instruccion: Instruccion) {
+        let canal = match self.canales_enviar_instrucciones.get(&hilo) {
+            Some(canal) => canal,
+            None => return,
+        };
+
+        // Para no desordenarlas, si ya hay instrucciones esperando para ese
+        // hilo la nueva va detrás
+        let instruccion: Instruccion = if self.en_espera.contains_key(&hilo) {
+            instruccion
+        } else {
+            match canal.intentar_enviar(instruccion) {
+                Envio::Enviada => return,
+                Envio::Llena(instruccion) => *instruccion,
+                Envio::Cerrada => {
                     self.registrador
                         .error("No se pudo enviar la instrucción a otro proceso", None);
+                    return;
+                }
+            }
+        };
+
+        if self.politica_cola == PoliticaCola::Descartar && instruccion.es_descartable() {
+            self.carga.sumar_instruccion_descartada();
+            self.registrador.debug(
+                &format!("Cola del hilo {} llena, se descarta una publicación", hilo),
+                None,
+            );
+
+            if let Some(publicacion) = instruccion.publicacion() {
+                if let Some(destino) = publicacion.destino_traza() {
+                    let topico: String = publicacion.topico.clone();
+                    self.trazar(destino, &topico, Salto::Descarte { hilo_destino: hilo });
                 }
             }
+            return;
         }
 
-        for grupo in self.suscripciones.grupos_topico(&publicacion.topico) {
-            if let Some(suscripcion) = grupo.suscripcion_random() {
-                if let Some(tx) = self.canales_enviar_instrucciones.get(suscripcion.id_hilo()) {
-                    let r = tx.send(Instruccion::PublicarExacto(
-                        suscripcion.clone(),
-                        publicacion.clone(),
-                    ));
-                    if r.is_err() {
-                        self.registrador
-                            .error("No se pudo enviar la instrucción a otro proceso", None);
+        self.en_espera
+            .entry(hilo)
+            .or_default()
+            .push_back(instruccion);
+    }
+
+    /// Envía las instrucciones que estaban esperando lugar en las colas de
+    /// otros hilos, en el orden en que se generaron
+    fn reenviar_en_espera(&mut self) {
+        for (hilo, instrucciones) in self.en_espera.iter_mut() {
+            let canal = match self.canales_enviar_instrucciones.get(hilo) {
+                Some(canal) => canal,
+                None => {
+                    instrucciones.clear();
+                    continue;
+                }
+            };
+
+            while let Some(instruccion) = instrucciones.pop_front() {
+                match canal.intentar_enviar(instruccion) {
+                    Envio::Enviada => {}
+                    Envio::Llena(instruccion) => {
+                        instrucciones.push_front(*instruccion);
+                        break;
                     }
+                    Envio::Cerrada => instrucciones.clear(),
                 }
             }
         }
+
+        self.en_espera
+            .retain(|_, instrucciones| !instrucciones.is_empty());
     }
 
     pub fn eliminar_conexiones_terminadas(&mut self) {
         let mut suscripciones_a_eliminar: Vec<(u64, String)> = Vec::new();
+        let mut desconexiones: Vec<Publicacion> = Vec::new();
 
         self.conexiones.retain(|id_conexion, conexion| {
             let esta_conectado = conexion.esta_conectado();
@@ -231,6 +520,16 @@ impl Hilo {
                 self.registrador
                     .info("Conexión terminada", Some(*id_conexion));
 
+                self.carga.restar_conexion();
+
+                if conexion.autenticado {
+                    desconexiones.push(
+                        conexion
+                            .evento(&self.id_servidor)
+                            .publicacion(TipoEvento::Desconexion),
+                    );
+                }
+
                 for suscripcion in self.suscripciones.suscripciones_conexion(id_conexion) {
                     suscripciones_a_eliminar.push((*id_conexion, suscripcion.id().to_owned()));
                 }
@@ -242,5 +541,9 @@ impl Hilo {
         for (id_conexion, id_suscripcion) in suscripciones_a_eliminar {
             self.enviar_instruccion(Instruccion::Desuscribir(id_conexion, id_suscripcion));
         }
+
+        for publicacion in desconexiones {
+            self.enviar_instruccion_si_mismo(Instruccion::NuevaPublicacion(publicacion));
+        }
     }
 }
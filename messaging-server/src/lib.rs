@@ -1,7 +1,17 @@
+pub mod autorizacion;
 pub mod conexion;
 pub mod cuenta;
+pub mod deduplicacion;
+pub mod escucha;
+pub mod esquema;
 pub mod hilo;
+pub mod limite_tasa;
+pub mod limites;
+pub mod mapeos;
+pub mod mqtt;
 pub mod publicacion;
+pub mod recarga;
 pub mod registrador;
 pub mod servidor;
+pub mod sistema;
 pub mod suscripciones;
@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    io,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+use lib::configuracion::Configuracion;
+
+/// Prefijo de las claves de configuración de las cuentas:
+/// `limite_cuenta.<usuario>.mensajes_por_segundo` y
+/// `limite_cuenta.<usuario>.bytes_por_segundo`
+pub const PREFIJO_LIMITE_CUENTA: &str = "limite_cuenta.";
+
+/// Qué se hace con una publicación que supera el límite de tasa
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PoliticaTasa {
+    /// Se deja de leer la conexión hasta que se pueda publicar, así el
+    /// cliente se frena solo cuando se llena el buffer del socket
+    #[default]
+    Demorar,
+    /// Se descarta la publicación y se responde `-ERR 'Rate Limit Exceeded'`
+    Error,
+}
+
+impl FromStr for PoliticaTasa {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "demorar" => Ok(PoliticaTasa::Demorar),
+            "error" => Ok(PoliticaTasa::Error),
+            _ => Err(format!("Política de tasa desconocida: {}", s)),
+        }
+    }
+}
+
+/// Balde de tokens que se llena a `por_segundo` tokens por segundo, hasta
+/// un segundo de ráfaga
+#[derive(Debug, Clone)]
+struct CuboTokens {
+    por_segundo: f64,
+    tokens: f64,
+    ultima_carga: Instant,
+}
+
+impl CuboTokens {
+    fn new(por_segundo: f64) -> Self {
+        Self {
+            por_segundo,
+            tokens: por_segundo,
+            ultima_carga: Instant::now(),
+        }
+    }
+
+    fn cargar(&mut self, ahora: Instant) {
+        let segundos: f64 = ahora.duration_since(self.ultima_carga).as_secs_f64();
+        self.tokens = (self.tokens + segundos * self.por_segundo).min(self.por_segundo);
+        self.ultima_carga = ahora;
+    }
+
+    /// Si hay tokens para `cantidad`. Lo que es más grande que el balde pasa
+    /// cuando el balde está lleno y lo deja en negativo, para que un mensaje
+    /// grande no quede trabado para siempre
+    fn alcanza(&self, cantidad: f64) -> bool {
+        self.tokens >= cantidad.min(self.por_segundo)
+    }
+
+    fn cambiar_tasa(&mut self, por_segundo: f64) {
+        self.por_segundo = por_segundo;
+        self.tokens = self.tokens.min(por_segundo);
+    }
+}
+
+/// Límite de mensajes y de bytes por segundo. Los que no se configuran no
+/// limitan
+#[derive(Debug, Clone, Default)]
+pub struct LimiteTasa {
+    mensajes: Option<CuboTokens>,
+    bytes: Option<CuboTokens>,
+}
+
+impl LimiteTasa {
+    /// Las tasas que no son positivas no limitan
+    pub fn new(mensajes_por_segundo: Option<f64>, bytes_por_segundo: Option<f64>) -> Self {
+        let mut limite: LimiteTasa = LimiteTasa::default();
+        limite.cambiar(mensajes_por_segundo, bytes_por_segundo);
+        limite
+    }
+
+    /// Cambia las tasas conservando los tokens que quedan
+    pub fn cambiar(&mut self, mensajes_por_segundo: Option<f64>, bytes_por_segundo: Option<f64>) {
+        cambiar_cubo(&mut self.mensajes, mensajes_por_segundo);
+        cambiar_cubo(&mut self.bytes, bytes_por_segundo);
+    }
+
+    /// Si se puede publicar un mensaje de `bytes` bytes. No consume tokens
+    pub fn permite(&mut self, bytes: usize) -> bool {
+        let ahora: Instant = Instant::now();
+
+        [(&mut self.mensajes, 1.), (&mut self.bytes, bytes as f64)]
+            .into_iter()
+            .all(|(cubo, cantidad)| match cubo {
+                Some(cubo) => {
+                    cubo.cargar(ahora);
+                    cubo.alcanza(cantidad)
+                }
+                None => true,
+            })
+    }
+
+    /// Descuenta un mensaje de `bytes` bytes
+    pub fn consumir(&mut self, bytes: usize) {
+        if let Some(cubo) = self.mensajes.as_mut() {
+            cubo.tokens -= 1.;
+        }
+        if let Some(cubo) = self.bytes.as_mut() {
+            cubo.tokens -= bytes as f64;
+        }
+    }
+}
+
+fn cambiar_cubo(cubo: &mut Option<CuboTokens>, por_segundo: Option<f64>) {
+    match (cubo.as_mut(), por_segundo.filter(|tasa| *tasa > 0.)) {
+        (Some(actual), Some(tasa)) => actual.cambiar_tasa(tasa),
+        (None, Some(tasa)) => *cubo = Some(CuboTokens::new(tasa)),
+        (_, None) => *cubo = None,
+    }
+}
+
+/// Las tasas de mensajes y de bytes de cada usuario en una configuración, ya
+/// validadas. Se leen antes de aplicarlas, así un error no deja nada a medias
+#[derive(Debug, Default)]
+pub struct TasasCuentas(HashMap<String, (Option<f64>, Option<f64>)>);
+
+/// Límites de tasa de cada usuario. Todas las conexiones de un usuario
+/// comparten el mismo límite, aunque estén en hilos distintos
+#[derive(Debug, Clone, Default)]
+pub struct LimitesCuentas {
+    limites: Arc<Mutex<HashMap<String, Arc<Mutex<LimiteTasa>>>>>,
+}
+
+impl LimitesCuentas {
+    pub fn desde_configuracion(configuracion: &Configuracion) -> io::Result<Self> {
+        let limites: LimitesCuentas = LimitesCuentas::default();
+        limites.actualizar(configuracion)?;
+        Ok(limites)
+    }
+
+    /// Aplica las tasas de la configuración. Los límites de los usuarios que
+    /// siguen configurados se cambian sin reemplazarlos, así las conexiones
+    /// que ya los usan ven las tasas nuevas
+    pub fn actualizar(&self, configuracion: &Configuracion) -> io::Result<()> {
+        self.reemplazar(LimitesCuentas::leer_tasas(configuracion)?);
+        Ok(())
+    }
+
+    /// Las tasas `limite_cuenta.<usuario>.<tasa>=<valor>` de la configuración
+    pub fn leer_tasas(configuracion: &Configuracion) -> io::Result<TasasCuentas> {
+        let mut tasas: HashMap<String, (Option<f64>, Option<f64>)> = HashMap::new();
+
+        for (clave, valor) in configuracion.con_prefijo(PREFIJO_LIMITE_CUENTA) {
+            let (usuario, tasa) = clave.rsplit_once('.').ok_or_else(|| {
+                error(format!(
+                    "Clave inválida: {}{}",
+                    PREFIJO_LIMITE_CUENTA, clave
+                ))
+            })?;
+            let valor: f64 = valor
+                .parse()
+                .ok()
+                .filter(|v: &f64| *v >= 0.)
+                .ok_or_else(|| error(format!("Tasa inválida para {}", clave)))?;
+
+            let entrada = tasas.entry(usuario.to_string()).or_default();
+            match tasa {
+                "mensajes_por_segundo" => entrada.0 = Some(valor),
+                "bytes_por_segundo" => entrada.1 = Some(valor),
+                _ => return Err(error(format!("Tasa desconocida: {}", clave))),
+            }
+        }
+
+        Ok(TasasCuentas(tasas))
+    }
+
+    /// Aplica las tasas leídas con `leer_tasas`
+    pub fn reemplazar(&self, tasas: TasasCuentas) {
+        let TasasCuentas(tasas) = tasas;
+        // Los límites se reemplazan completos, así que no importa si otro
+        // hilo dejó el lock envenenado
+        let mut limites = self.limites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        limites.retain(|usuario, _| tasas.contains_key(usuario));
+        for (usuario, (mensajes, bytes)) in tasas {
+            match limites.get(&usuario) {
+                Some(limite) => {
+                    if let Ok(mut limite) = limite.lock() {
+                        limite.cambiar(mensajes, bytes);
+                    }
+                }
+                None => {
+                    limites.insert(
+                        usuario,
+                        Arc::new(Mutex::new(LimiteTasa::new(mensajes, bytes))),
+                    );
+                }
+            }
+        }
+    }
+
+    /// El límite que comparten las conexiones del usuario, si tiene
+    pub fn de_usuario(&self, usuario: &str) -> Option<Arc<Mutex<LimiteTasa>>> {
+        self.limites.lock().ok()?.get(usuario).cloned()
+    }
+}
+
+fn error(mensaje: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, mensaje)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use lib::configuracion::Configuracion;
+
+    use super::{LimiteTasa, LimitesCuentas};
+
+    fn publicar(limite: &mut LimiteTasa, bytes: usize) -> bool {
+        let permite = limite.permite(bytes);
+        if permite {
+            limite.consumir(bytes);
+        }
+        permite
+    }
+
+    #[test]
+    fn probar_mensajes_y_bytes() {
+        let mut limite = LimiteTasa::new(Some(10.), None);
+        assert_eq!((0..20).filter(|_| publicar(&mut limite, 1)).count(), 10);
+
+        // Se recarga a 10 por segundo
+        thread::sleep(Duration::from_millis(220));
+        assert!(publicar(&mut limite, 1));
+        assert!(publicar(&mut limite, 1));
+
+        // Un mensaje más grande que el balde pasa con el balde lleno y lo deja en negativo
+        let mut limite = LimiteTasa::new(None, Some(100.));
+        assert!(publicar(&mut limite, 250));
+        assert!(!publicar(&mut limite, 1));
+
+        let mut limite = LimiteTasa::new(Some(0.), None);
+        assert!((0..1000).all(|_| publicar(&mut limite, 1)));
+    }
+
+    #[test]
+    fn probar_limites_de_cuentas() {
+        let cuentas = LimitesCuentas::desde_configuracion(&Configuracion::parsear(
+            "limite_cuenta.dron.1.mensajes_por_segundo=2\nlimite_cuenta.camaras.bytes_por_segundo=10",
+        ))
+        .unwrap();
+
+        assert!(cuentas.de_usuario("otro").is_none());
+        let dron = cuentas.de_usuario("dron.1").unwrap();
+        assert!(publicar(&mut dron.lock().unwrap(), 1));
+        assert!(publicar(&mut dron.lock().unwrap(), 1));
+        assert!(!publicar(
+            &mut cuentas.de_usuario("dron.1").unwrap().lock().unwrap(),
+            1
+        ));
+
+        // Al recargar se conserva el mismo límite y se quita el de los usuarios que ya no están
+        cuentas
+            .actualizar(&Configuracion::parsear(
+                "limite_cuenta.dron.1.mensajes_por_segundo=0",
+            ))
+            .unwrap();
+        assert!(publicar(&mut dron.lock().unwrap(), 1));
+        assert!(cuentas.de_usuario("camaras").is_none());
+
+        for linea in [
+            "limite_cuenta.a.otra=1",
+            "limite_cuenta.a.bytes_por_segundo=x",
+            "limite_cuenta.a=1",
+        ] {
+            assert!(LimitesCuentas::desde_configuracion(&Configuracion::parsear(linea)).is_err());
+        }
+    }
+}
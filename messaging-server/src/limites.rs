@@ -0,0 +1,155 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use lib::{
+    configuracion::Configuracion,
+    parseador::limites::{self, Limites},
+};
+
+use crate::limite_tasa::{LimiteTasa, PoliticaTasa};
+
+/// Límites que aplica el servidor a sus clientes. Todos se pueden cambiar
+/// desde la configuración con la clave del mismo nombre
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimitesServidor {
+    /// Tamaño máximo de los headers y el payload de una publicación. Se informa en el INFO
+    pub max_payload: usize,
+    /// Largo máximo de la línea de un comando
+    pub max_control_line: usize,
+    /// Cantidad máxima de conexiones abiertas en todo el servidor
+    pub max_conexiones: usize,
+    /// Cantidad máxima de suscripciones de cada conexión
+    pub max_suscripciones: usize,
+    /// Bytes que se pueden acumular para enviarle a una conexión antes de
+    /// desconectarla por no leerlos (consumidor lento)
+    pub max_pending: usize,
+    /// Cada cuánto se le envía un PING a una conexión que no envió nada (en
+    /// la configuración, en segundos)
+    pub ping_interval: Duration,
+    /// PINGs seguidos sin respuesta después de los cuales se desconecta al cliente
+    pub max_pings_out: u32,
+    /// Publicaciones por segundo de cada conexión (sin límite si no se configura)
+    pub max_mensajes_por_segundo: Option<f64>,
+    /// Bytes de headers y payload publicados por segundo de cada conexión
+    pub max_bytes_por_segundo: Option<f64>,
+    /// Qué se hace cuando una conexión o una cuenta supera su límite de tasa
+    pub politica_tasa: PoliticaTasa,
+}
+
+impl Default for LimitesServidor {
+    fn default() -> Self {
+        Self {
+            max_payload: limites::MAX_PAYLOAD_POR_DEFECTO,
+            max_control_line: limites::MAX_CONTROL_LINE_POR_DEFECTO,
+            max_conexiones: 64 * 1024,
+            max_suscripciones: usize::MAX,
+            max_pending: 64 * 1024 * 1024,
+            ping_interval: Duration::from_secs(20),
+            max_pings_out: 2,
+            max_mensajes_por_segundo: None,
+            max_bytes_por_segundo: None,
+            politica_tasa: PoliticaTasa::default(),
+        }
+    }
+}
+
+impl LimitesServidor {
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Self {
+        let defecto: LimitesServidor = LimitesServidor::default();
+
+        Self {
+            max_payload: configuracion
+                .obtener("max_payload")
+                .unwrap_or(defecto.max_payload),
+            max_control_line: configuracion
+                .obtener("max_control_line")
+                .unwrap_or(defecto.max_control_line),
+            max_conexiones: configuracion
+                .obtener("max_conexiones")
+                .unwrap_or(defecto.max_conexiones),
+            max_suscripciones: configuracion
+                .obtener("max_suscripciones")
+                .unwrap_or(defecto.max_suscripciones),
+            max_pending: configuracion
+                .obtener("max_pending")
+                .unwrap_or(defecto.max_pending),
+            ping_interval: configuracion
+                .obtener::<f64>("ping_interval")
+                .and_then(|segundos| Duration::try_from_secs_f64(segundos).ok())
+                .unwrap_or(defecto.ping_interval),
+            max_pings_out: configuracion
+                .obtener("max_pings_out")
+                .unwrap_or(defecto.max_pings_out),
+            max_mensajes_por_segundo: configuracion.obtener("max_mensajes_por_segundo"),
+            max_bytes_por_segundo: configuracion.obtener("max_bytes_por_segundo"),
+            politica_tasa: configuracion
+                .obtener("politica_tasa")
+                .unwrap_or(defecto.politica_tasa),
+        }
+    }
+
+    /// Los límites que le corresponden al parser de cada conexión
+    pub fn parseador(&self) -> Limites {
+        Limites::new(self.max_payload, self.max_control_line)
+    }
+
+    /// El límite de tasa propio de cada conexión
+    pub fn tasa(&self) -> LimiteTasa {
+        LimiteTasa::new(self.max_mensajes_por_segundo, self.max_bytes_por_segundo)
+    }
+}
+
+/// Cuenta las conexiones abiertas en todo el servidor
+#[derive(Debug, Clone, Default)]
+pub struct ContadorConexiones(Arc<AtomicUsize>);
+
+impl ContadorConexiones {
+    /// Reserva un lugar para una conexión nueva, si todavía no se llegó al
+    /// máximo. El lugar se libera cuando se descarta
+    pub fn reservar(&self, maximo: usize) -> Option<LugarConexion> {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |abiertas| {
+                (abiertas < maximo).then_some(abiertas + 1)
+            })
+            .ok()
+            .map(|_| LugarConexion(self.0.clone()))
+    }
+
+    pub fn abiertas(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// El lugar que ocupa una conexión en el `ContadorConexiones`
+#[derive(Debug)]
+pub struct LugarConexion(Arc<AtomicUsize>);
+
+impl Drop for LugarConexion {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContadorConexiones;
+
+    #[test]
+    fn se_liberan_los_lugares() {
+        let contador = ContadorConexiones::default();
+
+        let primero = contador.reservar(2);
+        let segundo = contador.reservar(2);
+        assert!(primero.is_some() && segundo.is_some());
+        assert!(contador.reservar(2).is_none());
+
+        drop(primero);
+        assert_eq!(contador.abiertas(), 1);
+        assert!(contador.reservar(2).is_some());
+    }
+}
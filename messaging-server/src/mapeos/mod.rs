@@ -0,0 +1,283 @@
+pub mod plantilla;
+
+use std::io;
+
+use lib::configuracion::{esquema::ErrorClave, Configuracion};
+use rand::{thread_rng, Rng};
+
+use self::plantilla::{Captura, Plantilla};
+
+/// Prefijo de las claves de configuración de los mapeos: `mapeo.<origen>=<destino>`
+pub const PREFIJO_MAPEO: &str = "mapeo.";
+
+/// Cambia el tópico de una publicación por otro antes de buscar a los
+/// suscriptores, para poder renombrar tópicos sin actualizar a la vez a
+/// todos los que publican.
+///
+/// El destino puede ser uno solo o varios con un porcentaje cada uno (se
+/// elige uno al azar en cada publicación). Si los porcentajes no suman 100,
+/// el resto de las publicaciones conserva el tópico original
+#[derive(Debug, Clone)]
+struct Mapeo {
+    /// Tokens del tópico de origen, que puede tener `*` y terminar en `>`
+    origen: Vec<String>,
+    destinos: Vec<(Plantilla, u32)>,
+}
+
+impl Mapeo {
+    /// `origen` y `destino` como en la configuración:
+    /// ```text
+    /// dron.*.informacion=drones.{{wildcard(1)}}.telemetria
+    /// incidentes.creado=incidentes.v2.creado:10,incidentes.creado:90
+    /// ```
+    fn parsear(origen: &str, destino: &str) -> Result<Self, String> {
+        let tokens: Vec<String> = origen.split('.').map(str::to_string).collect();
+        if let Some(i) = tokens.iter().position(|t| t.is_empty() || t == ">") {
+            if tokens[i].is_empty() || i != tokens.len() - 1 {
+                return Err(format!("Origen inválido: {}", origen));
+            }
+        }
+
+        let comodines: usize = tokens.iter().filter(|t| *t == "*").count();
+        let resto: bool = tokens.last().is_some_and(|t| t == ">");
+
+        let mut destinos: Vec<(Plantilla, u32)> = Vec::new();
+        for destino in separar_destinos(destino) {
+            let (destino, peso) = match destino.rsplit_once(':') {
+                Some((destino, peso)) => {
+                    let peso: u32 = peso
+                        .trim()
+                        .trim_end_matches('%')
+                        .parse()
+                        .map_err(|_| format!("Peso inválido en {}", origen))?;
+                    (destino.trim(), peso)
+                }
+                None => (destino, 100),
+            };
+
+            if peso == 0 || peso > 100 {
+                return Err(format!("Peso inválido en {}: {}", origen, peso));
+            }
+
+            destinos.push((Plantilla::parsear(destino, comodines, resto)?, peso));
+        }
+
+        if destinos.iter().map(|(_, peso)| peso).sum::<u32>() > 100 {
+            return Err(format!("Los pesos de {} suman más de 100", origen));
+        }
+
+        Ok(Self {
+            origen: tokens,
+            destinos,
+        })
+    }
+
+    fn es_exacto(&self) -> bool {
+        !self.origen.iter().any(|t| t == "*" || t == ">")
+    }
+
+    /// Los tokens del tópico que coinciden con los comodines del origen, o
+    /// `None` si el tópico no coincide
+    fn capturar<'a>(&self, topico: &'a str) -> Option<Captura<'a>> {
+        let tokens: Vec<&str> = topico.split('.').collect();
+        let mut captura = Captura {
+            topico,
+            ..Default::default()
+        };
+
+        for (i, origen) in self.origen.iter().enumerate() {
+            match origen.as_str() {
+                ">" if tokens.len() > i => {
+                    captura.resto = tokens[i..].to_vec();
+                    return Some(captura);
+                }
+                "*" => captura.comodines.push(tokens.get(i)?),
+                origen if tokens.get(i) == Some(&origen) => {}
+                _ => return None,
+            }
+        }
+
+        (tokens.len() == self.origen.len()).then_some(captura)
+    }
+
+    /// Elige el destino según los pesos. `None` si le toca conservar el tópico
+    fn elegir_destino(&self) -> Option<&Plantilla> {
+        if let [(plantilla, 100)] = self.destinos.as_slice() {
+            return Some(plantilla);
+        }
+
+        let mut sorteo: u32 = thread_rng().gen_range(0..100);
+        for (plantilla, peso) in self.destinos.iter() {
+            if sorteo < *peso {
+                return Some(plantilla);
+            }
+            sorteo -= peso;
+        }
+
+        None
+    }
+}
+
+/// Separa los destinos por las comas que no están dentro de una función
+/// (`{{partition(3,1)}}`)
+fn separar_destinos(texto: &str) -> Vec<&str> {
+    let mut destinos: Vec<&str> = Vec::new();
+    let mut inicio: usize = 0;
+    let mut en_funcion: bool = false;
+
+    for (i, caracter) in texto.char_indices() {
+        match caracter {
+            '{' => en_funcion = true,
+            '}' => en_funcion = false,
+            ',' if !en_funcion => {
+                destinos.push(texto[inicio..i].trim());
+                inicio = i + 1;
+            }
+            _ => {}
+        }
+    }
+    destinos.push(texto[inicio..].trim());
+
+    destinos
+}
+
+/// Los mapeos de tópicos del servidor. Se aplica el primero que coincide:
+/// los orígenes sin comodines tienen prioridad y después se sigue el orden
+/// alfabético de los orígenes
+#[derive(Debug, Clone, Default)]
+pub struct Mapeos {
+    mapeos: Vec<Mapeo>,
+}
+
+impl Mapeos {
+    /// Lee las claves `mapeo.<origen>` de la configuración
+    pub fn desde_configuracion(configuracion: &Configuracion) -> io::Result<Self> {
+        let mut mapeos: Vec<Mapeo> = configuracion
+            .con_prefijo(PREFIJO_MAPEO)
+            .iter()
+            .map(|(origen, destino)| Mapeo::parsear(origen, destino))
+            .collect::<Result<_, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // El orden alfabético se mantiene entre los de cada tipo
+        mapeos.sort_by_key(|mapeo| !mapeo.es_exacto());
+
+        Ok(Self { mapeos })
+    }
+
+    /// Los errores de cada mapeo inválido, como los de las claves del
+    /// esquema para informarlos junto con ellos
+    pub fn validar(configuracion: &Configuracion) -> Vec<ErrorClave> {
+        configuracion
+            .con_prefijo(PREFIJO_MAPEO)
+            .into_iter()
+            .filter_map(|(origen, destino)| {
+                let error: String = Mapeo::parsear(&origen, &destino).err()?;
+                Some(ErrorClave::Invalida {
+                    clave: format!("{}{}", PREFIJO_MAPEO, origen),
+                    valor: destino,
+                    esperado: format!("un mapeo válido: {}", error),
+                })
+            })
+            .collect()
+    }
+
+    /// El tópico al que se tiene que publicar, o `None` si queda igual
+    pub fn aplicar(&self, topico: &str) -> Option<String> {
+        for mapeo in self.mapeos.iter() {
+            if let Some(captura) = mapeo.capturar(topico) {
+                return mapeo
+                    .elegir_destino()
+                    .map(|plantilla| plantilla.aplicar(&captura));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::configuracion::Configuracion;
+
+    use super::Mapeos;
+
+    fn mapeos(lineas: &str) -> Mapeos {
+        Mapeos::desde_configuracion(&Configuracion::parsear(lineas)).unwrap()
+    }
+
+    #[test]
+    fn probar_comodines_y_resto() {
+        let mapeos = mapeos(
+            "mapeo.dron.*.informacion=drones.{{wildcard(1)}}.telemetria\n\
+             mapeo.a.*.*=b.{{ wildcard(2) }}.{{wildcard(1)}}\n\
+             mapeo.viejo.>=nuevo.>\n\
+             mapeo.a.x.y=exacto",
+        );
+
+        assert_eq!(
+            mapeos.aplicar("dron.7.informacion"),
+            Some("drones.7.telemetria".to_string())
+        );
+        assert_eq!(mapeos.aplicar("a.1.2"), Some("b.2.1".to_string()));
+        assert_eq!(mapeos.aplicar("a.x.y"), Some("exacto".to_string()));
+        assert_eq!(mapeos.aplicar("viejo.c.d"), Some("nuevo.c.d".to_string()));
+        assert_eq!(mapeos.aplicar("viejo"), None);
+        assert_eq!(mapeos.aplicar("dron.7.informacion.extra"), None);
+        assert_eq!(mapeos.aplicar("dron.7"), None);
+    }
+
+    #[test]
+    fn probar_pesos_y_particiones() {
+        let mapeos = mapeos(
+            "mapeo.canario=canario.v2:25%,canario.v1:75%\n\
+             mapeo.parcial=parcial.v2:50\n\
+             mapeo.pedidos.*=pedidos.{{partition(3,1)}}.{{wildcard(1)}}",
+        );
+
+        let mut v2 = 0;
+        let mut sin_cambios = 0;
+        for _ in 0..1000 {
+            match mapeos.aplicar("canario").as_deref() {
+                Some("canario.v2") => v2 += 1,
+                Some("canario.v1") => {}
+                otro => panic!("{:?}", otro),
+            }
+            if mapeos.aplicar("parcial").is_none() {
+                sin_cambios += 1;
+            }
+        }
+        assert!((150..350).contains(&v2));
+        assert!((350..650).contains(&sin_cambios));
+
+        // Un mismo token siempre va a la misma partición
+        let pedido = mapeos.aplicar("pedidos.abc").unwrap();
+        assert!(pedido.ends_with(".abc"));
+        assert_eq!(mapeos.aplicar("pedidos.abc").unwrap(), pedido);
+        let particion: u32 = pedido.split('.').nth(1).unwrap().parse().unwrap();
+        assert!(particion < 3);
+    }
+
+    #[test]
+    fn probar_errores() {
+        for linea in [
+            "mapeo.a.*=b.{{wildcard(2)}}",
+            "mapeo.a=b.>",
+            "mapeo.a=b:60,c:60",
+            "mapeo.a=b:0",
+            "mapeo.a=b.{{wildcard(1)",
+            "mapeo.a.*=b.{{otra(1)}}",
+            "mapeo.a.>.b=c",
+            "mapeo.a.*=b.{{partition(0,1)}}",
+            "mapeo.a.*=b.{{partition(4294967296,1)}}",
+        ] {
+            let configuracion = Configuracion::parsear(linea);
+            assert!(
+                Mapeos::desde_configuracion(&configuracion).is_err(),
+                "{}",
+                linea
+            );
+            assert_eq!(Mapeos::validar(&configuracion).len(), 1, "{}", linea);
+        }
+    }
+}
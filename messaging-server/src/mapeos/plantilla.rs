@@ -0,0 +1,183 @@
+/// Una parte de un token del tópico de destino
+#[derive(Debug, Clone, PartialEq)]
+enum Parte {
+    Texto(String),
+    /// `{{wildcard(n)}}`: el token que coincidió con el `n`-ésimo `*` del origen
+    Comodin(usize),
+    /// `{{partition(cantidad,n,m,...)}}`: un número entre 0 y `cantidad - 1`
+    /// que depende de los tokens de los comodines indicados (o de todo el
+    /// tópico si no se indica ninguno), siempre el mismo para los mismos tokens
+    Particion(u32, Vec<usize>),
+}
+
+/// Un token del destino: `>` o partes concatenadas (`a{{wildcard(1)}}`)
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Partes(Vec<Parte>),
+    /// Los tokens que coincidieron con el `>` del origen
+    Resto,
+}
+
+/// Tópico de destino de un mapeo, con referencias a los tokens del origen
+///
+/// ```text
+/// drones.{{wildcard(1)}}.telemetria
+/// pedidos.{{partition(3,1)}}.{{wildcard(1)}}
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plantilla {
+    tokens: Vec<Token>,
+}
+
+/// Tokens del tópico original que usan las plantillas
+#[derive(Debug, Default)]
+pub struct Captura<'a> {
+    /// En el orden de los `*` del origen
+    pub comodines: Vec<&'a str>,
+    /// Lo que coincidió con el `>` del origen
+    pub resto: Vec<&'a str>,
+    /// El tópico original completo
+    pub topico: &'a str,
+}
+
+impl Plantilla {
+    /// Parsea el destino. `comodines` es la cantidad de `*` del origen y `resto`
+    /// si termina en `>`, para validar las referencias
+    pub fn parsear(texto: &str, comodines: usize, resto: bool) -> Result<Self, String> {
+        if texto.is_empty() {
+            return Err("El destino está vacío".to_string());
+        }
+
+        let mut tokens: Vec<Token> = Vec::new();
+
+        for token in texto.split('.') {
+            if token == ">" {
+                if !resto {
+                    return Err(format!(
+                        "{}: el destino usa '>' pero el origen no termina en '>'",
+                        texto
+                    ));
+                }
+                tokens.push(Token::Resto);
+                continue;
+            }
+
+            let partes = parsear_partes(token)?;
+            for parte in partes.iter() {
+                let indices: &[usize] = match parte {
+                    Parte::Comodin(indice) => std::slice::from_ref(indice),
+                    Parte::Particion(_, indices) => indices,
+                    Parte::Texto(_) => &[],
+                };
+                if let Some(indice) = indices.iter().find(|i| **i == 0 || **i > comodines) {
+                    return Err(format!(
+                        "{}: el origen no tiene el comodín {}",
+                        texto, indice
+                    ));
+                }
+            }
+            tokens.push(Token::Partes(partes));
+        }
+
+        Ok(Self { tokens })
+    }
+
+    /// El tópico de destino para los tokens capturados
+    pub fn aplicar(&self, captura: &Captura) -> String {
+        let mut tokens: Vec<String> = Vec::with_capacity(self.tokens.len());
+
+        for token in self.tokens.iter() {
+            match token {
+                Token::Resto => tokens.push(captura.resto.join(".")),
+                Token::Partes(partes) => {
+                    let mut texto = String::new();
+                    for parte in partes {
+                        match parte {
+                            Parte::Texto(t) => texto.push_str(t),
+                            Parte::Comodin(indice) => texto.push_str(captura.comodines[indice - 1]),
+                            Parte::Particion(cantidad, indices) => {
+                                let clave: String = if indices.is_empty() {
+                                    captura.topico.to_string()
+                                } else {
+                                    indices
+                                        .iter()
+                                        .map(|i| captura.comodines[i - 1])
+                                        .collect::<Vec<&str>>()
+                                        .join(".")
+                                };
+                                texto.push_str(&(fnv1a(clave.as_bytes()) % cantidad).to_string());
+                            }
+                        }
+                    }
+                    tokens.push(texto);
+                }
+            }
+        }
+
+        tokens.join(".")
+    }
+}
+
+/// Separa el texto de las funciones `{{...}}` de un token
+fn parsear_partes(token: &str) -> Result<Vec<Parte>, String> {
+    let mut partes: Vec<Parte> = Vec::new();
+    let mut resto: &str = token;
+
+    while let Some(inicio) = resto.find("{{") {
+        if inicio > 0 {
+            partes.push(Parte::Texto(resto[..inicio].to_string()));
+        }
+        let fin = match resto[inicio..].find("}}") {
+            Some(fin) => inicio + fin,
+            None => return Err(format!("{}: falta cerrar '}}}}'", token)),
+        };
+        partes.push(parsear_funcion(&resto[inicio + 2..fin])?);
+        resto = &resto[fin + 2..];
+    }
+
+    if !resto.is_empty() {
+        partes.push(Parte::Texto(resto.to_string()));
+    }
+    if partes.is_empty() {
+        return Err("El destino tiene un token vacío".to_string());
+    }
+
+    Ok(partes)
+}
+
+/// `wildcard(n)` o `partition(cantidad,n,...)`
+fn parsear_funcion(funcion: &str) -> Result<Parte, String> {
+    let funcion: String = funcion.split_whitespace().collect();
+
+    let (nombre, argumentos) = match funcion.strip_suffix(')').and_then(|f| f.split_once('(')) {
+        Some(partes) => partes,
+        None => return Err(format!("Función inválida: {}", funcion)),
+    };
+
+    let argumentos: Vec<usize> = argumentos
+        .split(',')
+        .filter(|a| !a.is_empty())
+        .map(|a| a.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Argumentos inválidos: {}", funcion))?;
+
+    match (nombre.to_lowercase().as_str(), argumentos.as_slice()) {
+        ("wildcard", [indice]) => Ok(Parte::Comodin(*indice)),
+        ("partition", [cantidad, indices @ ..]) => match u32::try_from(*cantidad) {
+            Ok(cantidad) if cantidad > 0 => Ok(Parte::Particion(cantidad, indices.to_vec())),
+            _ => Err(format!("Cantidad de particiones inválida: {}", funcion)),
+        },
+        _ => Err(format!("Función inválida: {}", funcion)),
+    }
+}
+
+/// Hash FNV-1a de 32 bits. Es estable entre ejecuciones (a diferencia del de
+/// `HashMap`), así que un mismo token siempre va a la misma partición
+fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
@@ -0,0 +1,634 @@
+pub mod paquete;
+pub mod retenidos;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use lib::{
+    parseador::{mensaje::Mensaje, parametros_conectar::ParametrosConectar, Parseador},
+    stream::Stream,
+};
+
+use crate::suscripciones::topico::Topico;
+
+use self::{paquete::PaqueteMqtt, retenidos::MensajesRetenidos};
+
+/// Cuántos bytes de paquetes sin enviar se aceptan antes de que `write`
+/// devuelva `WouldBlock` (y la conexión los guarde como pendientes)
+const MAX_SALIDA: usize = 64 * 1024;
+
+/// Convierte un tópico de MQTT (`a/b/c`) en un tópico de NATS (`a.b.c`). Los
+/// tópicos de publicación no pueden tener comodines
+pub fn topico_a_subject(topico: &str) -> Option<String> {
+    if topico.is_empty() || topico.contains(['+', '#']) {
+        return None;
+    }
+
+    Some(topico.replace('/', "."))
+}
+
+/// Convierte un filtro de suscripción de MQTT en un patrón de NATS: `+`
+/// equivale a `*` y `#` (solo al final) a `>`
+pub fn filtro_a_patron(filtro: &str) -> Option<String> {
+    let niveles: Vec<&str> = filtro.split('/').collect();
+    let mut patron: Vec<&str> = Vec::with_capacity(niveles.len());
+
+    for (i, nivel) in niveles.iter().enumerate() {
+        match *nivel {
+            "+" => patron.push("*"),
+            "#" if i == niveles.len() - 1 => patron.push(">"),
+            nivel if nivel.contains(['+', '#']) => return None,
+            nivel => patron.push(nivel),
+        }
+    }
+
+    Some(patron.join("."))
+}
+
+/// Convierte un tópico de NATS en el tópico de MQTT con el que se entrega
+pub fn subject_a_topico(subject: &str) -> String {
+    subject.replace('.', "/")
+}
+
+/// Comando enviado al servidor que espera un `+OK` o `-ERR`
+#[derive(Debug)]
+enum Pendiente {
+    Conectar,
+    Publicar {
+        /// Si es QoS 1 hay que responder con PUBACK
+        id_paquete: Option<u16>,
+        /// Se guarda como retenido cuando el servidor acepta la publicación
+        retenido: Option<(String, Bytes)>,
+    },
+    Suscribir {
+        id_paquete: u16,
+        sid: String,
+        qos: u8,
+        /// Si es el último filtro del SUBSCRIBE (el que dispara el SUBACK)
+        ultimo: bool,
+    },
+    Desuscribir {
+        /// Solo en el último filtro del UNSUBSCRIBE
+        id_paquete: Option<u16>,
+    },
+}
+
+/// Comando enviado al servidor que espera un `PONG`
+#[derive(Debug)]
+enum PendientePong {
+    PingCliente,
+    /// Un UNSUBSCRIBE sin filtros conocidos solo espera a que el servidor
+    /// procese lo anterior para responder
+    Desuscribir(u16),
+}
+
+/// Suscripción de un cliente MQTT
+#[derive(Debug)]
+struct SuscripcionMqtt {
+    filtro: String,
+    patron: String,
+    qos: u8,
+}
+
+/// Envuelve un stream del lado del servidor y traduce MQTT 3.1.1 al protocolo
+/// de texto, para que `Conexion` atienda a los clientes MQTT igual que a los
+/// de NATS: lo que se lee son los comandos que habría enviado un cliente NATS
+/// y lo que se escribe son las respuestas del servidor, que se convierten en
+/// paquetes MQTT.
+///
+/// Soporta QoS 0 y 1 (se confirma con PUBACK cuando el servidor acepta la
+/// publicación, pero no se reenvían los mensajes sin confirmar) y los mensajes
+/// retenidos, que se comparten entre todas las conexiones MQTT
+pub struct StreamMqtt<S: Read + Write + Send> {
+    stream: S,
+    retenidos: MensajesRetenidos,
+    max_paquete: usize,
+    conectado: bool,
+    cerrado: bool,
+    /// Bytes recibidos del cliente que todavía no forman un paquete completo
+    entrada: Vec<u8>,
+    /// Comandos del protocolo de texto que todavía no leyó la conexión
+    comandos: Vec<u8>,
+    /// Paquetes que el stream todavía no aceptó
+    salida: Vec<u8>,
+    /// Las respuestas del servidor
+    parseador: Parseador,
+    pendientes: VecDeque<Pendiente>,
+    pendientes_pong: VecDeque<PendientePong>,
+    /// Por sid
+    suscripciones: HashMap<String, SuscripcionMqtt>,
+    proximo_sid: u64,
+    proximo_id_paquete: u16,
+    /// Códigos del SUBACK que se está armando
+    codigos_suback: Vec<u8>,
+    keep_alive: Duration,
+    ultima_actividad: Instant,
+}
+
+impl<S: Read + Write + Send> StreamMqtt<S> {
+    pub fn servidor(stream: S, retenidos: MensajesRetenidos, max_paquete: usize) -> Self {
+        Self {
+            stream,
+            retenidos,
+            max_paquete,
+            conectado: false,
+            cerrado: false,
+            entrada: Vec::new(),
+            comandos: Vec::new(),
+            salida: Vec::new(),
+            parseador: Parseador::new().con_respuestas(),
+            pendientes: VecDeque::new(),
+            pendientes_pong: VecDeque::new(),
+            suscripciones: HashMap::new(),
+            proximo_sid: 1,
+            proximo_id_paquete: 1,
+            codigos_suback: Vec::new(),
+            keep_alive: Duration::ZERO,
+            ultima_actividad: Instant::now(),
+        }
+    }
+
+    /// Traduce los paquetes completos que hay en `entrada` a comandos
+    fn decodificar_paquetes(&mut self) -> io::Result<()> {
+        while !self.cerrado {
+            let (paquete, largo) = match paquete::decodificar(&self.entrada, self.max_paquete) {
+                Ok(Some(paquete)) => paquete,
+                Ok(None) => break,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            self.entrada.drain(..largo);
+            self.ultima_actividad = Instant::now();
+
+            self.procesar_paquete(paquete)?;
+        }
+
+        Ok(())
+    }
+
+    fn procesar_paquete(&mut self, paquete: PaqueteMqtt) -> io::Result<()> {
+        if !self.conectado && !matches!(paquete, PaqueteMqtt::Conectar { .. }) {
+            return Err(error("El primer paquete tiene que ser CONNECT"));
+        }
+
+        match paquete {
+            PaqueteMqtt::Conectar { .. } if self.conectado => {
+                return Err(error("Se recibió un segundo CONNECT"));
+            }
+            PaqueteMqtt::Conectar { nivel, .. } if nivel != 3 && nivel != 4 => {
+                self.salida
+                    .extend(paquete::connack(paquete::PROTOCOLO_NO_SOPORTADO));
+                self.cerrado = true;
+            }
+            PaqueteMqtt::Conectar {
+                id_cliente,
+                user,
+                pass,
+                keep_alive,
+                ..
+            } => {
+                self.conectado = true;
+                self.keep_alive = Duration::from_secs(keep_alive as u64);
+
+                let parametros = ParametrosConectar {
+                    user,
+                    pass,
+                    name: Some(id_cliente).filter(|id| !id.is_empty()),
+                    pedantic: None,
+                };
+                self.comando(&format!("CONNECT {}\r\n", parametros.hacia_json()));
+                self.pendientes.push_back(Pendiente::Conectar);
+            }
+            PaqueteMqtt::Publicar {
+                topico,
+                payload,
+                qos,
+                retener,
+                id_paquete,
+            } => {
+                if qos > 1 {
+                    return Err(error("QoS 2 no está soportado"));
+                }
+                let subject = match topico_a_subject(&topico) {
+                    Some(subject) => subject,
+                    None => return Err(error("Tópico de publicación inválido")),
+                };
+
+                self.comando(&format!("PUB {} {}\r\n", subject, payload.len()));
+                self.comandos.extend_from_slice(&payload);
+                self.comandos.extend_from_slice(b"\r\n");
+
+                self.pendientes.push_back(Pendiente::Publicar {
+                    id_paquete,
+                    retenido: retener.then_some((subject, payload)),
+                });
+            }
+            // No se reenvían los mensajes sin confirmar, así que no hay nada que hacer
+            PaqueteMqtt::PubAck(_) => {}
+            PaqueteMqtt::Suscribir {
+                id_paquete,
+                filtros,
+            } => {
+                self.codigos_suback.clear();
+                let cantidad = filtros.len();
+
+                for (i, (filtro, qos)) in filtros.into_iter().enumerate() {
+                    let ultimo = i == cantidad - 1;
+
+                    let patron = match filtro_a_patron(&filtro) {
+                        Some(patron) => patron,
+                        None => {
+                            self.codigos_suback.push(paquete::SUSCRIPCION_RECHAZADA);
+                            if ultimo {
+                                self.enviar_suback(id_paquete);
+                            }
+                            continue;
+                        }
+                    };
+
+                    // Suscribirse otra vez al mismo filtro reemplaza la suscripción
+                    if let Some(sid) = self.sid_de_filtro(&filtro) {
+                        self.suscripciones.remove(&sid);
+                        self.comando(&format!("UNSUB {}\r\n", sid));
+                        self.pendientes
+                            .push_back(Pendiente::Desuscribir { id_paquete: None });
+                    }
+
+                    let sid = self.proximo_sid.to_string();
+                    self.proximo_sid += 1;
+
+                    self.comando(&format!("SUB {} {}\r\n", patron, sid));
+                    self.suscripciones.insert(
+                        sid.clone(),
+                        SuscripcionMqtt {
+                            filtro,
+                            patron,
+                            qos: qos.min(1),
+                        },
+                    );
+                    self.pendientes.push_back(Pendiente::Suscribir {
+                        id_paquete,
+                        sid,
+                        qos: qos.min(1),
+                        ultimo,
+                    });
+                }
+            }
+            PaqueteMqtt::Desuscribir {
+                id_paquete,
+                filtros,
+            } => {
+                let sids: Vec<String> = filtros
+                    .iter()
+                    .filter_map(|filtro| self.sid_de_filtro(filtro))
+                    .collect();
+
+                if sids.is_empty() {
+                    self.comando("PING\r\n");
+                    self.pendientes_pong
+                        .push_back(PendientePong::Desuscribir(id_paquete));
+                }
+
+                for (i, sid) in sids.iter().enumerate() {
+                    self.suscripciones.remove(sid);
+                    self.comando(&format!("UNSUB {}\r\n", sid));
+                    self.pendientes.push_back(Pendiente::Desuscribir {
+                        id_paquete: (i == sids.len() - 1).then_some(id_paquete),
+                    });
+                }
+            }
+            PaqueteMqtt::PingReq => {
+                self.comando("PING\r\n");
+                self.pendientes_pong.push_back(PendientePong::PingCliente);
+            }
+            PaqueteMqtt::Desconectar => self.cerrado = true,
+        }
+
+        Ok(())
+    }
+
+    fn comando(&mut self, comando: &str) {
+        self.comandos.extend_from_slice(comando.as_bytes());
+    }
+
+    fn sid_de_filtro(&self, filtro: &str) -> Option<String> {
+        self.suscripciones
+            .iter()
+            .find(|(_, suscripcion)| suscripcion.filtro == filtro)
+            .map(|(sid, _)| sid.clone())
+    }
+
+    fn nuevo_id_paquete(&mut self) -> u16 {
+        let id = self.proximo_id_paquete;
+        // El id 0 no es válido
+        self.proximo_id_paquete = self.proximo_id_paquete.checked_add(1).unwrap_or(1);
+        id
+    }
+
+    fn enviar_suback(&mut self, id_paquete: u16) {
+        let codigos = std::mem::take(&mut self.codigos_suback);
+        self.salida.extend(paquete::suback(id_paquete, &codigos));
+    }
+
+    /// Entrega al cliente una publicación con el QoS de la suscripción
+    fn enviar_publicacion(&mut self, topico: &str, payload: &[u8], qos: u8, retener: bool) {
+        let id_paquete = (qos > 0).then(|| self.nuevo_id_paquete());
+        self.salida.extend(paquete::publish(
+            &subject_a_topico(topico),
+            payload,
+            qos,
+            retener,
+            id_paquete,
+        ));
+    }
+
+    /// Traduce una respuesta del servidor a paquetes para el cliente
+    fn procesar_mensaje(&mut self, mensaje: Mensaje) {
+        match mensaje {
+            Mensaje::RespuestaOk() => self.procesar_respuesta(true),
+            Mensaje::RespuestaErr(_) => self.procesar_respuesta(false),
+            Mensaje::Publicacion(topico, sid, _, payload)
+            | Mensaje::PublicacionConHeader(topico, sid, _, _, payload) => {
+                if let Some(qos) = self.suscripciones.get(&sid).map(|s| s.qos) {
+                    self.enviar_publicacion(&topico, &payload, qos, false);
+                }
+            }
+            Mensaje::Pong() => match self.pendientes_pong.pop_front() {
+                Some(PendientePong::PingCliente) => self.salida.extend(paquete::pingresp()),
+                Some(PendientePong::Desuscribir(id_paquete)) => {
+                    self.salida.extend(paquete::unsuback(id_paquete))
+                }
+                None => {}
+            },
+            // Solo se responde por el cliente si sigue activo, para que el
+            // servidor pueda detectar las conexiones abandonadas
+            Mensaje::Ping()
+                if self.keep_alive.is_zero()
+                    || self.ultima_actividad.elapsed() <= self.keep_alive.mul_f32(1.5) =>
+            {
+                self.comando("PONG\r\n");
+            }
+            _ => {}
+        }
+    }
+
+    fn procesar_respuesta(&mut self, ok: bool) {
+        match self.pendientes.pop_front() {
+            Some(Pendiente::Conectar) => {
+                let codigo = if ok {
+                    paquete::CONEXION_ACEPTADA
+                } else {
+                    paquete::NO_AUTORIZADO
+                };
+                self.salida.extend(paquete::connack(codigo));
+            }
+            Some(Pendiente::Publicar {
+                id_paquete,
+                retenido,
+            }) => {
+                if !ok {
+                    return;
+                }
+                if let Some((subject, payload)) = retenido {
+                    self.retenidos.guardar(subject, payload);
+                }
+                if let Some(id_paquete) = id_paquete {
+                    self.salida.extend(paquete::puback(id_paquete));
+                }
+            }
+            Some(Pendiente::Suscribir {
+                id_paquete,
+                sid,
+                qos,
+                ultimo,
+            }) => {
+                let patron = if ok {
+                    self.codigos_suback.push(qos);
+                    self.suscripciones.get(&sid).map(|s| s.patron.clone())
+                } else {
+                    self.codigos_suback.push(paquete::SUSCRIPCION_RECHAZADA);
+                    self.suscripciones.remove(&sid);
+                    None
+                };
+
+                if ultimo {
+                    self.enviar_suback(id_paquete);
+                }
+
+                // Los retenidos se envían después del SUBACK
+                if let Some(Ok(topico)) = patron.map(Topico::new) {
+                    for (subject, payload) in self.retenidos.coincidentes(&topico) {
+                        self.enviar_publicacion(&subject, &payload, qos, true);
+                    }
+                }
+            }
+            Some(Pendiente::Desuscribir {
+                id_paquete: Some(id_paquete),
+            }) => self.salida.extend(paquete::unsuback(id_paquete)),
+            Some(Pendiente::Desuscribir { id_paquete: None }) | None => {}
+        }
+    }
+
+    /// Escribe todo lo que acepte el stream de `salida`
+    fn vaciar_salida(&mut self) -> io::Result<()> {
+        while !self.salida.is_empty() {
+            match self.stream.write(&self.salida) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.salida.drain(..n);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.stream.flush()
+    }
+
+    /// Como `vaciar_salida`, pero si el stream no acepta más bytes no es un error
+    fn intentar_vaciar_salida(&mut self) -> io::Result<()> {
+        match self.vaciar_salida() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            resultado => resultado,
+        }
+    }
+}
+
+fn error(razon: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, razon)
+}
+
+impl<S: Read + Write + Send> Read for StreamMqtt<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Se aprovecha para enviar lo que quedó pendiente
+        self.intentar_vaciar_salida()?;
+
+        loop {
+            if !self.comandos.is_empty() {
+                let largo = buf.len().min(self.comandos.len());
+                buf[..largo].copy_from_slice(&self.comandos[..largo]);
+                self.comandos.drain(..largo);
+                return Ok(largo);
+            }
+
+            if self.cerrado {
+                self.intentar_vaciar_salida()?;
+                return Ok(0);
+            }
+
+            let mut lectura = [0u8; 16 * 1024];
+            let leidos = self.stream.read(&mut lectura)?;
+            if leidos == 0 {
+                return Ok(0);
+            }
+            self.entrada.extend_from_slice(&lectura[..leidos]);
+
+            self.decodificar_paquetes()?;
+            self.intentar_vaciar_salida()?;
+        }
+    }
+}
+
+impl<S: Read + Write + Send> Write for StreamMqtt<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.intentar_vaciar_salida()?;
+        if self.salida.len() > MAX_SALIDA {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        self.parseador.agregar_bytes(buf);
+        while let Some(mensaje) = self.parseador.proximo_mensaje() {
+            self.procesar_mensaje(mensaje);
+        }
+        self.intentar_vaciar_salida()?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.intentar_vaciar_salida()
+    }
+}
+
+impl<S: Read + Write + Send> Stream for StreamMqtt<S> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use lib::stream::mock_handler::MockHandler;
+
+    use super::{
+        filtro_a_patron, paquete, retenidos::MensajesRetenidos, topico_a_subject, StreamMqtt,
+    };
+
+    /// Lee todo lo que la conexión recibiría del cliente
+    fn leer_comandos<S: Read>(stream: &mut S) -> String {
+        let mut buffer = [0u8; 1024];
+        let mut comandos = String::new();
+        while let Ok(leidos) = stream.read(&mut buffer) {
+            if leidos == 0 {
+                break;
+            }
+            comandos.push_str(&String::from_utf8_lossy(&buffer[..leidos]));
+        }
+        comandos
+    }
+
+    fn recibidos(mock: &MockHandler) -> Vec<u8> {
+        mock.recibir.try_iter().flatten().collect()
+    }
+
+    /// Arma un paquete con el largo restante de un byte
+    fn paquete_cliente(primer_byte: u8, campos: &[&[u8]]) -> Vec<u8> {
+        let cuerpo: Vec<u8> = campos.concat();
+        let mut bytes = vec![primer_byte, cuerpo.len() as u8];
+        bytes.extend(cuerpo);
+        bytes
+    }
+
+    fn texto(texto: &str) -> Vec<u8> {
+        let mut bytes = (texto.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(texto.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn probar_traduccion_de_topicos() {
+        assert_eq!(topico_a_subject("a/b/c"), Some("a.b.c".to_string()));
+        assert_eq!(topico_a_subject("a/+/c"), None);
+        assert_eq!(filtro_a_patron("a/+/c"), Some("a.*.c".to_string()));
+        assert_eq!(filtro_a_patron("a/#"), Some("a.>".to_string()));
+        assert_eq!(filtro_a_patron("a/#/c"), None);
+        assert_eq!(filtro_a_patron("a/b+"), None);
+    }
+
+    #[test]
+    fn probar_conexion_suscripcion_y_retenidos() {
+        let (mut mock, stream) = MockHandler::new();
+        let retenidos = MensajesRetenidos::default();
+        let mut mqtt = StreamMqtt::servidor(stream, retenidos.clone(), 1024);
+
+        // CONNECT con id "cam", usuario "admin" y contraseña "1234"
+        mock.escribir_bytes(&paquete_cliente(
+            0x10,
+            &[
+                &texto("MQTT"),
+                &[4, 0xC2, 0, 60],
+                &texto("cam"),
+                &texto("admin"),
+                &texto("1234"),
+            ],
+        ));
+        let comandos = leer_comandos(&mut mqtt);
+        assert!(comandos.starts_with("CONNECT {"));
+        assert!(comandos.contains("\"user\":\"admin\""));
+        assert!(comandos.contains("\"name\":\"cam\""));
+
+        mqtt.write_all(b"INFO {}\r\n+OK\r\n").unwrap();
+        assert_eq!(recibidos(&mock), paquete::connack(0));
+
+        // PUBLISH QoS 1 retenido a incidentes/1/creado
+        mock.escribir_bytes(&paquete::publish(
+            "incidentes/1/creado",
+            b"hola",
+            1,
+            true,
+            Some(7),
+        ));
+        assert_eq!(
+            leer_comandos(&mut mqtt),
+            "PUB incidentes.1.creado 4\r\nhola\r\n"
+        );
+        mqtt.write_all(b"+OK\r\n").unwrap();
+        assert_eq!(recibidos(&mock), paquete::puback(7));
+
+        // SUBSCRIBE a incidentes/+/creado con QoS 0
+        mock.escribir_bytes(&paquete_cliente(
+            0x82,
+            &[&[0, 1], &texto("incidentes/+/creado"), &[0]],
+        ));
+        assert_eq!(leer_comandos(&mut mqtt), "SUB incidentes.*.creado 1\r\n");
+
+        mqtt.write_all(b"+OK\r\n").unwrap();
+        let mut esperado = paquete::suback(1, &[0]);
+        esperado.extend(paquete::publish(
+            "incidentes/1/creado",
+            b"hola",
+            0,
+            true,
+            None,
+        ));
+        assert_eq!(recibidos(&mock), esperado);
+
+        // Una publicación de un cliente de NATS
+        mqtt.write_all(b"MSG incidentes.2.creado 1 4\r\nchau\r\n")
+            .unwrap();
+        assert_eq!(
+            recibidos(&mock),
+            paquete::publish("incidentes/2/creado", b"chau", 0, false, None)
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use bytes::Bytes;
+
+/// Paquetes de MQTT 3.1.1 que envía un cliente (los que se soportan)
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaqueteMqtt {
+    Conectar {
+        /// Nivel del protocolo: 4 para MQTT 3.1.1, 3 para MQTT 3.1
+        nivel: u8,
+        id_cliente: String,
+        user: Option<String>,
+        pass: Option<String>,
+        /// Segundos que puede pasar el cliente sin enviar nada. Cero si no hay límite
+        keep_alive: u16,
+    },
+    Publicar {
+        topico: String,
+        payload: Bytes,
+        qos: u8,
+        retener: bool,
+        /// Solo en QoS 1 y 2
+        id_paquete: Option<u16>,
+    },
+    /// Confirmación de una publicación QoS 1 que le envió el servidor
+    PubAck(u16),
+    Suscribir {
+        id_paquete: u16,
+        /// Filtros y QoS pedidos
+        filtros: Vec<(String, u8)>,
+    },
+    Desuscribir {
+        id_paquete: u16,
+        filtros: Vec<String>,
+    },
+    PingReq,
+    Desconectar,
+}
+
+/// Tipos de paquete (los 4 bits altos del primer byte)
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+/// Códigos de retorno del CONNACK
+pub const CONEXION_ACEPTADA: u8 = 0;
+pub const PROTOCOLO_NO_SOPORTADO: u8 = 1;
+pub const NO_AUTORIZADO: u8 = 5;
+/// Código del SUBACK cuando se rechaza un filtro
+pub const SUSCRIPCION_RECHAZADA: u8 = 0x80;
+
+/// Lee un paquete del principio del buffer. Devuelve el paquete y los bytes que
+/// ocupaba, `Ok(None)` si todavía no llegó completo o `Err` si está mal formado,
+/// es de un tipo que no se soporta o supera `max_largo`
+pub fn decodificar(
+    buffer: &[u8],
+    max_largo: usize,
+) -> Result<Option<(PaqueteMqtt, usize)>, String> {
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+
+    // Largo restante: hasta 4 bytes, 7 bits cada uno
+    let mut largo: usize = 0;
+    let mut posicion: usize = 1;
+    loop {
+        let byte = match buffer.get(posicion) {
+            Some(byte) => *byte,
+            None => return Ok(None),
+        };
+        largo |= ((byte & 0x7F) as usize) << (7 * (posicion - 1));
+        posicion += 1;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if posicion > 4 {
+            return Err("Largo de paquete MQTT inválido".to_string());
+        }
+    }
+
+    if largo > max_largo {
+        return Err(format!("Paquete MQTT demasiado grande: {} bytes", largo));
+    }
+    if buffer.len() < posicion + largo {
+        return Ok(None);
+    }
+
+    let mut cuerpo = Lector::new(&buffer[posicion..posicion + largo]);
+    let banderas = buffer[0] & 0x0F;
+
+    let paquete = match buffer[0] >> 4 {
+        CONNECT => {
+            let protocolo = cuerpo.texto()?;
+            let nivel = cuerpo.byte()?;
+            let opciones = cuerpo.byte()?;
+            let keep_alive = cuerpo.u16()?;
+            let id_cliente = cuerpo.texto()?;
+
+            if protocolo != "MQTT" && protocolo != "MQIsdp" {
+                return Err(format!("Protocolo desconocido: {}", protocolo));
+            }
+
+            // Última voluntad (no se usa, pero hay que saltearla)
+            if opciones & 0x04 != 0 {
+                cuerpo.texto()?;
+                cuerpo.binario()?;
+            }
+            let user = if opciones & 0x80 != 0 {
+                Some(cuerpo.texto()?)
+            } else {
+                None
+            };
+            let pass = if opciones & 0x40 != 0 {
+                Some(String::from_utf8_lossy(cuerpo.binario()?).to_string())
+            } else {
+                None
+            };
+
+            PaqueteMqtt::Conectar {
+                nivel,
+                id_cliente,
+                user,
+                pass,
+                keep_alive,
+            }
+        }
+        PUBLISH => {
+            let qos = (banderas >> 1) & 0x03;
+            let topico = cuerpo.texto()?;
+            let id_paquete = if qos > 0 { Some(cuerpo.u16()?) } else { None };
+
+            PaqueteMqtt::Publicar {
+                topico,
+                payload: Bytes::copy_from_slice(cuerpo.resto()),
+                qos,
+                retener: banderas & 0x01 != 0,
+                id_paquete,
+            }
+        }
+        PUBACK => PaqueteMqtt::PubAck(cuerpo.u16()?),
+        SUBSCRIBE => {
+            let id_paquete = cuerpo.u16()?;
+            let mut filtros = Vec::new();
+            while !cuerpo.resto().is_empty() {
+                let filtro = cuerpo.texto()?;
+                filtros.push((filtro, cuerpo.byte()? & 0x03));
+            }
+
+            PaqueteMqtt::Suscribir {
+                id_paquete,
+                filtros,
+            }
+        }
+        UNSUBSCRIBE => {
+            let id_paquete = cuerpo.u16()?;
+            let mut filtros = Vec::new();
+            while !cuerpo.resto().is_empty() {
+                filtros.push(cuerpo.texto()?);
+            }
+
+            PaqueteMqtt::Desuscribir {
+                id_paquete,
+                filtros,
+            }
+        }
+        PINGREQ => PaqueteMqtt::PingReq,
+        DISCONNECT => PaqueteMqtt::Desconectar,
+        tipo => return Err(format!("Paquete MQTT no soportado: {}", tipo)),
+    };
+
+    Ok(Some((paquete, posicion + largo)))
+}
+
+pub fn connack(codigo: u8) -> Vec<u8> {
+    vec![CONNACK << 4, 2, 0, codigo]
+}
+
+pub fn publish(
+    topico: &str,
+    payload: &[u8],
+    qos: u8,
+    retener: bool,
+    id_paquete: Option<u16>,
+) -> Vec<u8> {
+    let mut cuerpo = Vec::with_capacity(topico.len() + payload.len() + 4);
+    escribir_texto(&mut cuerpo, topico);
+    if let Some(id_paquete) = id_paquete {
+        cuerpo.extend_from_slice(&id_paquete.to_be_bytes());
+    }
+    cuerpo.extend_from_slice(payload);
+
+    paquete(PUBLISH << 4 | qos << 1 | retener as u8, &cuerpo)
+}
+
+pub fn puback(id_paquete: u16) -> Vec<u8> {
+    paquete(PUBACK << 4, &id_paquete.to_be_bytes())
+}
+
+pub fn suback(id_paquete: u16, codigos: &[u8]) -> Vec<u8> {
+    let mut cuerpo = id_paquete.to_be_bytes().to_vec();
+    cuerpo.extend_from_slice(codigos);
+    paquete(SUBACK << 4, &cuerpo)
+}
+
+pub fn unsuback(id_paquete: u16) -> Vec<u8> {
+    paquete(UNSUBACK << 4, &id_paquete.to_be_bytes())
+}
+
+pub fn pingresp() -> Vec<u8> {
+    vec![PINGRESP << 4, 0]
+}
+
+fn paquete(primer_byte: u8, cuerpo: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![primer_byte];
+
+    let mut largo = cuerpo.len();
+    loop {
+        let mut byte = (largo % 128) as u8;
+        largo /= 128;
+        if largo > 0 {
+            byte |= 0x80;
+        }
+        bytes.push(byte);
+        if largo == 0 {
+            break;
+        }
+    }
+
+    bytes.extend_from_slice(cuerpo);
+    bytes
+}
+
+fn escribir_texto(bytes: &mut Vec<u8>, texto: &str) {
+    bytes.extend_from_slice(&(texto.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(texto.as_bytes());
+}
+
+/// Lee los campos del cuerpo de un paquete en orden
+struct Lector<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Lector<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn tomar(&mut self, cantidad: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < cantidad {
+            return Err("Paquete MQTT incompleto".to_string());
+        }
+        let (tomados, resto) = self.bytes.split_at(cantidad);
+        self.bytes = resto;
+        Ok(tomados)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.tomar(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.tomar(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn binario(&mut self) -> Result<&'a [u8], String> {
+        let largo = self.u16()? as usize;
+        self.tomar(largo)
+    }
+
+    fn texto(&mut self) -> Result<String, String> {
+        let bytes = self.binario()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "Texto MQTT inválido".to_string())
+    }
+
+    fn resto(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
@@ -0,0 +1,40 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+
+use crate::suscripciones::topico::Topico;
+
+/// Último mensaje retenido de cada tópico (con el formato de NATS). Se
+/// comparten entre todas las conexiones MQTT del servidor
+#[derive(Debug, Clone, Default)]
+pub struct MensajesRetenidos {
+    mensajes: Arc<Mutex<HashMap<String, Bytes>>>,
+}
+
+impl MensajesRetenidos {
+    /// Reemplaza el mensaje retenido del tópico. Un payload vacío lo elimina
+    pub fn guardar(&self, topico: String, payload: Bytes) {
+        if let Ok(mut mensajes) = self.mensajes.lock() {
+            if payload.is_empty() {
+                mensajes.remove(&topico);
+            } else {
+                mensajes.insert(topico, payload);
+            }
+        }
+    }
+
+    /// Los mensajes retenidos de los tópicos que coinciden con el patrón
+    pub fn coincidentes(&self, patron: &Topico) -> Vec<(String, Bytes)> {
+        match self.mensajes.lock() {
+            Ok(mensajes) => mensajes
+                .iter()
+                .filter(|(topico, _)| patron.test(topico))
+                .map(|(topico, payload)| (topico.clone(), payload.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
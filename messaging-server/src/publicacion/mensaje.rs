@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use bytes::Bytes;
+
 use lib::parseador::mensaje::formatear_payload_debug;
 
 /// Representa un mensaje que se va a publicar en un tópico
@@ -7,17 +9,19 @@ use lib::parseador::mensaje::formatear_payload_debug;
 pub struct PublicacionMensaje {
     pub sid: String,
     pub topico: String,
-    pub payload: Vec<u8>,          // El mensaje que se va a enviar
-    pub header: Option<Vec<u8>>,   // EL header del mensaje que se va a enviar
+    pub payload: Bytes,            // El mensaje que se va a enviar
+    pub header: Option<Bytes>,     // EL header del mensaje que se va a enviar
     pub replay_to: Option<String>, // Campo que tiene nats
+    /// Si la publicó el usuario de la cuenta de sistema
+    pub de_sistema: bool,
 }
 
 impl PublicacionMensaje {
     pub fn new(
         sid: String,
         topico: String,
-        payload: Vec<u8>,
-        header: Option<Vec<u8>>,
+        payload: Bytes,
+        header: Option<Bytes>,
         replay_to: Option<String>,
     ) -> Self {
         Self {
@@ -26,14 +30,32 @@ impl PublicacionMensaje {
             payload,
             replay_to,
             header,
+            de_sistema: false,
         }
     }
 
+    /// Marca si la publicó el usuario de la cuenta de sistema
+    pub fn con_origen_sistema(mut self, de_sistema: bool) -> Self {
+        self.de_sistema = de_sistema;
+        self
+    }
+
+    /// Bytes de headers y payload (lo que se cuenta en las estadísticas)
+    pub fn bytes_contenido(&self) -> usize {
+        self.header.as_ref().map_or(0, |h| h.len()) + self.payload.len()
+    }
+
     pub fn serializar_msg(&self) -> Vec<u8> {
         // MSG <subject> <sid> [reply-to] <#bytes>␍␊[payload]␍␊
         // HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>␍␊[headers]␍␊␍␊[payload]␍␊
+        // (los headers ya incluyen el ␍␊␍␊ final)
 
-        let mut bytes = Vec::new();
+        let mut bytes = Vec::with_capacity(
+            self.topico.len()
+                + self.header.as_ref().map_or(0, |h| h.len())
+                + self.payload.len()
+                + 64,
+        );
 
         if self.header.is_some() {
             bytes.extend_from_slice(b"HMSG ");
@@ -52,12 +74,12 @@ impl PublicacionMensaje {
         }
 
         if let Some(header) = &self.header {
+            // El total incluye a los headers, que ya terminan con su propio salto de linea
             bytes.extend_from_slice(header.len().to_string().as_bytes());
             bytes.extend_from_slice(b" ");
-            bytes.extend_from_slice(self.payload.len().to_string().as_bytes());
+            bytes.extend_from_slice((header.len() + self.payload.len()).to_string().as_bytes());
             bytes.extend_from_slice(b"\r\n");
             bytes.extend_from_slice(header);
-            bytes.extend_from_slice(b"\r\n");
         } else {
             bytes.extend_from_slice(self.payload.len().to_string().as_bytes());
             bytes.extend_from_slice(b"\r\n");
@@ -1,5 +1,11 @@
 use std::fmt::Debug;
 
+use bytes::Bytes;
+use lib::{
+    headers::{Headers, HEADER_DESTINO_TRAZA},
+    serializables::Serializable,
+};
+
 use self::mensaje::PublicacionMensaje;
 
 pub mod mensaje;
@@ -8,16 +14,18 @@ pub mod mensaje;
 #[derive(Clone)]
 pub struct Publicacion {
     pub topico: String,            // A donde se envia el mensaje
-    pub payload: Vec<u8>,          // El mensaje que se va a enviar
-    pub header: Option<Vec<u8>>,   // EL header del mensaje que se va a enviar
+    pub payload: Bytes,            // El mensaje que se va a enviar
+    pub header: Option<Bytes>,     // EL header del mensaje que se va a enviar
     pub replay_to: Option<String>, // Campo que tiene nats
+    /// Si la publicó el usuario de la cuenta de sistema
+    pub de_sistema: bool,
 }
 
 impl Publicacion {
     pub fn new(
         topico: String,
-        payload: Vec<u8>,
-        header: Option<Vec<u8>>,
+        payload: Bytes,
+        header: Option<Bytes>,
         replay_to: Option<String>,
     ) -> Self {
         Self {
@@ -25,9 +33,16 @@ impl Publicacion {
             payload,
             replay_to,
             header,
+            de_sistema: false,
         }
     }
 
+    /// Marca si la publicó el usuario de la cuenta de sistema
+    pub fn con_origen_sistema(mut self, de_sistema: bool) -> Self {
+        self.de_sistema = de_sistema;
+        self
+    }
+
     pub fn mensaje(&self, sid: String) -> PublicacionMensaje {
         PublicacionMensaje::new(
             sid,
@@ -36,6 +51,19 @@ impl Publicacion {
             self.header.clone(),
             self.replay_to.clone(),
         )
+        .con_origen_sistema(self.de_sistema)
+    }
+
+    /// El tópico del header `Nats-Trace-Dest`, si se pidió trazar la publicación
+    pub fn destino_traza(&self) -> Option<String> {
+        let header: &[u8] = self.header.as_ref()?;
+
+        Headers::deserializar(header)
+            .ok()?
+            .obtener(HEADER_DESTINO_TRAZA)
+            .map(str::trim)
+            .filter(|destino| !destino.is_empty())
+            .map(str::to_string)
     }
 }
 
@@ -53,6 +81,7 @@ impl Debug for Publicacion {
             .field("payload", &contenido_max_100_chars)
             .field("header", &self.header)
             .field("replay_to", &self.replay_to)
+            .field("de_sistema", &self.de_sistema)
             .finish()
     }
 }
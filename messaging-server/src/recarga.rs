@@ -0,0 +1,92 @@
+use std::{fmt::Debug, fs, sync::Arc, time::SystemTime};
+
+use crate::{cuenta::Cuenta, limites::LimitesServidor, mapeos::Mapeos};
+
+/// Lo que cambia al recargar la configuración del servidor. Se les envía a
+/// todos los hilos para que lo apliquen a las conexiones que ya existen
+#[derive(Clone)]
+pub struct Recarga {
+    pub cuentas: Option<Arc<Vec<Cuenta>>>,
+    pub limites: LimitesServidor,
+    pub mapeos: Mapeos,
+}
+
+impl Debug for Recarga {
+    // No se muestran las contraseñas de las cuentas
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recarga")
+            .field("cuentas", &self.cuentas.as_ref().map(|c| c.len()))
+            .field("limites", &self.limites)
+            .field("mapeos", &self.mapeos)
+            .finish()
+    }
+}
+
+/// Detecta cambios en los archivos de configuración comparando la fecha de
+/// última modificación de cada uno
+#[derive(Debug)]
+pub struct VigilanteArchivos {
+    archivos: Vec<(String, Option<SystemTime>)>,
+}
+
+impl VigilanteArchivos {
+    pub fn new(rutas: Vec<String>) -> Self {
+        let archivos = rutas
+            .into_iter()
+            .map(|ruta| {
+                let modificacion = modificacion(&ruta);
+                (ruta, modificacion)
+            })
+            .collect();
+
+        Self { archivos }
+    }
+
+    /// Si alguno de los archivos cambió desde la última vez que se preguntó
+    pub fn cambiaron(&mut self) -> bool {
+        let mut cambiaron = false;
+
+        for (ruta, ultima) in self.archivos.iter_mut() {
+            let actual = modificacion(ruta);
+            if actual != *ultima {
+                *ultima = actual;
+                cambiaron = true;
+            }
+        }
+
+        cambiaron
+    }
+}
+
+fn modificacion(ruta: &str) -> Option<SystemTime> {
+    fs::metadata(ruta).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use super::VigilanteArchivos;
+
+    #[test]
+    fn probar_cambios_en_archivos() {
+        let ruta = std::env::temp_dir().join(format!("vigilante-{}.txt", std::process::id()));
+        fs::write(&ruta, "puerto=4222").unwrap();
+        let mut vigilante = VigilanteArchivos::new(vec![ruta.to_str().unwrap().to_string()]);
+
+        assert!(!vigilante.cambiaron());
+
+        let archivo = File::options().write(true).open(&ruta).unwrap();
+        archivo
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+
+        assert!(vigilante.cambiaron());
+        assert!(!vigilante.cambiaron());
+
+        fs::remove_file(&ruta).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// Archivo de registros que se rota cuando supera un tamaño o una antigüedad.
+/// Al rotar, `archivo` pasa a ser `archivo.1`, `archivo.1` pasa a ser
+/// `archivo.2` y así hasta `archivos_maximos`; el más viejo se elimina
+pub struct ArchivoRotativo {
+    ruta: String,
+    archivo: File,
+    /// Bytes escritos en el archivo actual
+    tamanio: u64,
+    /// Cuándo se empezó a escribir el archivo actual
+    apertura: Instant,
+    tamanio_maximo: u64,
+    antiguedad_maxima: Option<Duration>,
+    archivos_maximos: usize,
+}
+
+impl ArchivoRotativo {
+    pub fn abrir(
+        ruta: &str,
+        tamanio_maximo: u64,
+        antiguedad_maxima: Option<Duration>,
+        archivos_maximos: usize,
+    ) -> io::Result<Self> {
+        let archivo = abrir_para_agregar(ruta)?;
+        let tamanio = archivo.metadata()?.len();
+
+        Ok(Self {
+            ruta: ruta.to_string(),
+            archivo,
+            tamanio,
+            apertura: Instant::now(),
+            tamanio_maximo,
+            antiguedad_maxima,
+            archivos_maximos,
+        })
+    }
+
+    pub fn escribir_linea(&mut self, linea: &str) -> io::Result<()> {
+        if self.hay_que_rotar() {
+            self.rotar()?;
+        }
+
+        self.archivo.write_all(linea.as_bytes())?;
+        self.archivo.write_all(b"\n")?;
+        self.tamanio += linea.len() as u64 + 1;
+
+        Ok(())
+    }
+
+    fn hay_que_rotar(&self) -> bool {
+        if self.tamanio == 0 {
+            return false;
+        }
+
+        self.tamanio >= self.tamanio_maximo
+            || self
+                .antiguedad_maxima
+                .is_some_and(|antiguedad| self.apertura.elapsed() >= antiguedad)
+    }
+
+    fn rotar(&mut self) -> io::Result<()> {
+        self.archivo.flush()?;
+
+        if self.archivos_maximos == 0 {
+            fs::remove_file(&self.ruta)?;
+        } else {
+            let _ = fs::remove_file(self.ruta_rotada(self.archivos_maximos));
+            for i in (1..self.archivos_maximos).rev() {
+                let _ = fs::rename(self.ruta_rotada(i), self.ruta_rotada(i + 1));
+            }
+            fs::rename(&self.ruta, self.ruta_rotada(1))?;
+        }
+
+        self.archivo = abrir_para_agregar(&self.ruta)?;
+        self.tamanio = 0;
+        self.apertura = Instant::now();
+
+        Ok(())
+    }
+
+    fn ruta_rotada(&self, numero: usize) -> String {
+        format!("{}.{}", self.ruta, numero)
+    }
+}
+
+fn abrir_para_agregar(ruta: &str) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(ruta)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::ArchivoRotativo;
+
+    #[test]
+    fn probar_rotacion_por_tamanio() {
+        let directorio = std::env::temp_dir().join(format!("registros-{}", std::process::id()));
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("servidor.log");
+        let ruta = ruta.to_str().unwrap();
+
+        let mut archivo = ArchivoRotativo::abrir(ruta, 10, None, 2).unwrap();
+        for linea in ["uno 12345", "dos 12345", "tres 1234", "cuatro 12"] {
+            archivo.escribir_linea(linea).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(ruta).unwrap(), "cuatro 12\n");
+        assert_eq!(
+            fs::read_to_string(format!("{}.1", ruta)).unwrap(),
+            "tres 1234\n"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}.2", ruta)).unwrap(),
+            "dos 12345\n"
+        );
+        assert!(fs::metadata(format!("{}.3", ruta)).is_err());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
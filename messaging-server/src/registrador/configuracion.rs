@@ -0,0 +1,137 @@
+use std::{collections::HashMap, time::Duration};
+
+use lib::configuracion::Configuracion;
+
+use super::registro::NivelRegistro;
+
+/// Cómo se escribe cada registro
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatoRegistro {
+    /// `{Momento} {Nivel} [{objetivo}] ... {Mensaje}`
+    Texto,
+    /// Un objeto JSON por línea
+    Json,
+}
+
+/// Qué se registra y a dónde se escribe
+#[derive(Debug, Clone)]
+pub struct ConfiguracionRegistro {
+    /// Nivel mínimo de los registros que se escriben
+    pub nivel: NivelRegistro,
+    /// Si no se escribe ningún registro (`log_nivel=ninguno`)
+    pub desactivado: bool,
+    /// Nivel mínimo de cada objetivo, si es distinto del general
+    pub objetivos: HashMap<String, NivelRegistro>,
+    pub formato: FormatoRegistro,
+    /// Archivo en el que se escriben los registros. Si no se indica, se
+    /// escriben por consola
+    pub archivo: Option<String>,
+    /// Tamaño a partir del cual se rota el archivo
+    pub tamanio_maximo: u64,
+    /// Tiempo a partir del cual se rota el archivo
+    pub antiguedad_maxima: Option<Duration>,
+    /// Cantidad de archivos rotados que se conservan (`archivo.1`, `archivo.2`, ...)
+    pub archivos_maximos: usize,
+}
+
+impl Default for ConfiguracionRegistro {
+    fn default() -> Self {
+        Self {
+            nivel: NivelRegistro::Informacion,
+            desactivado: false,
+            objetivos: HashMap::new(),
+            formato: FormatoRegistro::Texto,
+            archivo: None,
+            tamanio_maximo: 10 * 1024 * 1024,
+            antiguedad_maxima: None,
+            archivos_maximos: 5,
+        }
+    }
+}
+
+impl ConfiguracionRegistro {
+    /// Lee la configuración de los registros:
+    ///
+    /// - `log_nivel`: `trace`, `debug`, `info`, `advertencia`, `error` o `ninguno`
+    /// - `log_objetivos`: niveles por objetivo (`conexion:trace,hilo:debug`)
+    /// - `log_formato`: `texto` o `json`
+    /// - `log_archivo`: ruta del archivo
+    /// - `log_tamanio_maximo`: bytes a partir de los cuales se rota el archivo
+    /// - `log_antiguedad_maxima`: segundos a partir de los cuales se rota el archivo
+    /// - `log_archivos_maximos`: archivos rotados que se conservan
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Self {
+        let por_defecto = Self::default();
+
+        let objetivos = configuracion
+            .obtener::<String>("log_objetivos")
+            .map(|objetivos| parsear_objetivos(&objetivos))
+            .unwrap_or_default();
+
+        let formato = match configuracion.obtener::<String>("log_formato").as_deref() {
+            Some("json") => FormatoRegistro::Json,
+            _ => FormatoRegistro::Texto,
+        };
+
+        Self {
+            nivel: configuracion
+                .obtener::<NivelRegistro>("log_nivel")
+                .unwrap_or(por_defecto.nivel),
+            desactivado: configuracion.obtener::<String>("log_nivel").as_deref() == Some("ninguno"),
+            objetivos,
+            formato,
+            archivo: configuracion.obtener::<String>("log_archivo"),
+            tamanio_maximo: configuracion
+                .obtener::<u64>("log_tamanio_maximo")
+                .unwrap_or(por_defecto.tamanio_maximo),
+            // Una antigüedad que no entra en un `Duration` es como no tener límite
+            antiguedad_maxima: configuracion
+                .obtener::<f64>("log_antiguedad_maxima")
+                .filter(|segundos| *segundos > 0.)
+                .and_then(|segundos| Duration::try_from_secs_f64(segundos).ok()),
+            archivos_maximos: configuracion
+                .obtener::<usize>("log_archivos_maximos")
+                .unwrap_or(por_defecto.archivos_maximos),
+        }
+    }
+
+    /// Si se escriben los registros de ese nivel para el objetivo
+    pub fn habilitado(&self, nivel: NivelRegistro, objetivo: &str) -> bool {
+        let minimo = self.objetivos.get(objetivo).unwrap_or(&self.nivel);
+        !self.desactivado && nivel >= *minimo
+    }
+}
+
+/// `conexion:trace,hilo:debug` (se ignoran los niveles que no se reconocen)
+fn parsear_objetivos(texto: &str) -> HashMap<String, NivelRegistro> {
+    texto
+        .split(',')
+        .filter_map(|par| {
+            let (objetivo, nivel) = par.split_once(':')?;
+            Some((objetivo.trim().to_string(), nivel.parse().ok()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::configuracion::Configuracion;
+
+    use crate::registrador::registro::NivelRegistro;
+
+    use super::{ConfiguracionRegistro, FormatoRegistro};
+
+    #[test]
+    fn probar_niveles_por_objetivo() {
+        let configuracion = Configuracion::desde_parametros(&[
+            "log_nivel=advertencia",
+            "log_objetivos=conexion:trace, hilo:nada",
+            "log_formato=json",
+        ]);
+        let registro = ConfiguracionRegistro::desde_configuracion(&configuracion);
+
+        assert_eq!(registro.formato, FormatoRegistro::Json);
+        assert!(registro.habilitado(NivelRegistro::Trace, "conexion"));
+        assert!(!registro.habilitado(NivelRegistro::Informacion, "hilo"));
+        assert!(registro.habilitado(NivelRegistro::Error, "servidor"));
+    }
+}
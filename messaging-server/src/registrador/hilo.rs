@@ -1,17 +1,58 @@
-use std::{sync::mpsc::Receiver, thread};
+use std::{
+    io::{self, Write},
+    sync::{mpsc::Receiver, Arc},
+    thread,
+};
 
-use super::registro::{NivelRegistro, Registro};
+use super::{
+    archivo::ArchivoRotativo,
+    configuracion::{ConfiguracionRegistro, FormatoRegistro},
+    registro::{NivelRegistro, Registro},
+};
 
-pub fn hilo_registrador(rx: Receiver<Registro>) {
-    // Mientras se reciba un registro, se verifica el "nivel" del registro, y se imprime
+/// A dónde se escriben los registros
+enum Salida {
+    Consola,
+    Archivo(ArchivoRotativo),
+}
+
+pub fn hilo_registrador(rx: Receiver<Registro>, configuracion: Arc<ConfiguracionRegistro>) {
+    // Mientras se reciba un registro, se formatea y se escribe en la salida configurada
     thread::spawn(move || {
+        let mut salida = match &configuracion.archivo {
+            Some(ruta) => match ArchivoRotativo::abrir(
+                ruta,
+                configuracion.tamanio_maximo,
+                configuracion.antiguedad_maxima,
+                configuracion.archivos_maximos,
+            ) {
+                Ok(archivo) => Salida::Archivo(archivo),
+                Err(e) => {
+                    eprintln!("No se pudo abrir el archivo de registros {}: {}", ruta, e);
+                    Salida::Consola
+                }
+            },
+            None => Salida::Consola,
+        };
+
         while let Ok(registro) = rx.recv() {
-            match registro.nivel {
-                NivelRegistro::Advertencia => {
-                    eprintln!("{}", registro)
+            let linea = match configuracion.formato {
+                FormatoRegistro::Texto => registro.to_string(),
+                FormatoRegistro::Json => registro.json(),
+            };
+
+            match &mut salida {
+                Salida::Consola if registro.nivel >= NivelRegistro::Advertencia => {
+                    let _ = writeln!(io::stderr(), "{}", linea);
+                }
+                Salida::Consola => {
+                    let _ = writeln!(io::stdout(), "{}", linea);
                 }
-                _ => {
-                    println!("{}", registro)
+                Salida::Archivo(archivo) => {
+                    if let Err(e) = archivo.escribir_linea(&linea) {
+                        eprintln!("Error al escribir el registro: {}", e);
+                        eprintln!("{}", linea);
+                    }
                 }
             }
         }
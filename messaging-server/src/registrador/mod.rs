@@ -1,15 +1,32 @@
-use std::sync::mpsc::{channel, Sender};
+use std::sync::{
+    mpsc::{channel, Sender},
+    Arc,
+};
+
+use lib::configuracion::Configuracion;
 
 use crate::hilo::id::IdHilo;
 
-use self::{hilo::hilo_registrador, registro::Registro};
+use self::{
+    configuracion::ConfiguracionRegistro,
+    hilo::hilo_registrador,
+    registro::{NivelRegistro, Registro},
+};
 
+mod archivo;
+pub mod configuracion;
 mod hilo;
 pub mod registro;
 
+/// Objetivo de los registros que no indican otro
+pub const OBJETIVO_POR_DEFECTO: &str = "servidor";
+
 pub struct Registrador {
     emisor: Sender<Registro>,
     hilo: Option<IdHilo>,
+    /// Subsistema al que pertenecen los registros de este registrador
+    objetivo: &'static str,
+    configuracion: Arc<ConfiguracionRegistro>,
 }
 
 impl Default for Registrador {
@@ -19,11 +36,28 @@ impl Default for Registrador {
 }
 
 impl Registrador {
+    /// Registrador que escribe por consola a partir del nivel Info
     pub fn new() -> Self {
+        Self::con_configuracion(ConfiguracionRegistro::default())
+    }
+
+    /// Lee el nivel, los objetivos, el formato y el archivo de `log_*`
+    /// (ver `ConfiguracionRegistro::desde_configuracion`)
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Self {
+        Self::con_configuracion(ConfiguracionRegistro::desde_configuracion(configuracion))
+    }
+
+    pub fn con_configuracion(configuracion: ConfiguracionRegistro) -> Self {
+        let configuracion = Arc::new(configuracion);
         let (emisor, receptor) = channel();
-        hilo_registrador(receptor);
+        hilo_registrador(receptor, configuracion.clone());
 
-        Registrador { emisor, hilo: None }
+        Registrador {
+            emisor,
+            hilo: None,
+            objetivo: OBJETIVO_POR_DEFECTO,
+            configuracion,
+        }
     }
 
     /// Establece el valor por defecto del hilo
@@ -31,28 +65,64 @@ impl Registrador {
         self.hilo = Some(hilo);
     }
 
+    /// Una copia del registrador para los registros de otro subsistema
+    pub fn con_objetivo(&self, objetivo: &'static str) -> Self {
+        let mut registrador = self.clone();
+        registrador.objetivo = objetivo;
+        registrador
+    }
+
+    /// Si los registros de ese nivel se van a escribir. Sirve para no armar
+    /// mensajes costosos que después se descartan
+    pub fn habilitado(&self, nivel: NivelRegistro) -> bool {
+        self.configuracion.habilitado(nivel, self.objetivo)
+    }
+
     /// Registra un evento
     pub fn registrar(&self, registro: Registro) {
-        let _ = self.emisor.send(registro);
+        if self
+            .configuracion
+            .habilitado(registro.nivel, registro.objetivo)
+        {
+            let _ = self.emisor.send(registro);
+        }
+    }
+
+    fn registrar_nivel(&self, nivel: NivelRegistro, mensaje: &str, conexion: Option<u64>) {
+        if self.habilitado(nivel) {
+            self.registrar(Registro::new(
+                nivel,
+                self.objetivo,
+                mensaje.to_owned(),
+                self.hilo,
+                conexion,
+            ));
+        }
+    }
+
+    /// Registra un evento de traza (cada mensaje con su contenido) utilizando el hilo por defecto
+    pub fn trace(&self, mensaje: &str, conexion: Option<u64>) {
+        self.registrar_nivel(NivelRegistro::Trace, mensaje, conexion);
+    }
+
+    /// Registra un evento de depuración utilizando el hilo por defecto
+    pub fn debug(&self, mensaje: &str, conexion: Option<u64>) {
+        self.registrar_nivel(NivelRegistro::Debug, mensaje, conexion);
     }
 
     /// Registra un evento de información utilizando el hilo por defecto
     pub fn info(&self, mensaje: &str, conexion: Option<u64>) {
-        self.registrar(Registro::info(mensaje.to_owned(), self.hilo, conexion));
+        self.registrar_nivel(NivelRegistro::Informacion, mensaje, conexion);
     }
 
     /// Registra un evento de advertencia utilizando el hilo por defecto
     pub fn advertencia(&self, mensaje: &str, conexion: Option<u64>) {
-        self.registrar(Registro::advertencia(
-            mensaje.to_owned(),
-            self.hilo,
-            conexion,
-        ));
+        self.registrar_nivel(NivelRegistro::Advertencia, mensaje, conexion);
     }
 
     /// Registra un evento de error utilizando el hilo por defecto
     pub fn error(&self, mensaje: &str, conexion: Option<u64>) {
-        self.registrar(Registro::error(mensaje.to_owned(), self.hilo, conexion));
+        self.registrar_nivel(NivelRegistro::Error, mensaje, conexion);
     }
 }
 
@@ -65,6 +135,8 @@ impl Clone for Registrador {
             // El hilo no se clona. Esto es para evitar errores de consistencia
             // podría pasar que se clone entre hilos e imprima el hilo incorrecto
             hilo: None,
+            objetivo: self.objetivo,
+            configuracion: self.configuracion.clone(),
         }
     }
 }
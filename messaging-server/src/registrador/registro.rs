@@ -1,65 +1,84 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-#[derive(Debug, Clone)]
+use chrono::{DateTime, Local};
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Registro {
+    /// Fecha y hora en la que se generó el registro
+    #[serde(serialize_with = "serializar_momento")]
+    pub momento: DateTime<Local>,
     pub nivel: NivelRegistro,
+    /// Subsistema que generó el registro (`servidor`, `hilo`, `conexion`, ...)
+    pub objetivo: &'static str,
     pub hilo: Option<u64>,
     pub conexion: Option<u64>,
     pub mensaje: String,
 }
 
 impl Registro {
-    pub fn info(mensaje: String, hilo: Option<u64>, conexion: Option<u64>) -> Registro {
+    pub fn new(
+        nivel: NivelRegistro,
+        objetivo: &'static str,
+        mensaje: String,
+        hilo: Option<u64>,
+        conexion: Option<u64>,
+    ) -> Registro {
         Registro {
-            nivel: NivelRegistro::Informacion,
+            momento: Local::now(),
+            nivel,
+            objetivo,
             hilo,
             conexion,
             mensaje,
         }
     }
 
-    pub fn advertencia(mensaje: String, hilo: Option<u64>, conexion: Option<u64>) -> Registro {
-        Registro {
-            nivel: NivelRegistro::Advertencia,
-            hilo,
-            conexion,
-            mensaje,
-        }
+    /// Una línea de JSON con todos los campos del registro
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
     }
+}
 
-    pub fn error(mensaje: String, hilo: Option<u64>, conexion: Option<u64>) -> Registro {
-        Registro {
-            nivel: NivelRegistro::Error,
-            hilo,
-            conexion,
-            mensaje,
-        }
-    }
+fn serializar_momento<S: Serializer>(
+    momento: &DateTime<Local>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&momento.to_rfc3339())
 }
 
 impl Display for Registro {
-    /// Formato: `{Nivel} [hilo: {}] [cliente: {}] {Mensaje}`
+    /// Formato: `{Momento} {Nivel} [{objetivo}] [hilo: {}] [cliente: {}] {Mensaje}`
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} [{}]",
+            self.momento.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.nivel,
+            self.objetivo
+        )?;
+
         if let Some(hilo) = self.hilo {
-            if let Some(conexion) = self.conexion {
-                write!(
-                    f,
-                    "{} [hilo: {}] [cliente: {}] {}",
-                    self.nivel, hilo, conexion, self.mensaje
-                )
-            } else {
-                write!(f, "{} [hilo: {}] {}", self.nivel, hilo, self.mensaje)
-            }
-        } else if let Some(conexion) = self.conexion {
-            write!(f, "{} [cliente: {}] {}", self.nivel, conexion, self.mensaje)
-        } else {
-            write!(f, "{} {}", self.nivel, self.mensaje)
+            write!(f, " [hilo: {}]", hilo)?;
         }
+
+        if let Some(conexion) = self.conexion {
+            write!(f, " [cliente: {}]", conexion)?;
+        }
+
+        write!(f, " {}", self.mensaje)
     }
 }
 
-#[derive(Debug, Clone)]
+/// Niveles de registro, de menor a mayor importancia
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NivelRegistro {
+    /// Cada mensaje que entra o sale del servidor, con su contenido
+    Trace,
+    /// Detalles para diagnosticar el comportamiento del servidor
+    Debug,
+    #[serde(rename = "info")]
     Informacion,
     Advertencia,
     Error,
@@ -68,9 +87,26 @@ pub enum NivelRegistro {
 impl Display for NivelRegistro {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            NivelRegistro::Trace => write!(f, "Trace"),
+            NivelRegistro::Debug => write!(f, "Debug"),
             NivelRegistro::Informacion => write!(f, "Info"),
             NivelRegistro::Advertencia => write!(f, "Advertencia"),
             NivelRegistro::Error => write!(f, "Error"),
         }
     }
 }
+
+impl FromStr for NivelRegistro {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" => Ok(NivelRegistro::Trace),
+            "debug" => Ok(NivelRegistro::Debug),
+            "info" | "informacion" => Ok(NivelRegistro::Informacion),
+            "advertencia" | "warn" => Ok(NivelRegistro::Advertencia),
+            "error" => Ok(NivelRegistro::Error),
+            _ => Err(format!("Nivel de registro desconocido: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use lib::configuracion::{
+    esquema::{ErrorClave, ErrorConfiguracion},
+    Configuracion,
+};
+
+use crate::{
+    cuenta::Cuenta, deduplicacion::Deduplicador, esquema::esquema, limite_tasa::LimitesCuentas,
+    mapeos::Mapeos,
+};
+
+use super::{Escucha, Protocolo, Servidor};
+
+impl Servidor {
+    /// Configura un servidor para correr dentro de otro proceso (tests de
+    /// integración, el modo local del monitoreo):
+    ///
+    /// ```no_run
+    /// use messaging_server::servidor::Servidor;
+    ///
+    /// let servidor = Servidor::embebido().puerto(0).iniciar().unwrap();
+    /// println!("Escuchando en {}", servidor.direccion());
+    /// servidor.detener().unwrap();
+    /// ```
+    pub fn embebido() -> Embebido {
+        Embebido::default()
+    }
+}
+
+/// Configuración de un servidor embebido. Por defecto escucha en
+/// `127.0.0.1` en un puerto libre, no escribe registros y no recarga archivos
+pub struct Embebido {
+    configuracion: Configuracion,
+    cuentas: Option<Vec<Cuenta>>,
+}
+
+impl Default for Embebido {
+    fn default() -> Self {
+        let mut configuracion: Configuracion = Configuracion::new();
+        configuracion.setear("direccion", "127.0.0.1");
+        configuracion.setear("puerto", 0);
+        configuracion.setear("hilos", 2);
+        configuracion.setear("log_nivel", "ninguno");
+        configuracion.setear("intervalo_recarga", 0);
+
+        Self {
+            configuracion,
+            cuentas: None,
+        }
+    }
+}
+
+impl Embebido {
+    /// Puerto de los clientes. Con 0 el sistema elige uno libre
+    pub fn puerto(mut self, puerto: u16) -> Self {
+        self.configuracion.setear("puerto", puerto);
+        self
+    }
+
+    pub fn direccion(mut self, direccion: &str) -> Self {
+        self.configuracion.setear("direccion", direccion);
+        self
+    }
+
+    pub fn hilos(mut self, hilos: usize) -> Self {
+        self.configuracion.setear("hilos", hilos);
+        self
+    }
+
+    /// Cuentas con las que se tienen que autenticar los clientes. Sin
+    /// cuentas se acepta a cualquiera
+    pub fn cuentas(mut self, cuentas: Vec<Cuenta>) -> Self {
+        self.cuentas = Some(cuentas);
+        self
+    }
+
+    /// Cualquier otra clave de la configuración del servidor (límites,
+    /// mapeos, `log_nivel` para ver los registros, ...)
+    pub fn configuracion<T: ToString>(mut self, clave: &str, valor: T) -> Self {
+        self.configuracion.setear(clave, valor);
+        self
+    }
+
+    /// Escucha en la dirección configurada y atiende a los clientes en otro
+    /// thread. Si la configuración es inválida o no se puede escuchar
+    /// devuelve el error
+    pub fn iniciar(self) -> io::Result<ServidorEmbebido> {
+        // El servidor descartaría en silencio los valores inválidos (no
+        // escribe registros), así que se informan todos juntos antes de iniciar
+        let invalida = |error: String| io::Error::new(io::ErrorKind::InvalidInput, error);
+        let mut errores: Vec<ErrorClave> = esquema().validar(&self.configuracion);
+        errores.extend(Mapeos::validar(&self.configuracion));
+        if !errores.is_empty() {
+            return Err(invalida(ErrorConfiguracion::Claves(errores).to_string()));
+        }
+        Deduplicador::leer_reglas(&self.configuracion).map_err(|e| invalida(e.to_string()))?;
+        LimitesCuentas::leer_tasas(&self.configuracion).map_err(|e| invalida(e.to_string()))?;
+
+        let mut servidor: Servidor = Servidor::desde_configuracion(self.configuracion);
+        if let Some(cuentas) = self.cuentas {
+            servidor.establecer_cuentas(cuentas);
+        }
+
+        let escuchas: Vec<(Escucha, Protocolo)> = servidor.escuchar()?;
+        let direccion: SocketAddr = escuchas
+            .iter()
+            .find_map(|(escucha, _)| escucha.direccion_local())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "No hay una dirección TCP")
+            })?;
+
+        let detenido = servidor.detenido.clone();
+        let handle: JoinHandle<()> = thread::spawn(move || servidor.atender(escuchas));
+
+        Ok(ServidorEmbebido {
+            direccion,
+            detenido,
+            handle: Some(handle),
+        })
+    }
+}
+
+/// Un servidor embebido que está atendiendo clientes. Se detiene con
+/// `detener` o al descartarlo
+pub struct ServidorEmbebido {
+    direccion: SocketAddr,
+    detenido: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ServidorEmbebido {
+    /// La dirección en la que escucha, con el puerto que eligió el sistema
+    pub fn direccion(&self) -> SocketAddr {
+        self.direccion
+    }
+
+    /// Deja de aceptar conexiones, cierra las que hay y espera a que terminen
+    /// todos los hilos del servidor
+    pub fn detener(mut self) -> io::Result<()> {
+        self.esperar_fin()
+    }
+
+    fn esperar_fin(&mut self) -> io::Result<()> {
+        self.detenido.store(true, Ordering::Relaxed);
+
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| io::Error::other("El servidor terminó con un error")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ServidorEmbebido {
+    fn drop(&mut self) {
+        let _ = self.esperar_fin();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, BufRead, BufReader, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    use crate::cuenta::Cuenta;
+
+    use super::Servidor;
+
+    fn leer_linea(lector: &mut BufReader<TcpStream>) -> String {
+        let mut linea = String::new();
+        lector.read_line(&mut linea).unwrap();
+        linea
+    }
+
+    #[test]
+    fn probar_servidor_embebido() {
+        let cuenta = Cuenta {
+            id: 1,
+            user: "admin".to_string(),
+            pass: "1234".to_string(),
+            sistema: false,
+        };
+        let servidor = Servidor::embebido()
+            .puerto(0)
+            .cuentas(vec![cuenta])
+            .iniciar()
+            .unwrap();
+        let direccion = servidor.direccion();
+        assert_ne!(direccion.port(), 0);
+
+        let mut stream = TcpStream::connect(direccion).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut lector = BufReader::new(stream.try_clone().unwrap());
+        assert!(leer_linea(&mut lector).starts_with("INFO"));
+        // El INFO termina con una línea vacía
+        assert_eq!(leer_linea(&mut lector), "\r\n");
+
+        stream
+            .write_all(b"CONNECT {\"user\":\"admin\",\"pass\":\"1234\"}\r\nSUB a 1\r\n")
+            .unwrap();
+        assert!(leer_linea(&mut lector).starts_with("+OK"));
+        assert!(leer_linea(&mut lector).starts_with("+OK"));
+        stream.write_all(b"PUB a 4\r\nhola\r\n").unwrap();
+        assert!(leer_linea(&mut lector).starts_with("+OK"));
+        assert_eq!(leer_linea(&mut lector), "MSG a 1 4\r\n");
+
+        // Al detenerlo se cierran las conexiones y se deja de escuchar
+        servidor.detener().unwrap();
+        assert_eq!(leer_linea(&mut lector), "hola\r\n");
+        assert_eq!(leer_linea(&mut lector), "");
+        assert!(TcpStream::connect(direccion).is_err());
+    }
+
+    #[test]
+    fn probar_configuracion_invalida() {
+        let error = Servidor::embebido()
+            .hilos(0)
+            .configuracion("mapeo.a.>.b", "c")
+            .iniciar()
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("hilos"), "{}", error);
+        assert!(error.to_string().contains("mapeo.a.>.b"), "{}", error);
+
+        let error = Servidor::embebido()
+            .configuracion("limite_cuenta.dron.otra", 1)
+            .iniciar()
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
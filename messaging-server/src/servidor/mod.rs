@@ -0,0 +1,711 @@
+pub mod embebido;
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+
+use lib::{
+    configuracion::{
+        esquema::{ErrorClave, ErrorConfiguracion},
+        Configuracion,
+    },
+    stream::Stream,
+    websocket::StreamWebSocket,
+};
+
+use crate::{
+    autorizacion::AutorizacionExterna,
+    conexion::{id::IdConexion, respuesta::Respuesta},
+    cuenta::Cuenta,
+    deduplicacion::{Deduplicador, ReglasDeduplicacion},
+    escucha::Escucha,
+    esquema::esquema,
+    hilo::{
+        canal::{
+            canal_instrucciones, EmisorInstrucciones, Envio, PoliticaCola, ReceptorInstrucciones,
+            CAPACIDAD_COLA_POR_DEFECTO,
+        },
+        carga::{menos_cargado, CargaHilo, EstadisticasHilo},
+        id::IdHilo,
+        instruccion::Instruccion,
+    },
+    limite_tasa::{LimitesCuentas, TasasCuentas},
+    limites::{ContadorConexiones, LimitesServidor, LugarConexion},
+    mapeos::Mapeos,
+    mqtt::{retenidos::MensajesRetenidos, StreamMqtt},
+    publicacion::Publicacion,
+    recarga::{Recarga, VigilanteArchivos},
+    registrador::Registrador,
+    sistema::{
+        estadisticas::Estadisticas, evento::EventoEstadisticas, nuevo_id_servidor,
+        topico_estadisticas,
+    },
+};
+use lib::parseador::error_protocolo::ErrorProtocolo;
+
+use super::{conexion::Conexion, hilo::Hilo};
+
+type InfoHilo = (Sender<(IdConexion, Conexion)>, JoinHandle<()>);
+
+/// Lo que se permite en un paquete MQTT además del payload (tópico, id y
+/// largos de los campos)
+const MARGEN_PAQUETE_MQTT: usize = 64 * 1024;
+
+/// Protocolo que hablan los clientes de cada listener
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocolo {
+    Nats,
+    /// El protocolo de texto dentro de tramas de WebSocket
+    WebSocket,
+    /// MQTT 3.1.1, que se traduce al protocolo de texto
+    Mqtt,
+}
+
+pub struct Servidor {
+    pub configuracion: Configuracion,
+    hilos: Vec<InfoHilo>,
+    // Cada conexión que se genera hay que asignarla a un hilo. Se le da al
+    // que tenga menos carga. El índice en el Vec es el id del hilo
+    cargas: Vec<CargaHilo>,
+    // Cada id tiene que ser único por cada conexion. Se incrementa cada vez
+    // que se crea una nueva conexion
+    ultimo_id_conexion: IdConexion,
+    registrador: Registrador,
+    pub cuentas: Option<Arc<Vec<Cuenta>>>,
+    /// Límites de tamaños y cantidades que se aplican a los clientes
+    pub limites: LimitesServidor,
+    /// Conexiones abiertas en todos los hilos
+    conexiones_abiertas: ContadorConexiones,
+    /// Identificador del servidor en los tópicos de sistema (`$SYS.SERVER.<id>...`)
+    pub id: String,
+    /// Mensajes y bytes de todas las conexiones
+    estadisticas: Estadisticas,
+    /// Cuándo se creó el servidor
+    inicio: DateTime<Local>,
+    /// Canales para enviarle instrucciones a los hilos. El primero publica
+    /// las estadísticas del servidor
+    canales_instrucciones: Vec<EmisorInstrucciones>,
+    /// Cada cuánto se publican las estadísticas en `$SYS.SERVER.<id>.STATSZ`
+    intervalo_estadisticas: Duration,
+    /// Cada cuánto se revisa si cambiaron los archivos de configuración y de
+    /// cuentas para recargarlos. Si es cero no se recargan
+    intervalo_recarga: Duration,
+    /// Mensajes retenidos de los clientes MQTT
+    retenidos: MensajesRetenidos,
+    /// Ids de mensaje recientes, compartidos con los hilos
+    deduplicador: Deduplicador,
+    /// Límites de tasa de las cuentas, compartidos con las conexiones
+    limites_cuentas: LimitesCuentas,
+    /// Servicio que autoriza a los usuarios que no están en las cuentas
+    autorizacion_externa: Option<AutorizacionExterna>,
+    /// Se activa para que el servidor deje de atender y detenga los hilos
+    detenido: Arc<AtomicBool>,
+}
+
+impl Servidor {
+    pub fn desde_configuracion(configuracion: Configuracion) -> Servidor {
+        // La cantidad es la cantidad de hilos que se van a crear
+        // Vector con los canales para enviar nuevas conexiones y handle de los threads
+        let mut hilos: Vec<InfoHilo> = Vec::new();
+        let mut cargas: Vec<CargaHilo> = Vec::new();
+
+        // Puntas emisoras de los canales para enviar mensajes a los hilos
+        let mut canales_enviar: Vec<EmisorInstrucciones> = Vec::new();
+        // Puntas receptoras de los canales para recibir mensajes de los hilos
+        let mut canales_recibir: Vec<ReceptorInstrucciones> = Vec::new();
+
+        // `logger`
+        let registrador: Registrador = Registrador::desde_configuracion(&configuracion);
+
+        let cantidad: usize = configuracion.obtener::<usize>("hilos").unwrap_or(4);
+
+        let id: String = configuracion
+            .obtener::<String>("id_servidor")
+            .unwrap_or_else(nuevo_id_servidor);
+
+        // Si hay mapeos inválidos no se aplica ninguno
+        let mapeos: Mapeos = Mapeos::desde_configuracion(&configuracion).unwrap_or_else(|e| {
+            registrador.error(&format!("Mapeos de tópicos inválidos: {}", e), None);
+            Mapeos::default()
+        });
+
+        let deduplicador: Deduplicador = Deduplicador::desde_configuracion(&configuracion)
+            .unwrap_or_else(|e| {
+                registrador.error(&format!("Deduplicación inválida: {}", e), None);
+                Deduplicador::default()
+            });
+
+        let limites_cuentas: LimitesCuentas = LimitesCuentas::desde_configuracion(&configuracion)
+            .unwrap_or_else(|e| {
+                registrador.error(
+                    &format!("Límites de tasa de cuentas inválidos: {}", e),
+                    None,
+                );
+                LimitesCuentas::default()
+            });
+
+        // Instrucciones que puede acumular la cola de cada hilo, y qué hacen
+        // los otros hilos cuando está llena
+        let capacidad_cola: usize = configuracion
+            .obtener::<usize>("capacidad_cola_hilos")
+            .unwrap_or(CAPACIDAD_COLA_POR_DEFECTO);
+        let politica_cola: PoliticaCola = configuracion
+            .obtener::<PoliticaCola>("politica_cola_hilos")
+            .unwrap_or_default();
+
+        // Creamos los canales para enviar y recibir instrucciones entre los hilos
+        for _ in 0..cantidad {
+            let (tx, rx) = canal_instrucciones(capacidad_cola);
+            canales_enviar.push(tx);
+            canales_recibir.push(rx);
+        }
+
+        // Para cada punta receptora en canales_recibir, se insertan las
+        // puntas emisoras de los canales en canales_a_enviar_mensajes que
+        // tiene las puntas emisoras a cada hilo para enviar instrucciones
+        // a ellos
+        for (indice_hilo, rx) in canales_recibir.drain(..).enumerate() {
+            // HashMap con las puntas emisoras a cada hilo para enviar instrucciones a los mismos
+            let mut canales_a_enviar_mensajes: HashMap<u64, EmisorInstrucciones> = HashMap::new();
+
+            // Insertamos las puntas emisoras de los canales en el HashMap
+            for (id_canal_a_enviar, tx) in canales_enviar.iter().enumerate() {
+                let id: u64 = id_canal_a_enviar as IdHilo;
+                // El id es el id del hilo. Yo quiero mandarle mensaje a todos los hilos.
+                // A cada id, le asigno un emisor a ese hilo. (id 2, le asigno un emisor al hilo 2)
+                canales_a_enviar_mensajes.insert(id, tx.clone());
+            }
+
+            // Obtengo el id del hilo
+            let id_hilo: u64 = indice_hilo as IdHilo; // Id del hilo actual. Suponiendo cronologia; 1, 2...
+
+            // Creamos el canal para enviar nuevas conexiones al hilo
+            let (tx_conexiones, rx_conexiones) = channel();
+            // La carga del hilo la actualiza el hilo y la lee el servidor
+            let carga: CargaHilo = CargaHilo::default();
+            cargas.push(carga.clone());
+            // Creamos el registrador para el hilo
+            let mut registrador: Registrador = registrador.con_objetivo("hilo");
+            // Establecemos el hilo actual para el registrador
+            registrador.establecer_hilo(id_hilo);
+            // Creamos el hilo
+            let hilo: Hilo = Hilo::new(
+                id_hilo,
+                rx_conexiones,
+                canales_a_enviar_mensajes,
+                rx,
+                registrador,
+                id.clone(),
+                carga.clone(),
+            )
+            .con_mapeos(mapeos.clone())
+            .con_deduplicador(deduplicador.clone())
+            .con_politica_cola(politica_cola);
+
+            // Iniciamos el thread del hilo
+            let handle: JoinHandle<()> = Hilo::iniciar(hilo);
+            // Tx_conexciones es por donde le van a asignar conexiones al hilo y el handle del hilo
+            hilos.push((tx_conexiones, handle));
+        }
+
+        let limites: LimitesServidor = LimitesServidor::desde_configuracion(&configuracion);
+        // Los que no entran en un `Duration` se toman como el valor por defecto
+        let intervalo_estadisticas: Duration = configuracion
+            .obtener::<f64>("intervalo_estadisticas")
+            .and_then(|segundos| Duration::try_from_secs_f64(segundos.max(0.)).ok())
+            .unwrap_or(Duration::from_secs(10));
+        let intervalo_recarga: Duration = configuracion
+            .obtener::<f64>("intervalo_recarga")
+            .and_then(|segundos| Duration::try_from_secs_f64(segundos.max(0.)).ok())
+            .unwrap_or(Duration::from_secs(2));
+        let autorizacion_externa: Option<AutorizacionExterna> =
+            AutorizacionExterna::desde_configuracion(&configuracion);
+
+        Servidor {
+            id,
+            estadisticas: Estadisticas::default(),
+            inicio: Local::now(),
+            canales_instrucciones: canales_enviar,
+            intervalo_estadisticas,
+            intervalo_recarga,
+            hilos,
+            limites,
+            conexiones_abiertas: ContadorConexiones::default(),
+            configuracion,
+            cargas,
+            ultimo_id_conexion: 0,
+            registrador,
+            cuentas: None,
+            retenidos: MensajesRetenidos::default(),
+            deduplicador,
+            limites_cuentas,
+            autorizacion_externa,
+            detenido: Arc::default(),
+        }
+    }
+
+    pub fn cargar_cuentas(&mut self, ruta_archivo_cuentas: String) -> io::Result<()> {
+        let cuentas: Vec<Cuenta> = Cuenta::cargar(&ruta_archivo_cuentas)?;
+        self.establecer_cuentas(cuentas);
+        Ok(())
+    }
+
+    /// Reemplaza las cuentas con las que se autentican los clientes
+    pub fn establecer_cuentas(&mut self, mut cuentas: Vec<Cuenta>) {
+        // Usuario de la cuenta de sistema, que puede suscribirse a `$SYS.>`
+        if let Some(cuenta_sistema) = self.configuracion.obtener::<String>("cuenta_sistema") {
+            for cuenta in cuentas.iter_mut() {
+                cuenta.sistema = cuenta.user == cuenta_sistema;
+            }
+        }
+
+        self.cuentas = Some(Arc::new(cuentas));
+    }
+
+    fn nuevo_id_conexion(&mut self) -> IdConexion {
+        self.ultimo_id_conexion += 1;
+        self.ultimo_id_conexion
+    }
+
+    pub fn iniciar(mut servidor: Servidor) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || servidor.inicio())
+    }
+
+    /// Escucha en todas las direcciones configuradas y atiende las conexiones.
+    /// Solo vuelve si no se pudo escuchar en alguna de ellas, o cuando se
+    /// detiene el servidor
+    pub fn inicio(&mut self) -> io::Result<()> {
+        let escuchas: Vec<(Escucha, Protocolo)> = self.escuchar()?;
+        self.atender(escuchas);
+        Ok(())
+    }
+
+    /// Acepta las conexiones de los listeners hasta que se detiene el
+    /// servidor. Después detiene los hilos, que cierran sus conexiones
+    fn atender(&mut self, escuchas: Vec<(Escucha, Protocolo)>) {
+        for (escucha, _) in escuchas.iter() {
+            self.registrador
+                .info(&format!("Escuchando en {}", escucha), None);
+        }
+
+        let mut ultimas_estadisticas: Instant = Instant::now();
+        let mut ultima_revision: Instant = Instant::now();
+        let mut vigilante: VigilanteArchivos = VigilanteArchivos::new(self.archivos_vigilados());
+
+        while !self.detenido.load(Ordering::Relaxed) {
+            if ultimas_estadisticas.elapsed() >= self.intervalo_estadisticas {
+                ultimas_estadisticas = Instant::now();
+                self.publicar_estadisticas();
+            }
+
+            if !self.intervalo_recarga.is_zero()
+                && ultima_revision.elapsed() >= self.intervalo_recarga
+            {
+                ultima_revision = Instant::now();
+                if vigilante.cambiaron() {
+                    match self.recargar() {
+                        Ok(()) => self.registrador.info("Configuración recargada", None),
+                        Err(e) => self.registrador.error(
+                            &format!("No se pudo recargar la configuración: {}", e),
+                            None,
+                        ),
+                    }
+                    vigilante = VigilanteArchivos::new(self.archivos_vigilados());
+                }
+            }
+
+            let mut aceptadas: usize = 0;
+            for (escucha, protocolo) in escuchas.iter() {
+                if self.aceptar(escucha, *protocolo) {
+                    aceptadas += 1;
+                }
+            }
+            // Sin conexiones nuevas no hace falta revisar los listeners sin pausa
+            if aceptadas == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        self.registrador.info("Deteniendo el servidor", None);
+        self.detener_hilos();
+    }
+
+    /// Les pide a los hilos que terminen y espera a que lo hagan
+    fn detener_hilos(&mut self) {
+        for canal in &self.canales_instrucciones {
+            let _ = canal.enviar(Instruccion::Detener);
+        }
+
+        for (_, handle) in self.hilos.drain(..) {
+            if handle.join().is_err() {
+                self.registrador.error("Un hilo terminó con un error", None);
+            }
+        }
+    }
+
+    /// Abre los listeners de la configuración: `puerto` (y los opcionales
+    /// `puerto_websocket` y `puerto_mqtt`) en cada una de las direcciones de
+    /// `direccion`, separadas por comas, y el socket Unix de `socket_unix`
+    fn escuchar(&self) -> io::Result<Vec<(Escucha, Protocolo)>> {
+        let direcciones: String = self
+            .configuracion
+            .obtener::<String>("direccion")
+            .unwrap_or("127.0.0.1".to_string());
+
+        let mut puertos: Vec<(u16, Protocolo)> = vec![(
+            self.configuracion.obtener::<u16>("puerto").unwrap_or(4222),
+            Protocolo::Nats,
+        )];
+        // Puertos opcionales para los clientes que se conectan por WebSocket
+        // (navegadores) y por MQTT
+        for (clave, protocolo) in [
+            ("puerto_websocket", Protocolo::WebSocket),
+            ("puerto_mqtt", Protocolo::Mqtt),
+        ] {
+            if let Some(puerto) = self.configuracion.obtener::<u16>(clave) {
+                puertos.push((puerto, protocolo));
+            }
+        }
+
+        let mut escuchas: Vec<(Escucha, Protocolo)> = Vec::new();
+
+        for direccion in direcciones.split(',').map(str::trim) {
+            if direccion.is_empty() {
+                continue;
+            }
+            for (puerto, protocolo) in puertos.iter() {
+                escuchas.push((Escucha::tcp(direccion, *puerto)?, *protocolo));
+            }
+        }
+
+        if let Some(ruta) = self.configuracion.obtener::<String>("socket_unix") {
+            #[cfg(unix)]
+            escuchas.push((Escucha::unix(&ruta)?, Protocolo::Nats));
+            #[cfg(not(unix))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Los sockets Unix no están soportados: {}", ruta),
+            ));
+        }
+
+        Ok(escuchas)
+    }
+
+    /// Acepta una conexión nueva, si hay, y se la asigna al hilo con menos
+    /// carga. Devuelve si había una conexión
+    fn aceptar(&mut self, escucha: &Escucha, protocolo: Protocolo) -> bool {
+        match escucha.aceptar() {
+            // Si escucho algo, genero una nueva conexion
+            Ok(stream) => {
+                let lugar: LugarConexion = match self
+                    .conexiones_abiertas
+                    .reservar(self.limites.max_conexiones)
+                {
+                    Some(lugar) => lugar,
+                    None => {
+                        self.rechazar_conexion(stream);
+                        return true;
+                    }
+                };
+
+                // La conexión va al hilo con menos carga
+                let indice_hilo: usize = menos_cargado(&self.cargas);
+
+                // Creamos una copia del logger para la nueva conexion
+                let mut registrador_para_nueva_conexion: Registrador =
+                    self.registrador.con_objetivo("conexion");
+                // Establecemos el hilo actual para la nueva conexion
+                registrador_para_nueva_conexion.establecer_hilo(indice_hilo as IdHilo);
+
+                // Generamos un nuevo id único para la nueva conexión
+                let id_conexion: u64 = self.nuevo_id_conexion();
+
+                // Los clientes de WebSocket usan el mismo protocolo dentro de
+                // tramas y los de MQTT se traducen
+                let stream: Box<dyn Stream> = match protocolo {
+                    Protocolo::Nats => stream,
+                    // Una trama tiene que poder llevar una publicación completa:
+                    // la línea de control, el payload y sus dos `\r\n`
+                    Protocolo::WebSocket => Box::new(StreamWebSocket::servidor(
+                        stream,
+                        self.limites.max_payload + self.limites.max_control_line + 4,
+                    )),
+                    Protocolo::Mqtt => Box::new(StreamMqtt::servidor(
+                        stream,
+                        self.retenidos.clone(),
+                        self.limites.max_payload + MARGEN_PAQUETE_MQTT,
+                    )),
+                };
+
+                let mut conexion: Conexion = Conexion::con_autorizacion_externa(
+                    id_conexion,
+                    stream,
+                    registrador_para_nueva_conexion,
+                    self.cuentas.clone(),
+                    self.autorizacion_externa,
+                    self.limites,
+                );
+                conexion.ocupar_lugar(lugar);
+                conexion.compartir_estadisticas(self.estadisticas.clone());
+                conexion.compartir_limites_cuentas(self.limites_cuentas.clone());
+
+                let (tx, _) = &self.hilos[indice_hilo];
+                match tx.send((id_conexion, conexion)) {
+                    // Envio la conexion al hilo
+                    Ok(_) => {
+                        self.cargas[indice_hilo].sumar_conexion();
+                    }
+                    Err(e) => {
+                        panic!("Error: {}", e);
+                    }
+                }
+
+                thread::sleep(Duration::from_micros(500));
+                true
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // No hay conexiones nuevas
+                false
+            }
+            // Por ejemplo, si se alcanzó el límite de archivos abiertos del proceso
+            Err(e) => {
+                self.registrador
+                    .error(&format!("No se pudo aceptar una conexión: {}", e), None);
+                false
+            }
+        }
+    }
+
+    /// La carga actual de cada hilo
+    pub fn estadisticas_hilos(&self) -> Vec<EstadisticasHilo> {
+        self.cargas
+            .iter()
+            .enumerate()
+            .map(|(id, carga)| carga.estadisticas(id as IdHilo))
+            .collect()
+    }
+
+    /// Archivos que se recargan cuando cambian: los de configuración y el de
+    /// cuentas. Los incluidos con `include=` no se vigilan
+    fn archivos_vigilados(&self) -> Vec<String> {
+        ["config", "cuentas"]
+            .iter()
+            .filter_map(|clave| self.configuracion.obtener::<String>(clave))
+            .flat_map(|rutas| {
+                rutas
+                    .split(',')
+                    .map(|ruta| ruta.trim().to_string())
+                    .collect::<Vec<String>>()
+            })
+            .filter(|ruta| !ruta.is_empty())
+            .collect()
+    }
+
+    /// Vuelve a leer el archivo de configuración y el de cuentas, y les envía
+    /// las cuentas y los límites nuevos a todos los hilos. Las conexiones
+    /// nuevas ya se crean con la configuración recargada. La cantidad de hilos,
+    /// la dirección y el puerto no cambian hasta reiniciar el servidor
+    pub fn recargar(&mut self) -> io::Result<()> {
+        // Todo se lee y se valida antes de cambiar algo: con cualquier error
+        // se conserva completo el estado anterior. Se parte de lo que no salió
+        // de los archivos, así las claves borradas del archivo dejan de estar
+        let mut configuracion: Configuracion = self.configuracion.sin_archivos();
+
+        if let Some(rutas) = configuracion.obtener::<String>("config") {
+            let mut archivos: Configuracion = Configuracion::new();
+            for ruta in rutas.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                archivos.fusionar(Configuracion::leer(ruta)?);
+            }
+
+            // Con una clave o un mapeo inválido se conserva la configuración
+            // anterior. Los valores del entorno y de los parámetros siguen
+            // teniendo prioridad
+            let mut errores: Vec<ErrorClave> = esquema().validar(&archivos);
+            configuracion.fusionar(archivos);
+            errores.extend(Mapeos::validar(&configuracion));
+            if !errores.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    ErrorConfiguracion::Claves(errores),
+                ));
+            }
+        }
+        esquema().aplicar_defectos(&mut configuracion);
+
+        let cuentas: Option<Vec<Cuenta>> = match configuracion.obtener::<String>("cuentas") {
+            Some(ruta) => Some(Cuenta::cargar(&ruta)?),
+            None => None,
+        };
+        let mapeos: Mapeos = Mapeos::desde_configuracion(&configuracion)?;
+        let reglas: ReglasDeduplicacion = Deduplicador::leer_reglas(&configuracion)?;
+        let tasas: TasasCuentas = LimitesCuentas::leer_tasas(&configuracion)?;
+
+        // Si se borró `cuentas` se deja de pedir usuario. Las cuentas que se
+        // establecieron sin la clave (en el servidor embebido) se conservan
+        let habia_cuentas: bool = self.configuracion.obtener::<String>("cuentas").is_some();
+        self.configuracion = configuracion;
+        match cuentas {
+            Some(cuentas) => self.establecer_cuentas(cuentas),
+            None if habia_cuentas => self.cuentas = None,
+            None => {}
+        }
+        self.limites = LimitesServidor::desde_configuracion(&self.configuracion);
+        self.deduplicador.reemplazar(reglas);
+        self.limites_cuentas.reemplazar(tasas);
+        self.autorizacion_externa = AutorizacionExterna::desde_configuracion(&self.configuracion);
+
+        let recarga = Recarga {
+            cuentas: self.cuentas.clone(),
+            limites: self.limites,
+            mapeos,
+        };
+
+        for canal in &self.canales_instrucciones {
+            // Se espera a que haya lugar: la recarga no se puede perder
+            if let Envio::Cerrada = canal.enviar(Instruccion::Recargar(recarga.clone())) {
+                self.registrador
+                    .error("No se pudo enviar la recarga a un hilo", None);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Publica las estadísticas del servidor en `$SYS.SERVER.<id>.STATSZ`
+    fn publicar_estadisticas(&self) {
+        let estadisticas = EventoEstadisticas {
+            servidor: self.id.clone(),
+            momento: Local::now().to_rfc3339(),
+            inicio: self.inicio.to_rfc3339(),
+            hilos: self.estadisticas_hilos(),
+            conexiones: self.conexiones_abiertas.abiertas(),
+            conexiones_totales: self.ultimo_id_conexion,
+            contadores: self.estadisticas.contadores(),
+        };
+
+        let json: String = serde_json::to_string(&estadisticas).unwrap_or("{}".to_string());
+        let publicacion = Publicacion::new(topico_estadisticas(&self.id), json.into(), None, None);
+
+        // Si la cola del hilo está llena se pierden estas estadísticas, no
+        // vale la pena frenar al servidor por ellas
+        let envio: Envio = self.canales_instrucciones[0]
+            .intentar_enviar(Instruccion::NuevaPublicacion(publicacion));
+        if !matches!(envio, Envio::Enviada) {
+            self.registrador
+                .advertencia("No se pudieron publicar las estadísticas", None);
+        }
+    }
+
+    /// Se alcanzó el máximo de conexiones: se le avisa al cliente y se cierra la conexión
+    fn rechazar_conexion(&self, mut stream: Box<dyn Stream>) {
+        self.registrador.advertencia(
+            &format!(
+                "Conexión rechazada, se alcanzó el máximo de {} conexiones",
+                self.limites.max_conexiones
+            ),
+            None,
+        );
+
+        let error = Respuesta::Err(Some(ErrorProtocolo::MaximoConexiones.to_string()));
+        _ = stream.write_all(&error.serializar());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use lib::configuracion::Configuracion;
+
+    use super::Servidor;
+
+    #[test]
+    fn probar_recarga_invalida() {
+        let directorio = std::env::temp_dir().join(format!("recarga-{}", std::process::id()));
+        fs::create_dir_all(&directorio).unwrap();
+        let config = directorio.join("config.txt");
+        let cuentas = directorio.join("cuentas.csv");
+        fs::write(&cuentas, "1,admin,1234").unwrap();
+        fs::write(
+            &config,
+            format!("max_payload=100\ncuentas={}", cuentas.display()),
+        )
+        .unwrap();
+
+        let mut servidor = Servidor::desde_configuracion(Configuracion::parsear(&format!(
+            "config={}\nhilos=1",
+            config.display()
+        )));
+        servidor.recargar().unwrap();
+        assert_eq!(servidor.limites.max_payload, 100);
+
+        // Con un mapeo inválido no se aplica nada de lo demás
+        fs::write(&cuentas, "1,otro,1").unwrap();
+        fs::write(
+            &config,
+            format!(
+                "max_payload=200\ncuentas={}\nmapeo.a.>.b=c",
+                cuentas.display()
+            ),
+        )
+        .unwrap();
+        let error = servidor.recargar().unwrap_err();
+        assert!(error.to_string().contains("mapeo.a.>.b"), "{}", error);
+
+        assert_eq!(servidor.limites.max_payload, 100);
+        assert_eq!(
+            servidor.configuracion.obtener::<usize>("max_payload"),
+            Some(100)
+        );
+        assert_eq!(
+            servidor.configuracion.obtener::<String>("mapeo.a.>.b"),
+            None
+        );
+        assert_eq!(servidor.cuentas.as_ref().unwrap()[0].user, "admin");
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn probar_recarga_sin_claves_borradas() {
+        let directorio =
+            std::env::temp_dir().join(format!("recarga-borradas-{}", std::process::id()));
+        fs::create_dir_all(&directorio).unwrap();
+        let config = directorio.join("config.txt");
+        fs::write(
+            &config,
+            "max_payload=100\nmapeo.a=b\nlimite_cuenta.dron.mensajes_por_segundo=5",
+        )
+        .unwrap();
+
+        let mut servidor = Servidor::desde_configuracion(Configuracion::parsear(&format!(
+            "config={}\nhilos=1\nmax_control_line=2048\nintervalo_recarga=1e30",
+            config.display()
+        )));
+        servidor.recargar().unwrap();
+        assert_eq!(servidor.configuracion.con_prefijo("mapeo.").len(), 1);
+        assert!(servidor.limites_cuentas.de_usuario("dron").is_some());
+
+        // Lo que se borró del archivo deja de aplicarse; los parámetros quedan
+        fs::write(&config, "hilos=3").unwrap();
+        servidor.recargar().unwrap();
+        assert!(servidor.configuracion.con_prefijo("mapeo.").is_empty());
+        assert!(servidor.limites_cuentas.de_usuario("dron").is_none());
+        assert_ne!(servidor.limites.max_payload, 100);
+        assert_eq!(servidor.configuracion.obtener::<usize>("hilos"), Some(1));
+        assert_eq!(servidor.limites.max_control_line, 2048);
+        assert_eq!(servidor.intervalo_recarga, Duration::from_secs(2));
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
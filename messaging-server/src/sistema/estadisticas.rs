@@ -0,0 +1,73 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use serde::Serialize;
+
+/// Mensajes y bytes (headers y payload) que entraron y salieron
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Contadores {
+    pub mensajes_recibidos: u64,
+    pub bytes_recibidos: u64,
+    pub mensajes_enviados: u64,
+    pub bytes_enviados: u64,
+    /// Publicaciones que superaron el límite de tasa (demoradas o rechazadas)
+    pub mensajes_limitados: u64,
+}
+
+impl Contadores {
+    pub fn sumar_recibido(&mut self, bytes: usize) {
+        self.mensajes_recibidos += 1;
+        self.bytes_recibidos += bytes as u64;
+    }
+
+    pub fn sumar_enviado(&mut self, bytes: usize) {
+        self.mensajes_enviados += 1;
+        self.bytes_enviados += bytes as u64;
+    }
+
+    pub fn sumar_limitado(&mut self) {
+        self.mensajes_limitados += 1;
+    }
+}
+
+/// Contadores de todo el servidor, compartidos entre todas las conexiones de
+/// todos los hilos
+#[derive(Debug, Clone, Default)]
+pub struct Estadisticas {
+    mensajes_recibidos: Arc<AtomicU64>,
+    bytes_recibidos: Arc<AtomicU64>,
+    mensajes_enviados: Arc<AtomicU64>,
+    bytes_enviados: Arc<AtomicU64>,
+    mensajes_limitados: Arc<AtomicU64>,
+}
+
+impl Estadisticas {
+    pub fn sumar_recibido(&self, bytes: usize) {
+        self.mensajes_recibidos.fetch_add(1, Ordering::Relaxed);
+        self.bytes_recibidos
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sumar_enviado(&self, bytes: usize) {
+        self.mensajes_enviados.fetch_add(1, Ordering::Relaxed);
+        self.bytes_enviados
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sumar_limitado(&self) {
+        self.mensajes_limitados.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Los valores de los contadores en este momento
+    pub fn contadores(&self) -> Contadores {
+        Contadores {
+            mensajes_recibidos: self.mensajes_recibidos.load(Ordering::Relaxed),
+            bytes_recibidos: self.bytes_recibidos.load(Ordering::Relaxed),
+            mensajes_enviados: self.mensajes_enviados.load(Ordering::Relaxed),
+            bytes_enviados: self.bytes_enviados.load(Ordering::Relaxed),
+            mensajes_limitados: self.mensajes_limitados.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::Local;
+use serde::Serialize;
+
+use crate::{conexion::id::IdConexion, hilo::carga::EstadisticasHilo, publicacion::Publicacion};
+
+use super::{estadisticas::Contadores, PREFIJO_SISTEMA};
+
+/// Eventos de los clientes que el servidor publica en
+/// `$SYS.SERVER.<id>.CLIENT.<tipo>`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TipoEvento {
+    /// El cliente se autenticó (`CONNECT`)
+    Conexion,
+    /// Se cerró la conexión de un cliente que se había autenticado (`DISCONNECT`)
+    Desconexion,
+    /// El cliente envió credenciales incorrectas (`AUTH.ERR`)
+    ErrorAutenticacion,
+}
+
+impl TipoEvento {
+    pub fn topico(&self, id_servidor: &str) -> String {
+        let tipo: &str = match self {
+            TipoEvento::Conexion => "CONNECT",
+            TipoEvento::Desconexion => "DISCONNECT",
+            TipoEvento::ErrorAutenticacion => "AUTH.ERR",
+        };
+
+        format!("{}.SERVER.{}.CLIENT.{}", PREFIJO_SISTEMA, id_servidor, tipo)
+    }
+}
+
+/// Lo que se publica en cada evento de un cliente (como JSON)
+#[derive(Debug, Clone, Serialize)]
+pub struct EventoCliente {
+    pub servidor: String,
+    /// Fecha y hora del evento (RFC 3339)
+    pub momento: String,
+    pub id_conexion: IdConexion,
+    /// El nombre que envió el cliente en el CONNECT
+    pub nombre: Option<String>,
+    pub user: Option<String>,
+    /// Tópicos de las suscripciones activas del cliente
+    pub suscripciones: Vec<String>,
+    #[serde(flatten)]
+    pub contadores: Contadores,
+    /// Por qué se cerró la conexión o se rechazó la autenticación
+    pub razon: Option<String>,
+}
+
+impl EventoCliente {
+    pub fn new(servidor: &str, id_conexion: IdConexion) -> Self {
+        Self {
+            servidor: servidor.to_string(),
+            momento: Local::now().to_rfc3339(),
+            id_conexion,
+            nombre: None,
+            user: None,
+            suscripciones: Vec::new(),
+            contadores: Contadores::default(),
+            razon: None,
+        }
+    }
+
+    pub fn publicacion(&self, tipo: TipoEvento) -> Publicacion {
+        let json: String = serde_json::to_string(self).unwrap_or("{}".to_string());
+        Publicacion::new(tipo.topico(&self.servidor), json.into(), None, None)
+    }
+}
+
+/// Lo que se publica periódicamente en `$SYS.SERVER.<id>.STATSZ`
+#[derive(Debug, Clone, Serialize)]
+pub struct EventoEstadisticas {
+    pub servidor: String,
+    pub momento: String,
+    /// Cuándo se inició el servidor (RFC 3339)
+    pub inicio: String,
+    /// Conexiones, mensajes por segundo y bytes pendientes de cada hilo
+    pub hilos: Vec<EstadisticasHilo>,
+    /// Conexiones abiertas en este momento
+    pub conexiones: usize,
+    /// Conexiones aceptadas desde que se inició el servidor
+    pub conexiones_totales: u64,
+    #[serde(flatten)]
+    pub contadores: Contadores,
+}
@@ -0,0 +1,37 @@
+pub mod estadisticas;
+pub mod evento;
+pub mod traza;
+
+/// Primer token de los tópicos en los que el servidor publica sus eventos
+pub const PREFIJO_SISTEMA: &str = "$SYS";
+
+/// Si el tópico (o patrón) es uno de los eventos del servidor. Solo los
+/// usuarios de la cuenta de sistema pueden suscribirse a estos tópicos
+pub fn es_topico_de_sistema(topico: &str) -> bool {
+    topico.split('.').next() == Some(PREFIJO_SISTEMA)
+}
+
+/// `$SYS.SERVER.<id>.STATSZ`, donde se publican periódicamente las estadísticas
+pub fn topico_estadisticas(id_servidor: &str) -> String {
+    format!("{}.SERVER.{}.STATSZ", PREFIJO_SISTEMA, id_servidor)
+}
+
+/// Genera un identificador al azar para el servidor, con el mismo formato que
+/// los de NATS (56 letras mayúsculas y números)
+pub fn nuevo_id_servidor() -> String {
+    nuevo_id_aleatorio()
+}
+
+/// 56 letras mayúsculas y números al azar, imposibles de adivinar
+pub fn nuevo_id_aleatorio() -> String {
+    use rand::{distributions::Uniform, Rng};
+
+    const CARACTERES: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let distribucion = Uniform::from(0..CARACTERES.len());
+
+    rand::thread_rng()
+        .sample_iter(distribucion)
+        .take(56)
+        .map(|i| CARACTERES[i] as char)
+        .collect()
+}
@@ -0,0 +1,144 @@
+use chrono::Local;
+use serde::Serialize;
+
+use crate::{
+    conexion::id::IdConexion,
+    hilo::id::IdHilo,
+    publicacion::Publicacion,
+    suscripciones::{id::IdSuscripcion, suscripcion::Suscripcion},
+};
+
+/// Lo que publica cada hilo (como JSON) en el tópico de `Nats-Trace-Dest`
+/// por cada paso de una publicación trazada
+#[derive(Debug, Clone, Serialize)]
+pub struct EventoTraza {
+    pub servidor: String,
+    /// Fecha y hora del paso (RFC 3339)
+    pub momento: String,
+    /// Hilo en el que ocurrió el paso
+    pub hilo: IdHilo,
+    /// Tópico de la publicación (ya mapeado, salvo en el ingreso)
+    pub topico: String,
+    #[serde(flatten)]
+    pub salto: Salto,
+}
+
+/// Los pasos de una publicación por el servidor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "salto", rename_all = "snake_case")]
+pub enum Salto {
+    /// La publicó un cliente. Si es `duplicado` no sigue
+    Ingreso {
+        conexion: IdConexion,
+        duplicado: bool,
+    },
+    /// Se mapeó el tópico y se eligieron los hilos con suscripciones y un
+    /// suscriptor de cada queue group
+    Ruteo {
+        /// El tópico publicado, si un mapeo lo cambió
+        topico_original: Option<String>,
+        hilos: Vec<IdHilo>,
+        grupos: Vec<SeleccionGrupo>,
+    },
+    /// La cola del hilo de destino estaba llena y se descartó la publicación
+    Descarte { hilo_destino: IdHilo },
+    /// El hilo escribió la publicación a sus suscripciones
+    Entrega { suscripciones: Vec<EntregaTraza> },
+}
+
+/// El suscriptor que se eligió en un queue group
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeleccionGrupo {
+    pub grupo: Option<IdSuscripcion>,
+    pub hilo: IdHilo,
+    pub conexion: IdConexion,
+    pub sid: IdSuscripcion,
+}
+
+impl SeleccionGrupo {
+    pub fn new(suscripcion: &Suscripcion) -> Self {
+        Self {
+            grupo: suscripcion.id_grupo().cloned(),
+            hilo: *suscripcion.id_hilo(),
+            conexion: *suscripcion.id_conexion(),
+            sid: suscripcion.id().to_owned(),
+        }
+    }
+}
+
+/// Resultado de escribir la publicación a una suscripción
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntregaTraza {
+    pub conexion: IdConexion,
+    pub sid: IdSuscripcion,
+    pub grupo: Option<IdSuscripcion>,
+    /// Si se escribió al socket o quedó en los bytes pendientes de la conexión
+    pub entregado: bool,
+    pub error: Option<String>,
+}
+
+impl EntregaTraza {
+    pub fn new(suscripcion: &Suscripcion, error: Option<String>) -> Self {
+        Self {
+            conexion: *suscripcion.id_conexion(),
+            sid: suscripcion.id().to_owned(),
+            grupo: suscripcion.id_grupo().cloned(),
+            entregado: error.is_none(),
+            error,
+        }
+    }
+}
+
+impl EventoTraza {
+    pub fn new(servidor: &str, hilo: IdHilo, topico: &str, salto: Salto) -> Self {
+        Self {
+            servidor: servidor.to_string(),
+            momento: Local::now().to_rfc3339(),
+            hilo,
+            topico: topico.to_string(),
+            salto,
+        }
+    }
+
+    pub fn publicacion(&self, destino: String) -> Publicacion {
+        let json: String = serde_json::to_string(self).unwrap_or("{}".to_string());
+        Publicacion::new(destino, json.into(), None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::publicacion::Publicacion;
+
+    use super::{EventoTraza, Salto};
+
+    #[test]
+    fn probar_evento_de_traza() {
+        let header = b"NATS/1.0\r\nnats-trace-dest: trazas.1\r\n\r\n";
+        let publicacion = Publicacion::new(
+            "a".to_string(),
+            "hola".into(),
+            Some(header.as_slice().into()),
+            None,
+        );
+        assert_eq!(publicacion.destino_traza(), Some("trazas.1".to_string()));
+        assert_eq!(
+            Publicacion::new("a".to_string(), "hola".into(), None, None).destino_traza(),
+            None
+        );
+
+        let salto = Salto::Ingreso {
+            conexion: 3,
+            duplicado: false,
+        };
+        let traza = EventoTraza::new("S", 1, "a", salto).publicacion("trazas.1".to_string());
+        assert_eq!(traza.topico, "trazas.1");
+        assert!(traza.header.is_none());
+
+        let json: serde_json::Value = serde_json::from_slice(&traza.payload).unwrap();
+        assert_eq!(json["salto"], "ingreso");
+        assert_eq!(json["conexion"], 3);
+        assert_eq!(json["hilo"], 1);
+        assert_eq!(json["topico"], "a");
+    }
+}
@@ -4,13 +4,17 @@ use crate::{conexion::id::IdConexion, hilo::id::IdHilo};
 
 use self::{grupo::Grupo, id::IdSuscripcion, suscripcion::Suscripcion};
 
+/// Un queue group se identifica por el tópico y el nombre del grupo, dos
+/// suscripciones con el mismo nombre de grupo en tópicos distintos no compiten
+type ClaveGrupo = (String, IdSuscripcion);
+
 pub mod grupo;
 pub mod id;
 pub mod suscripcion;
 pub mod topico;
 pub struct Suscripciones {
     suscripciones: HashSet<Suscripcion>,
-    grupos: HashMap<IdSuscripcion, Grupo>,
+    grupos: HashMap<ClaveGrupo, Grupo>,
 }
 
 impl Default for Suscripciones {
@@ -59,7 +63,8 @@ impl Suscripciones {
     }
 
     fn suscribir_grupo(&mut self, suscripcion: Suscripcion, id_grupo: &IdSuscripcion) {
-        let grupo: &mut Grupo = self.grupos.entry(id_grupo.to_owned()).or_insert(Grupo::new(
+        let clave: ClaveGrupo = (suscripcion.topico().a_texto(), id_grupo.to_owned());
+        let grupo: &mut Grupo = self.grupos.entry(clave).or_insert(Grupo::new(
             id_grupo.to_owned(),
             suscripcion.topico().clone(),
         ));
@@ -68,8 +73,15 @@ impl Suscripciones {
     }
 
     fn desuscribir_grupo(&mut self, suscripcion: &Suscripcion, id_grupo: &IdSuscripcion) {
-        if let Some(grupo) = self.grupos.get_mut(id_grupo) {
+        let clave: ClaveGrupo = (suscripcion.topico().a_texto(), id_grupo.to_owned());
+
+        if let Some(grupo) = self.grupos.get_mut(&clave) {
             grupo.desuscribir(suscripcion);
+
+            // Si no quedan miembros, el grupo deja de existir
+            if grupo.es_vacio() {
+                self.grupos.remove(&clave);
+            }
         }
     }
 
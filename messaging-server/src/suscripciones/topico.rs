@@ -1,5 +1,9 @@
 use std::hash::Hash;
 
+use lib::topicos::{self, ErrorTopico};
+
+use crate::sistema::es_topico_de_sistema;
+
 #[derive(Debug, Clone)]
 enum Segmento {
     Texto(String),
@@ -14,15 +18,15 @@ pub struct Topico {
 }
 
 impl Topico {
-    pub fn new(patron: String) -> Result<Self, String> {
+    /// Crea el tópico a partir del patrón de una suscripción, que tiene que
+    /// ser válido según `topicos::validar_suscripcion`
+    pub fn new(patron: String) -> Result<Self, ErrorTopico> {
+        topicos::validar_suscripcion(&patron, false)?;
+
         let mut segmentos = Vec::new();
         let mut exacto = true;
 
         for str in patron.split('.') {
-            if !exacto {
-                return Err("Patrón no válido".to_string());
-            }
-
             if str.eq("*") {
                 segmentos.push(Segmento::Asteriso);
             } else if str.eq(">") {
@@ -48,6 +52,13 @@ impl Topico {
             return false;
         }
 
+        // Los eventos del servidor solo le llegan a quien se suscribe
+        // explícitamente a `$SYS`, no a los patrones que empiezan con comodín
+        if es_topico_de_sistema(subject) && !matches!(self.patron.first(), Some(Segmento::Texto(_)))
+        {
+            return false;
+        }
+
         for (i, segmento) in segmentos.iter().enumerate() {
             if i >= self.patron.len() {
                 return true;
@@ -96,3 +107,21 @@ impl PartialEq for Topico {
 }
 
 impl Eq for Topico {}
+
+#[cfg(test)]
+mod tests {
+    use super::Topico;
+
+    #[test]
+    fn probar_comodines_no_incluyen_sistema() {
+        let todo = Topico::new(">".to_string()).unwrap();
+        let sistema = Topico::new("$SYS.>".to_string()).unwrap();
+
+        assert!(todo.test("a.b"));
+        assert!(!todo.test("$SYS.SERVER.A.STATSZ"));
+        assert!(!Topico::new("*.SERVER.>".to_string())
+            .unwrap()
+            .test("$SYS.SERVER.A.STATSZ"));
+        assert!(sistema.test("$SYS.SERVER.A.STATSZ"));
+    }
+}
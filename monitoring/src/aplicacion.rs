@@ -62,6 +62,7 @@ impl Aplicacion {
         enviar_comando: Sender<Comando>,
         recibir_estado: Receiver<Estado>,
         contexto: Context,
+        configuracion: Configuracion,
     ) -> Self {
         egui_extras::install_image_loaders(&contexto);
 
@@ -76,7 +77,7 @@ impl Aplicacion {
             enviar_comando,
             listar: Listar::Incidentes,
             accion: Accion::Incidente(AccionIncidente::Crear),
-            configuracion: Configuracion::desde_argv().unwrap_or_default(),
+            configuracion,
         }
     }
 
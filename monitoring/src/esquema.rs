@@ -0,0 +1,16 @@
+use lib::configuracion::esquema::{Clave, Esquema, TipoValor};
+use messaging_client::cliente::opciones::OpcionesConexion;
+
+/// Las claves de configuración de la aplicación de monitoreo
+pub fn esquema() -> Esquema {
+    Esquema::new("monitoring", "Aplicación de monitoreo de incidentes")
+        .con_clave(
+            Clave::new(
+                "incidentes",
+                TipoValor::Texto,
+                "Archivo csv donde se guardan los incidentes",
+            )
+            .con_defecto("incidentes.csv"),
+        )
+        .con_claves(OpcionesConexion::claves())
+}
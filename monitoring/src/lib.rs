@@ -1,4 +1,5 @@
 pub mod accion;
 pub mod aplicacion;
+pub mod esquema;
 pub mod funcionamiento;
 pub mod sistema;
@@ -1,13 +1,22 @@
+use lib::configuracion::Configuracion;
 use monitoring::aplicacion::Aplicacion;
+use monitoring::esquema::esquema;
 use monitoring::sistema::intentar_iniciar_sistema;
 use std::{process, sync::mpsc::channel, thread};
 
 fn main() -> Result<(), eframe::Error> {
+    // Antes de abrir la ventana, para mostrar la ayuda o los errores de la
+    // configuración en la terminal
+    let configuracion: Configuracion = esquema().desde_argv_o_terminar();
+    let configuracion_sistema: Configuracion = configuracion.clone();
+
     let (enviar_comando, recibir_comando) = channel();
     let (enviar_estado, recibir_estado) = channel();
 
     thread::spawn(move || {
-        if let Err(e) = intentar_iniciar_sistema(recibir_comando, enviar_estado) {
+        if let Err(e) =
+            intentar_iniciar_sistema(configuracion_sistema, recibir_comando, enviar_estado)
+        {
             eprintln!("Error al iniciar el sistema: {}", e);
             process::exit(1);
         }
@@ -21,6 +30,7 @@ fn main() -> Result<(), eframe::Error> {
                 enviar_comando,
                 recibir_estado,
                 cc.egui_ctx.clone(),
+                configuracion,
             ))
         }),
     )
@@ -21,7 +21,7 @@ use std::{
 use {
     self::{comando::Comando, estado::Estado},
     drone::dron::Dron,
-    messaging_client::cliente::{suscripcion::Suscripcion, Cliente},
+    messaging_client::cliente::{opciones::OpcionesConexion, suscripcion::Suscripcion, Cliente},
 };
 
 pub mod comando;
@@ -38,12 +38,12 @@ pub struct Sistema {
 
 /// Crea un nuevo sistema e intenta iniciarlo.
 pub fn intentar_iniciar_sistema(
+    configuracion: Configuracion,
     recibir_comando: Receiver<Comando>,
     enviar_estado: Sender<Estado>,
 ) -> io::Result<()> {
     let estado: Estado = Estado::new();
 
-    let configuracion: Configuracion = Configuracion::desde_argv()?;
     let mut sistema: Sistema = Sistema::new(estado, configuracion, recibir_comando, enviar_estado);
 
     sistema.iniciar()?;
@@ -115,8 +115,7 @@ impl Sistema {
             self.estado.mensaje_error = None;
             drop(sub_conectado);
         } else {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 "No se pudo conectar al sistema".to_string(),
             ));
         }
@@ -151,23 +150,13 @@ impl Sistema {
 
     /// Conectar el cliente con usuario y contraeña.
     fn conectar(&self) -> io::Result<Cliente> {
-        let direccion = self
-            .configuracion
-            .obtener::<String>("direccion")
-            .unwrap_or("127.0.0.1".to_string());
-
-        let puerto = self.configuracion.obtener::<u16>("puerto").unwrap_or(4222);
-
-        println!("Conectando al servidor de NATS en {}:{}", direccion, puerto);
+        let opciones: OpcionesConexion = OpcionesConexion::desde_configuracion(&self.configuracion);
+        println!(
+            "Conectando al servidor de NATS en {}",
+            opciones.servidores.join(", ")
+        );
 
-        let user: Option<String> = self.configuracion.obtener::<String>("user");
-        let pass: Option<String> = self.configuracion.obtener::<String>("pass");
-
-        if user.is_some() || pass.is_some() {
-            Cliente::conectar_con_user_y_pass(&format!("{}:{}", direccion, puerto), user, pass)
-        } else {
-            Cliente::conectar(&format!("{}:{}", direccion, puerto))
-        }
+        Cliente::conectar_con_opciones(opciones)
     }
 
     /// Publica el estado general del sistema y lo guarda en un archivo
@@ -376,7 +365,7 @@ impl Sistema {
                     self.estado.conectado = false;
                     self.configuracion = Configuracion::default();
                     self.actualizar_estado_ui()?;
-                    return Err(io::Error::new(io::ErrorKind::Other, "".to_string()));
+                    return Err(io::Error::other("".to_string()));
                 }
                 Comando::CamaraNuevoRango(id, rango) => {
                     if let Some(_camara) = self.estado.camara(id) {
@@ -450,12 +439,9 @@ impl Sistema {
 
     /// Actualiza el estado de la interfaz de usuario
     fn actualizar_estado_ui(&self) -> io::Result<()> {
-        self.enviar_estado.send(self.estado.clone()).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error al enviar estado a la interfaz: {}", e),
-            )
-        })
+        self.enviar_estado
+            .send(self.estado.clone())
+            .map_err(|e| io::Error::other(format!("Error al enviar estado a la interfaz: {}", e)))
     }
 
     /// Solicita la actualización de las cámaras al servidor de NATS.